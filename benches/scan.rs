@@ -0,0 +1,84 @@
+//! Times a full repository scan on a synthetic monorepo, single-threaded and in parallel.
+//!
+//! Run with `cargo bench --bench scan`; set `PYBIA_BENCH_FILES` to change the tree size.
+
+use file_watcher::dependency::DependencyGraph;
+use file_watcher::scan::RepositoryScan;
+use file_watcher::service::detector::ServiceDetector;
+use file_watcher::utils::PathPatterns;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const SERVICES: usize = 50;
+
+/// Write `files` files spread over `SERVICES` services, each a package of modules importing
+/// their neighbours and a shared library, plus requirements and non-Python assets
+fn create_tree(root: &Path, files: usize) {
+    let per_service = files / SERVICES;
+    for service in 0..SERVICES {
+        let dir = root.join(format!("services/svc{service}"));
+        let package = dir.join(format!("svc{service}"));
+        std::fs::create_dir_all(&package).unwrap();
+        std::fs::write(dir.join("pyproject.toml"), format!("[project]\nname = \"svc{service}\"\ndependencies = [\"requests>=2.0\"]\n")).unwrap();
+        std::fs::write(dir.join("requirements.txt"), "requests==2.31.0\nflask>=2.0\n").unwrap();
+        std::fs::write(package.join("__init__.py"), "").unwrap();
+
+        for file in 0..per_service.saturating_sub(3) {
+            let sub = package.join(format!("mod{}", file / 100));
+            if file % 100 == 0 {
+                std::fs::create_dir_all(&sub).unwrap();
+                std::fs::write(sub.join("__init__.py"), "").unwrap();
+            }
+            if file % 5 == 4 {
+                std::fs::write(sub.join(format!("data{file}.json")), "{}\n").unwrap();
+                continue;
+            }
+            let content = format!(
+                "import os\nimport requests\nfrom svc{service}.mod{} import m{}\nimport svc{}.mod0.m0\n\n\ndef f{file}():\n    return os.getcwd()\n",
+                file / 100,
+                file.saturating_sub(1),
+                (service + 1) % SERVICES,
+            );
+            std::fs::write(sub.join(format!("m{file}.py")), content).unwrap();
+        }
+    }
+}
+
+/// One full analysis: walk, detect services, parse and build the graph
+fn analyse(root: &Path) -> usize {
+    let ignore = PathPatterns::default();
+    let mut scan = RepositoryScan::walk(root, &ignore);
+    let services = ServiceDetector::new(Vec::new()).detect_in(&scan).unwrap().services;
+    scan.parse(None);
+    let mut graph = DependencyGraph::new();
+    graph.build_from_scan(&scan, services).unwrap();
+    scan.entries.len()
+}
+
+fn time(threads: usize, root: &Path) -> Duration {
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+    // Best of three, after a warm-up run to fill the page cache
+    pool.install(|| analyse(root));
+    (0..3)
+        .map(|_| {
+            let start = Instant::now();
+            pool.install(|| analyse(root));
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let files = std::env::var("PYBIA_BENCH_FILES").ok().and_then(|n| n.parse().ok()).unwrap_or(50_000);
+    let temp = tempfile::tempdir().unwrap();
+    create_tree(temp.path(), files);
+    let threads = rayon::current_num_threads();
+    println!("synthetic tree: {} files in {} services", files, SERVICES);
+
+    let sequential = time(1, temp.path());
+    let parallel = time(threads, temp.path());
+    println!("1 thread:     {:>8.1?}", sequential);
+    println!("{:>2} thread(s): {:>8.1?}", threads, parallel);
+    println!("speedup:      {:>8.2}x", sequential.as_secs_f64() / parallel.as_secs_f64());
+}
@@ -0,0 +1,117 @@
+pub mod models;
+
+#[cfg(test)]
+mod tests;
+
+use models::{CacheData, CachedFile, CachedServices, ParsedFile};
+use crate::service::models::ServiceDetection;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_CACHE_DIR: &str = ".pybia/cache";
+const CACHE_FILE_NAME: &str = "analysis.json";
+/// Bumped whenever the cached data or the parsers producing it change shape
+const CACHE_FORMAT: u32 = 4;
+
+fn cache_version() -> String {
+    format!("{}+{}", env!("CARGO_PKG_VERSION"), CACHE_FORMAT)
+}
+
+/// Hex SHA-256 of `content`
+pub fn content_hash(content: &[u8]) -> String {
+    Sha256::digest(content).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Parse results and the service map from earlier runs, reused while file contents are unchanged
+#[derive(Debug)]
+pub struct AnalysisCache {
+    dir: PathBuf,
+    data: CacheData,
+    // Files looked up this run; entries for any other file are dropped on save
+    used: HashSet<PathBuf>,
+    hits: usize,
+    misses: usize,
+}
+
+impl AnalysisCache {
+    /// Load the cache in `dir`, starting empty if it is missing, unreadable or from another version
+    pub fn open(dir: &Path) -> Self {
+        let data = std::fs::read(dir.join(CACHE_FILE_NAME))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<CacheData>(&bytes).ok())
+            .filter(|data| data.version == cache_version())
+            .unwrap_or_else(|| CacheData { version: cache_version(), ..CacheData::default() });
+        Self {
+            dir: dir.to_path_buf(),
+            data,
+            used: HashSet::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// The parse result for `path`, reusing the cached one when `content` is unchanged
+    pub fn parsed(&mut self, path: &Path, content: &str, parse: impl FnOnce() -> ParsedFile) -> ParsedFile {
+        let hash = content_hash(content.as_bytes());
+        if let Some(parsed) = self.lookup(path, &hash).cloned() {
+            self.store(path, hash, parsed.clone(), true);
+            return parsed;
+        }
+        let parsed = parse();
+        self.store(path, hash, parsed.clone(), false);
+        parsed
+    }
+
+    /// The cached parse result for `path` if it was parsed from contents hashing to `hash`
+    pub fn lookup(&self, path: &Path, hash: &str) -> Option<&ParsedFile> {
+        self.data.files.get(path)
+            .filter(|cached| cached.hash == hash)
+            .map(|cached| &cached.parsed)
+    }
+
+    /// Record the parse result for `path`; `hit` marks one that came from `lookup`
+    pub fn store(&mut self, path: &Path, hash: String, parsed: ParsedFile, hit: bool) {
+        self.used.insert(path.to_path_buf());
+        if hit {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+            self.data.files.insert(path.to_path_buf(), CachedFile { hash, parsed });
+        }
+    }
+
+    /// The cached service detection, if it was made with the same `fingerprint`
+    pub fn services(&self, fingerprint: &str) -> Option<&ServiceDetection> {
+        self.data.services.as_ref()
+            .filter(|cached| cached.fingerprint == fingerprint)
+            .map(|cached| &cached.detection)
+    }
+
+    pub fn set_services(&mut self, fingerprint: String, detection: ServiceDetection) {
+        self.data.services = Some(CachedServices { fingerprint, detection });
+    }
+
+    /// Number of files whose cached parse was reused and re-parsed
+    pub fn stats(&self) -> (usize, usize) {
+        (self.hits, self.misses)
+    }
+
+    /// Write the cache, dropping entries for files not seen this run
+    pub fn save(&mut self) -> std::io::Result<()> {
+        let used = &self.used;
+        self.data.files.retain(|path, _| used.contains(path));
+
+        std::fs::create_dir_all(&self.dir)?;
+        let ignore_file = self.dir.join(".gitignore");
+        if !ignore_file.exists() {
+            std::fs::write(ignore_file, "*\n")?;
+        }
+        let json = serde_json::to_vec(&self.data)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        // Write then rename so a concurrent run never reads a partial file
+        let temp = self.dir.join(format!("{}.{}.tmp", CACHE_FILE_NAME, std::process::id()));
+        std::fs::write(&temp, json)?;
+        std::fs::rename(temp, self.dir.join(CACHE_FILE_NAME))
+    }
+}
@@ -0,0 +1,41 @@
+use crate::package_deps::models::{DependencyFile, ImportInfo};
+use crate::service::models::ServiceDetection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Everything pybia extracts from a single file's contents
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ParsedFile {
+    /// Dotted module names imported by a Python file, as written
+    pub modules: Vec<String>,
+    /// Imports of a Python file, by top-level package
+    pub imports: Vec<ImportInfo>,
+    /// Requirements declared by a dependency file
+    pub dependency_file: Option<DependencyFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedFile {
+    /// SHA-256 of the contents the entry was parsed from
+    pub hash: String,
+    pub parsed: ParsedFile,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedServices {
+    /// Fingerprint of the detector settings and service metadata files
+    pub fingerprint: String,
+    pub detection: ServiceDetection,
+}
+
+/// The on-disk cache file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CacheData {
+    /// Tool version and cache format the entries were written by
+    pub version: String,
+    #[serde(default)]
+    pub files: HashMap<PathBuf, CachedFile>,
+    #[serde(default)]
+    pub services: Option<CachedServices>,
+}
@@ -0,0 +1,79 @@
+use super::*;
+use crate::dependency::DependencyGraph;
+use crate::test_support::write_file;
+
+fn build(root: &Path, cache_dir: &Path) -> (DependencyGraph, (usize, usize)) {
+    let cache = AnalysisCache::open(cache_dir);
+    let mut graph = DependencyGraph::new().with_cache(cache);
+    graph.build_from_directory(root, Default::default()).unwrap();
+    let stats = graph.cache_stats().unwrap();
+    graph.save_cache().unwrap();
+    (graph, stats)
+}
+
+#[test]
+fn test_cache_reuses_unchanged_files() {
+    let temp = tempfile::tempdir().unwrap();
+    let root = temp.path().join("repo");
+    let cache_dir = temp.path().join("cache");
+    write_file(&root, "app/main.py", "import helpers\nimport requests\n");
+    write_file(&root, "app/helpers.py", "");
+    write_file(&root, "app/requirements.txt", "requests==2.0.0\n");
+
+    let (_, stats) = build(&root.join("app"), &cache_dir);
+    assert_eq!(stats, (0, 3));
+    assert!(cache_dir.join(".gitignore").is_file());
+
+    // Only the changed file is parsed again, and the graph is the same as a fresh build
+    write_file(&root, "app/helpers.py", "import main\n");
+    let (graph, stats) = build(&root.join("app"), &cache_dir);
+    assert_eq!(stats, (2, 1));
+    assert!(graph.get_affected_files(&root.join("app/main.py")).contains(&root.join("app/helpers.py")));
+    assert!(graph.get_affected_files(&root.join("app/requirements.txt")).contains(&root.join("app/main.py")));
+
+    // Entries for deleted files are dropped
+    std::fs::remove_file(root.join("app/helpers.py")).unwrap();
+    build(&root.join("app"), &cache_dir);
+    let cache = AnalysisCache::open(&cache_dir);
+    assert_eq!(cache.data.files.len(), 2);
+}
+
+#[test]
+fn test_cache_discards_other_versions_and_corrupt_files() {
+    let temp = tempfile::tempdir().unwrap();
+    std::fs::write(temp.path().join(CACHE_FILE_NAME), "not json").unwrap();
+    assert!(AnalysisCache::open(temp.path()).data.files.is_empty());
+
+    let mut cache = AnalysisCache::open(temp.path());
+    cache.parsed(Path::new("/repo/a.py"), "import os", ParsedFile::default);
+    cache.data.version = "0.0.0+0".into();
+    cache.save().unwrap();
+    assert!(AnalysisCache::open(temp.path()).data.files.is_empty());
+}
+
+#[test]
+fn test_cached_services_follow_fingerprint() {
+    let temp = tempfile::tempdir().unwrap();
+    write_file(temp.path(), "api/setup.py", "setup(name=\"api\")\n");
+    let detector = crate::service::detector::ServiceDetector::new(Vec::new());
+    let fingerprint = detector.fingerprint(temp.path()).unwrap();
+
+    let mut cache = AnalysisCache::open(&temp.path().join(".pybia/cache"));
+    cache.set_services(fingerprint.clone(), detector.detect(temp.path()).unwrap());
+    cache.save().unwrap();
+    let cache = AnalysisCache::open(&temp.path().join(".pybia/cache"));
+    assert_eq!(cache.services(&fingerprint).unwrap().services.len(), 1);
+
+    // Files that cannot change detection leave the fingerprint alone
+    write_file(temp.path(), "docs/deep/notes/guide.md", "");
+    write_file(temp.path(), "scripts/tools/__init__.py", "");
+    assert_eq!(detector.fingerprint(temp.path()).unwrap(), fingerprint);
+
+    write_file(temp.path(), "api/src/api/__init__.py", "");
+    let changed = detector.fingerprint(temp.path()).unwrap();
+    assert_ne!(changed, fingerprint);
+    write_file(temp.path(), "worker/pyproject.toml", "[project]\nname = \"worker\"\n");
+    let changed = detector.fingerprint(temp.path()).unwrap();
+    assert_ne!(changed, fingerprint);
+    assert!(cache.services(&changed).is_none());
+}
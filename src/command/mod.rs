@@ -0,0 +1,67 @@
+pub mod models;
+pub mod runner;
+
+#[cfg(test)]
+mod tests;
+
+use models::{AffectedService, CommandContext, Invocation, RunMode};
+
+/// Build the runs of `command` for a batch of changes.
+///
+/// An argument that is exactly a list placeholder such as `{affected_services}` expands
+/// to one argument per item; inside a longer argument the items are joined with spaces.
+/// Per-service mode yields one run per affected service and none when nothing is affected.
+/// A run whose arguments all expanded to nothing has no program and is dropped.
+pub fn invocations(command: &[String], context: &CommandContext, mode: RunMode) -> Vec<Invocation> {
+    let runs = match mode {
+        RunMode::Once => vec![invocation(command, context, None)],
+        RunMode::PerService => context.services.iter()
+            .map(|service| invocation(command, context, Some(service)))
+            .collect(),
+    };
+    runs.into_iter().filter(|run| !run.args.is_empty()).collect()
+}
+
+fn invocation(command: &[String], context: &CommandContext, service: Option<&AffectedService>) -> Invocation {
+    let values = placeholder_values(context, service);
+    let mut args = Vec::new();
+    for arg in command {
+        match values.iter().find(|(name, _)| arg == &format!("{{{}}}", name)) {
+            Some((_, items)) => args.extend(items.iter().cloned()),
+            None => {
+                let mut arg = arg.clone();
+                for (name, items) in &values {
+                    arg = arg.replace(&format!("{{{}}}", name), &items.join(" "));
+                }
+                args.push(arg);
+            }
+        }
+    }
+
+    let env = values.iter()
+        .map(|(name, items)| (format!("PYBIA_{}", name.to_uppercase()), items.join("\n")))
+        .collect();
+    Invocation { args, env, service: service.map(|s| s.name.clone()) }
+}
+
+/// Placeholder names and their items; `service` and `service_path` only in per-service mode
+fn placeholder_values(context: &CommandContext, service: Option<&AffectedService>) -> Vec<(&'static str, Vec<String>)> {
+    let mut values = vec![
+        ("affected_services", context.services.iter().map(|s| s.name.clone()).collect()),
+        ("affected_paths", context.services.iter().map(|s| s.path.display().to_string()).collect()),
+        ("changed_files", context.changed_files.iter().map(|f| f.display().to_string()).collect()),
+    ];
+    if let Some(service) = service {
+        values.push(("service", vec![service.name.clone()]));
+        values.push(("service_path", vec![service.path.display().to_string()]));
+    }
+    values
+}
+
+impl Invocation {
+    pub fn to_command(&self) -> std::process::Command {
+        let mut command = std::process::Command::new(&self.args[0]);
+        command.args(&self.args[1..]).envs(self.env.iter().map(|(k, v)| (k, v)));
+        command
+    }
+}
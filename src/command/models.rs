@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// How often the watched command runs for a batch of changes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RunMode {
+    /// Run once with every affected service
+    #[default]
+    Once,
+    /// Run once for each affected service
+    PerService,
+}
+
+impl std::str::FromStr for RunMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "once" => Ok(RunMode::Once),
+            "per-service" => Ok(RunMode::PerService),
+            _ => Err("Invalid run mode".into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AffectedService {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// What a batch of changes affected, substituted into the watched command
+#[derive(Debug, Clone, Default)]
+pub struct CommandContext {
+    pub services: Vec<AffectedService>,
+    pub changed_files: Vec<PathBuf>,
+}
+
+/// A single run of the watched command with placeholders substituted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+    /// The service this run is for, in per-service mode
+    pub service: Option<String>,
+}
+
+/// What to do with a new run while earlier runs are still in progress
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CommandPolicy {
+    /// Start it once the running command has finished
+    #[default]
+    Queue,
+    /// Kill the running command and start it immediately
+    Restart,
+    /// Run alongside other runs, up to a concurrency limit; one service's runs never overlap
+    Parallel,
+}
+
+impl std::str::FromStr for CommandPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "queue" => Ok(CommandPolicy::Queue),
+            "restart" => Ok(CommandPolicy::Restart),
+            "parallel" => Ok(CommandPolicy::Parallel),
+            _ => Err("Invalid command policy".into()),
+        }
+    }
+}
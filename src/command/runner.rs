@@ -0,0 +1,176 @@
+use super::models::{CommandPolicy, Invocation};
+use std::collections::VecDeque;
+use std::process::{Child, ExitStatus};
+use std::time::{Duration, Instant};
+
+/// How long a killed command gets to exit before it is forcibly killed
+const KILL_GRACE: Duration = Duration::from_secs(2);
+
+struct RunningCommand {
+    child: Child,
+    service: Option<String>,
+}
+
+/// Runs watched commands as child processes without blocking the watcher
+pub struct CommandRunner {
+    policy: CommandPolicy,
+    max_parallel: usize,
+    running: Vec<RunningCommand>,
+    queued: VecDeque<Invocation>,
+}
+
+impl CommandRunner {
+    pub fn new(policy: CommandPolicy) -> Self {
+        Self {
+            policy,
+            max_parallel: 1,
+            running: Vec::new(),
+            queued: VecDeque::new(),
+        }
+    }
+
+    /// Maximum number of simultaneous runs under the parallel policy
+    pub fn with_max_parallel(mut self, max_parallel: usize) -> Self {
+        self.max_parallel = max_parallel.max(1);
+        self
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.running.is_empty() && self.queued.is_empty()
+    }
+
+    pub fn running(&self) -> usize {
+        self.running.len()
+    }
+
+    /// Schedule the runs for a new batch of changes according to the policy
+    pub fn submit(&mut self, invocations: Vec<Invocation>) -> std::io::Result<()> {
+        match self.policy {
+            CommandPolicy::Queue => self.queued.extend(invocations),
+            CommandPolicy::Restart => {
+                self.terminate();
+                self.queued.extend(invocations);
+            }
+            CommandPolicy::Parallel => {
+                // A newer run for a service supersedes one still waiting; runs for whole
+                // batches each cover different changes, so all of them go ahead
+                for invocation in invocations {
+                    if invocation.service.is_some() {
+                        self.queued.retain(|queued| queued.service != invocation.service);
+                    }
+                    self.queued.push_back(invocation);
+                }
+            }
+        }
+        self.poll()
+    }
+
+    /// Reap finished runs, reporting failures, and start queued ones while there is capacity
+    pub fn poll(&mut self) -> std::io::Result<()> {
+        let mut index = 0;
+        while index < self.running.len() {
+            match self.running[index].child.try_wait()? {
+                Some(status) => {
+                    let finished = self.running.remove(index);
+                    report(finished.service.as_deref(), status);
+                }
+                None => index += 1,
+            }
+        }
+
+        let capacity = match self.policy {
+            CommandPolicy::Parallel => self.max_parallel,
+            CommandPolicy::Queue | CommandPolicy::Restart => 1,
+        };
+        while self.running.len() < capacity {
+            // Under the parallel policy a service never has two runs at once
+            let next = self.queued.iter().position(|queued| {
+                self.policy != CommandPolicy::Parallel
+                    || queued.service.is_none()
+                    || !self.running.iter().any(|running| running.service == queued.service)
+            });
+            let Some(invocation) = next.and_then(|i| self.queued.remove(i)) else {
+                break;
+            };
+            if let Some(service) = &invocation.service {
+                println!("Running for {}", service);
+            }
+            self.running.push(RunningCommand {
+                child: spawn(&invocation)?,
+                service: invocation.service,
+            });
+        }
+        Ok(())
+    }
+
+    /// Block until every running and queued run has finished
+    pub fn wait(&mut self) -> std::io::Result<()> {
+        while !self.is_idle() {
+            self.poll()?;
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        Ok(())
+    }
+
+    /// Drop queued runs and kill the process groups of running ones
+    pub fn terminate(&mut self) {
+        self.queued.clear();
+        for running in &mut self.running {
+            kill(&mut running.child);
+        }
+        self.running.clear();
+    }
+}
+
+impl Drop for CommandRunner {
+    fn drop(&mut self) {
+        self.terminate();
+    }
+}
+
+fn report(service: Option<&str>, status: ExitStatus) {
+    if status.success() {
+        return;
+    }
+    match service {
+        Some(service) => eprintln!("Command for {} failed: {}", service, status),
+        None => eprintln!("Command failed: {}", status),
+    }
+}
+
+fn spawn(invocation: &Invocation) -> std::io::Result<Child> {
+    let mut command = invocation.to_command();
+    // Its own process group, so everything it starts can be killed together
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    command.spawn()
+}
+
+/// Ask the child's process group to stop, killing it if it does not exit in time
+fn kill(child: &mut Child) {
+    // The leader is reaped only after the last signal, so the group ID cannot have been
+    // reused by then
+    #[cfg(unix)]
+    {
+        let group = child.id() as libc::pid_t;
+        unsafe { libc::killpg(group, libc::SIGTERM) };
+        let deadline = Instant::now() + KILL_GRACE;
+        while Instant::now() < deadline && !has_exited(child) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        // Stragglers in the group that ignored SIGTERM
+        unsafe { libc::killpg(group, libc::SIGKILL) };
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Whether the child has exited, without reaping it
+#[cfg(unix)]
+fn has_exited(child: &Child) -> bool {
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    let flags = libc::WEXITED | libc::WNOHANG | libc::WNOWAIT;
+    let result = unsafe { libc::waitid(libc::P_PID, child.id() as libc::id_t, &mut info, flags) };
+    // With WNOHANG a child still running leaves `si_pid` zero
+    result != 0 || unsafe { info.si_pid() } != 0
+}
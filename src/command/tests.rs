@@ -0,0 +1,157 @@
+use super::*;
+use std::path::PathBuf;
+
+fn args(command: &str) -> Vec<String> {
+    command.split_whitespace().map(String::from).collect()
+}
+
+fn context() -> CommandContext {
+    CommandContext {
+        services: vec![
+            AffectedService { name: "api".into(), path: PathBuf::from("/repo/api") },
+            AffectedService { name: "worker".into(), path: PathBuf::from("/repo/worker") },
+        ],
+        changed_files: vec![PathBuf::from("/repo/libs/db.py")],
+    }
+}
+
+#[test]
+fn test_placeholders_expand_to_arguments() {
+    let runs = invocations(&args("pytest {affected_paths} --services={affected_services}"), &context(), RunMode::Once);
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].args, ["pytest", "/repo/api", "/repo/worker", "--services=api worker"]);
+    assert!(runs[0].env.contains(&("PYBIA_AFFECTED_SERVICES".into(), "api\nworker".into())));
+    assert!(runs[0].env.contains(&("PYBIA_CHANGED_FILES".into(), "/repo/libs/db.py".into())));
+    assert!(runs[0].service.is_none());
+
+    // Nothing affected: list placeholders expand to no arguments
+    let runs = invocations(&args("echo {affected_services} done"), &CommandContext::default(), RunMode::Once);
+    assert_eq!(runs[0].args, args("echo done"));
+
+    // A command made only of list placeholders has nothing to run
+    assert!(invocations(&args("{affected_services}"), &CommandContext::default(), RunMode::Once).is_empty());
+    assert_eq!(invocations(&args("{changed_files}"), &context(), RunMode::Once)[0].args, ["/repo/libs/db.py"]);
+    assert!(invocations(&[], &context(), RunMode::Once).is_empty());
+}
+
+#[test]
+fn test_per_service_runs() {
+    let runs = invocations(&args("make -C {service_path} test SERVICE={service}"), &context(), RunMode::PerService);
+    assert_eq!(runs.len(), 2);
+    assert_eq!(runs[1].args, args("make -C /repo/worker test SERVICE=worker"));
+    assert_eq!(runs[1].service.as_deref(), Some("worker"));
+    assert!(runs[1].env.contains(&("PYBIA_SERVICE".into(), "worker".into())));
+    assert!(runs[1].env.contains(&("PYBIA_AFFECTED_SERVICES".into(), "api\nworker".into())));
+
+    assert!(invocations(&args("make test"), &CommandContext::default(), RunMode::PerService).is_empty());
+}
+
+// Liveness checks read /proc
+#[cfg(target_os = "linux")]
+mod runner {
+    use crate::command::models::{CommandPolicy, Invocation};
+    use crate::command::runner::CommandRunner;
+    use std::path::Path;
+    use std::time::{Duration, Instant};
+
+    fn shell(script: &str, service: Option<&str>) -> Invocation {
+        Invocation {
+            args: vec!["sh".into(), "-c".into(), script.into()],
+            env: Vec::new(),
+            service: service.map(String::from),
+        }
+    }
+
+    fn is_alive(pid: &str) -> bool {
+        std::fs::read_to_string(format!("/proc/{}/stat", pid.trim()))
+            .is_ok_and(|stat| !stat.contains(") Z "))
+    }
+
+    #[test]
+    fn test_queue_runs_one_at_a_time() {
+        let temp = tempfile::tempdir().unwrap();
+        let log = temp.path().join("log");
+        let script = |n: u8| format!("echo start{n} >> {0}; sleep 0.1; echo end{n} >> {0}", log.display());
+
+        let mut runner = CommandRunner::new(CommandPolicy::Queue);
+        runner.submit(vec![shell(&script(1), None)]).unwrap();
+        runner.submit(vec![shell(&script(2), None)]).unwrap();
+        assert_eq!(runner.running(), 1);
+        runner.wait().unwrap();
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "start1\nend1\nstart2\nend2\n");
+    }
+
+    #[test]
+    fn test_restart_kills_process_group() {
+        let temp = tempfile::tempdir().unwrap();
+        let pid_file = temp.path().join("pid");
+        let mut runner = CommandRunner::new(CommandPolicy::Restart);
+        runner.submit(vec![shell(&format!("sleep 30 & echo $! > {}; wait", pid_file.display()), None)]).unwrap();
+
+        let started = Instant::now();
+        while !Path::new(&pid_file).exists() || std::fs::read_to_string(&pid_file).unwrap().trim().is_empty() {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let grandchild = std::fs::read_to_string(&pid_file).unwrap();
+        assert!(is_alive(&grandchild));
+
+        runner.submit(vec![shell("true", None)]).unwrap();
+        runner.wait().unwrap();
+        assert!(started.elapsed() < Duration::from_secs(10));
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!is_alive(&grandchild));
+    }
+
+    #[test]
+    fn test_restart_kills_processes_ignoring_sigterm() {
+        let temp = tempfile::tempdir().unwrap();
+        let pid_file = temp.path().join("pid");
+        let mut runner = CommandRunner::new(CommandPolicy::Restart);
+        let script = format!("(trap '' TERM; exec sleep 30) & echo $! > {}; wait", pid_file.display());
+        runner.submit(vec![shell(&script, None)]).unwrap();
+
+        let started = Instant::now();
+        while !Path::new(&pid_file).exists() || std::fs::read_to_string(&pid_file).unwrap().trim().is_empty() {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let grandchild = std::fs::read_to_string(&pid_file).unwrap();
+
+        // The shell exits on SIGTERM at once; the sleep it left behind is killed with the group
+        runner.terminate();
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!is_alive(&grandchild));
+    }
+
+    #[test]
+    fn test_parallel_limit_and_per_service_runs() {
+        let mut runner = CommandRunner::new(CommandPolicy::Parallel).with_max_parallel(2);
+        runner.submit(vec![
+            shell("sleep 0.2", Some("api")),
+            shell("sleep 0.2", Some("worker")),
+            shell("sleep 0.2", Some("web")),
+        ]).unwrap();
+        assert_eq!(runner.running(), 2);
+
+        // A second run for a busy service waits, replacing its older queued run
+        runner.submit(vec![shell("sleep 0.2", Some("web")), shell("sleep 0.2", Some("api"))]).unwrap();
+        assert_eq!(runner.running(), 2);
+        runner.wait().unwrap();
+        assert!(runner.is_idle());
+    }
+
+    #[test]
+    fn test_parallel_batch_runs_overlap() {
+        let mut runner = CommandRunner::new(CommandPolicy::Parallel).with_max_parallel(2);
+        runner.submit(vec![shell("sleep 0.2", None)]).unwrap();
+        runner.submit(vec![shell("sleep 0.2", None)]).unwrap();
+        runner.submit(vec![shell("sleep 0.2", None)]).unwrap();
+        assert_eq!(runner.running(), 2);
+
+        // The third batch still runs once a slot frees up
+        let started = Instant::now();
+        runner.wait().unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(150));
+    }
+}
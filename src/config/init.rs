@@ -0,0 +1,127 @@
+use crate::service::models::{DetectedService, ServiceDetection};
+use crate::utils::relative_path_string;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Paths that are rarely worth scanning in a Python repository
+pub const DEFAULT_IGNORE: &[&str] = &[
+    ".git",
+    ".pybia",
+    ".tox",
+    ".nox",
+    ".venv",
+    "venv",
+    "build",
+    "dist",
+    "**/__pycache__",
+    "**/node_modules",
+    "**/*.egg-info",
+];
+
+fn sorted_services(detection: &ServiceDetection) -> Vec<&DetectedService> {
+    let mut services: Vec<&DetectedService> = detection.services.values().collect();
+    services.sort_by(|a, b| a.root_path.cmp(&b.root_path).then_with(|| a.name.cmp(&b.name)));
+    services
+}
+
+fn display_relative(path: &Path, root: &Path) -> String {
+    match relative_path_string(path, root) {
+        Some(relative) if relative.is_empty() => ".".to_string(),
+        Some(relative) => relative,
+        None => path.display().to_string(),
+    }
+}
+
+fn quote(value: &str) -> String {
+    toml::Value::String(value.to_string()).to_string()
+}
+
+/// Render a table of detected services for the terminal
+pub fn render_summary(detection: &ServiceDetection, root: &Path) -> String {
+    let rows: Vec<[String; 4]> = sorted_services(detection).into_iter()
+        .map(|service| [
+            service.name.clone(),
+            display_relative(&service.root_path, root),
+            display_relative(&service.package_root, root),
+            service.detection_type.to_string(),
+        ])
+        .collect();
+    let header = ["NAME", "ROOT", "PACKAGE ROOT", "DETECTED BY"].map(String::from);
+
+    let mut widths = header.each_ref().map(|h| h.len());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let mut output = String::new();
+    for row in std::iter::once(&header).chain(&rows) {
+        let line = row.iter().zip(widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        let _ = writeln!(output, "{}", line.trim_end());
+    }
+    output
+}
+
+/// Render a commented `pybia.toml` capturing the detected services, with paths relative to `root`
+pub fn render_config(detection: &ServiceDetection, root: &Path) -> String {
+    let mut output = String::new();
+    let _ = writeln!(output, "# pybia configuration generated by `pybia init`.");
+    let _ = writeln!(output, "# Paths are relative to the directory containing this file.");
+    let _ = writeln!(output);
+    let _ = writeln!(output, "# Directories imports are resolved against.");
+    let _ = writeln!(output, "source_roots = [\".\"]");
+    let _ = writeln!(output);
+    let _ = writeln!(output, "# Paths that are never scanned.");
+    let _ = writeln!(output, "ignore = [");
+    for pattern in DEFAULT_IGNORE {
+        let _ = writeln!(output, "    {},", quote(pattern));
+    }
+    let _ = writeln!(output, "]");
+    let _ = writeln!(output);
+    let _ = writeln!(output, "# Files whose change affects every service, e.g. \".github/workflows/*\".");
+    let _ = writeln!(output, "trigger_files = []");
+    let _ = writeln!(output);
+    let _ = writeln!(output, "# How to resolve services sharing a name: error, path-qualified or alias.");
+    let _ = writeln!(output, "name_collisions = \"path-qualified\"");
+    let _ = writeln!(output);
+    let _ = writeln!(output, "[output]");
+    let _ = writeln!(output, "# name or name-path");
+    let _ = writeln!(output, "service_format = \"name\"");
+    let _ = writeln!(output);
+    let _ = writeln!(output, "[watcher]");
+    let _ = writeln!(output, "debounce_ms = 100");
+    let _ = writeln!(output, "# once or per-service");
+    let _ = writeln!(output, "run_mode = \"once\"");
+    let _ = writeln!(output, "# queue, restart or parallel");
+    let _ = writeln!(output, "command_policy = \"queue\"");
+    let _ = writeln!(output, "# auto, native or poll; poll for network filesystems and some containers");
+    let _ = writeln!(output, "backend = \"auto\"");
+
+    for collision in &detection.collisions {
+        let _ = writeln!(output);
+        let paths: Vec<String> = collision.paths.iter().map(|p| display_relative(p, root)).collect();
+        let _ = writeln!(output, "# NOTE: service name '{}' is used by {}", collision.name, paths.join(", "));
+        let _ = writeln!(output, "# Rename the services below to keep their names stable.");
+    }
+
+    for service in sorted_services(detection) {
+        let _ = writeln!(output);
+        let _ = writeln!(
+            output,
+            "# Detected by {}; package root: {}",
+            service.detection_type,
+            display_relative(&service.package_root, root)
+        );
+        let _ = writeln!(output, "[[services]]");
+        let _ = writeln!(output, "name = {}", quote(&service.name));
+        let _ = writeln!(output, "path = {}", quote(&display_relative(&service.root_path, root)));
+        let _ = writeln!(output, "# include_paths = []");
+        let _ = writeln!(output, "# exclude_paths = []");
+    }
+
+    output
+}
@@ -0,0 +1,90 @@
+pub mod init;
+pub mod models;
+pub mod validate;
+
+use crate::utils::clean_path;
+use models::PybiaConfig;
+use std::path::{Path, PathBuf};
+
+pub const CONFIG_FILE_NAME: &str = "pybia.toml";
+
+/// Walk up from `start` looking for `pybia.toml`, or a `pyproject.toml` with a `[tool.pybia]` table
+pub fn discover(start: &Path) -> Option<PathBuf> {
+    for dir in start.ancestors() {
+        let candidate = dir.join(CONFIG_FILE_NAME);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+
+        let pyproject = dir.join("pyproject.toml");
+        if pyproject.is_file() && pyproject_has_pybia_table(&pyproject) {
+            return Some(pyproject);
+        }
+    }
+    None
+}
+
+fn pyproject_has_pybia_table(path: &Path) -> bool {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| content.parse::<toml::Table>().ok())
+        .is_some_and(|toml| toml.get("tool").and_then(|t| t.get("pybia")).is_some())
+}
+
+/// Load a config file, resolving relative paths against the directory containing it
+pub fn load(path: &Path) -> std::io::Result<PybiaConfig> {
+    // Commands compare scanned paths with absolute ones, so the root must be absolute too
+    let path = &clean_path(&std::path::absolute(path)?);
+    let content = std::fs::read_to_string(path)?;
+    let is_pyproject = path.file_name().is_some_and(|n| n == "pyproject.toml");
+    let mut config = parse(&content, is_pyproject).map_err(|e| std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("Failed to parse config {}: {}", path.display(), e),
+    ))?;
+
+    let root = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    config.resolve_paths(&root);
+    Ok(config)
+}
+
+/// Parse config file contents; `pyproject.toml` files are read from their `[tool.pybia]` table
+pub fn parse(content: &str, is_pyproject: bool) -> Result<PybiaConfig, toml::de::Error> {
+    if !is_pyproject {
+        return toml::from_str(content);
+    }
+
+    let mut toml: toml::Table = toml::from_str(content)?;
+    let pybia = toml.remove("tool")
+        .and_then(|tool| match tool {
+            toml::Value::Table(mut tool) => tool.remove("pybia"),
+            _ => None,
+        })
+        .unwrap_or_else(|| toml::Value::Table(toml::Table::new()));
+    pybia.try_into()
+}
+
+impl PybiaConfig {
+    /// Make service paths, source roots and the cache directory absolute relative to `root`
+    pub fn resolve_paths(&mut self, root: &Path) {
+        let resolve = |p: &PathBuf| clean_path(&root.join(p));
+        for service in &mut self.services {
+            service.path = resolve(&service.path);
+        }
+        self.source_roots = self.source_roots.iter().map(resolve).collect();
+        self.cache.dir = resolve(&self.cache.dir);
+        self.tests.coverage = self.tests.coverage.as_ref().map(resolve);
+        if self.source_roots.is_empty() {
+            self.source_roots.push(root.to_path_buf());
+        }
+        self.root = root.to_path_buf();
+    }
+
+    /// Directories to scan and watch: the whole config directory, so that services and trigger
+    /// files outside the source roots are seen; source roots only guide import resolution
+    pub fn scan_paths(&self) -> Vec<PathBuf> {
+        vec![self.root.clone()]
+    }
+}
+
+#[cfg(test)]
+mod tests;
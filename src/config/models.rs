@@ -0,0 +1,161 @@
+use crate::cache::DEFAULT_CACHE_DIR;
+use crate::command::models::{CommandPolicy, RunMode};
+use crate::dependency::{
+    assets::AssetRule, deps_check::DepsCheckConfig, impact::ImpactRule, rules::ImportRule, selection::TestConfig,
+};
+use crate::service::models::{NameCollisionPolicy, ServiceConfig};
+use crate::watcher::WatcherBackend;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Top-level pybia configuration, read from `pybia.toml` or `[tool.pybia]` in `pyproject.toml`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PybiaConfig {
+    /// Explicitly configured services
+    #[serde(default)]
+    pub services: Vec<ServiceConfig>,
+    /// Directories imports are resolved against; defaults to the config directory
+    #[serde(default)]
+    pub source_roots: Vec<PathBuf>,
+    /// Globs, relative to the config directory, for paths pybia should never scan
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    /// Globs, relative to the config directory, for files whose change affects every service
+    #[serde(default)]
+    pub trigger_files: Vec<String>,
+    /// Rules mapping changed paths to all, named or tagged services
+    #[serde(default)]
+    pub impact_rules: Vec<ImpactRule>,
+    /// Rules attributing non-Python files to the modules that load them
+    #[serde(default)]
+    pub asset_rules: Vec<AssetRule>,
+    /// Architecture rules allowing or forbidding imports, checked by `pybia check`
+    #[serde(default)]
+    pub import_rules: Vec<ImportRule>,
+    /// Import name mappings and allowlists for `pybia deps-check`
+    #[serde(default)]
+    pub deps_check: DepsCheckConfig,
+    /// Agreed version specifiers by distribution, checked by `pybia versions`
+    #[serde(default)]
+    pub version_baseline: BTreeMap<String, String>,
+    /// How `pybia tests` recognises test files
+    #[serde(default)]
+    pub tests: TestConfig,
+    /// How to resolve services that share a name
+    #[serde(default)]
+    pub name_collisions: NameCollisionPolicy,
+    /// How many import steps impact analysis follows; unset for the full transitive closure
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    #[serde(default)]
+    pub output: OutputConfig,
+    #[serde(default)]
+    pub watcher: WatcherConfig,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    /// Directory relative paths were resolved against
+    #[serde(skip)]
+    pub root: PathBuf,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OutputConfig {
+    /// Default output format for affected services
+    #[serde(default)]
+    pub service_format: ServiceFormat,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatcherConfig {
+    /// Quiet period after the last file event before a batch of changes is processed, in milliseconds
+    #[serde(default = "default_debounce_ms")]
+    pub debounce_ms: u64,
+    /// Run the command once per batch or once per affected service
+    #[serde(default)]
+    pub run_mode: RunMode,
+    /// What to do when changes arrive while the command is still running
+    #[serde(default)]
+    pub command_policy: CommandPolicy,
+    /// Maximum simultaneous runs under the parallel policy
+    #[serde(default = "default_max_parallel")]
+    pub max_parallel: usize,
+    /// Where file events come from: auto, native or poll
+    #[serde(default)]
+    pub backend: WatcherBackend,
+    /// How often the polling backend scans for changes, in milliseconds
+    #[serde(default = "default_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+}
+
+fn default_debounce_ms() -> u64 {
+    100
+}
+
+fn default_max_parallel() -> usize {
+    4
+}
+
+fn default_poll_interval_ms() -> u64 {
+    1000
+}
+
+impl Default for WatcherConfig {
+    fn default() -> Self {
+        Self {
+            debounce_ms: default_debounce_ms(),
+            run_mode: RunMode::default(),
+            command_policy: CommandPolicy::default(),
+            max_parallel: default_max_parallel(),
+            backend: WatcherBackend::default(),
+            poll_interval_ms: default_poll_interval_ms(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheConfig {
+    /// Reuse parse results for unchanged files between runs
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Where the cache is stored, relative to the config directory
+    #[serde(default = "default_cache_dir")]
+    pub dir: PathBuf,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_cache_dir() -> PathBuf {
+    PathBuf::from(DEFAULT_CACHE_DIR)
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dir: default_cache_dir(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ServiceFormat {
+    #[default]
+    Name,
+    NamePath,
+}
+
+impl std::str::FromStr for ServiceFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "name" => Ok(ServiceFormat::Name),
+            "name-path" => Ok(ServiceFormat::NamePath),
+            _ => Err("Invalid service format".into()),
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use super::*;
+use crate::config::models::ServiceFormat;
+use crate::service::models::NameCollisionPolicy;
+use std::fs;
+use crate::test_support::write_file;
+use tempfile::TempDir;
+
+fn setup_config_test() -> TempDir {
+    tempfile::tempdir().unwrap()
+}
+
+#[test]
+fn test_load_pybia_toml() {
+    let temp = setup_config_test();
+    write_file(
+        temp.path(),
+        "pybia.toml",
+        r#"
+        source_roots = ["src", "libs"]
+        ignore = ["build", "**/node_modules"]
+        trigger_files = [".github/workflows/*"]
+        name_collisions = "error"
+
+        [output]
+        service_format = "name-path"
+
+        [watcher]
+        debounce_ms = 250
+
+        [[services]]
+        name = "auth-service"
+        path = "services/auth"
+        "#,
+    );
+
+    let config = load(&temp.path().join("pybia.toml")).unwrap();
+
+    assert_eq!(config.root, temp.path());
+    assert_eq!(config.source_roots, vec![temp.path().join("src"), temp.path().join("libs")]);
+    assert_eq!(config.services.len(), 1);
+    assert_eq!(config.services[0].path, temp.path().join("services/auth"));
+    assert_eq!(config.ignore, vec!["build", "**/node_modules"]);
+    assert_eq!(config.trigger_files, vec![".github/workflows/*"]);
+    assert_eq!(config.name_collisions, NameCollisionPolicy::Error);
+    assert_eq!(config.output.service_format, ServiceFormat::NamePath);
+    assert_eq!(config.watcher.debounce_ms, 250);
+}
+
+#[test]
+fn test_defaults() {
+    let temp = setup_config_test();
+    write_file(temp.path(), "pybia.toml", "");
+
+    let config = load(&temp.path().join("pybia.toml")).unwrap();
+
+    assert!(config.services.is_empty());
+    assert_eq!(config.source_roots, vec![temp.path().to_path_buf()]);
+    assert_eq!(config.output.service_format, ServiceFormat::Name);
+    assert_eq!(config.watcher.debounce_ms, 100);
+}
+
+#[test]
+fn test_load_tool_pybia_from_pyproject() {
+    let temp = setup_config_test();
+    write_file(
+        temp.path(),
+        "pyproject.toml",
+        r#"
+        [project]
+        name = "monorepo"
+
+        [[tool.pybia.services]]
+        name = "api"
+        path = "services/api"
+        "#,
+    );
+
+    let config = load(&temp.path().join("pyproject.toml")).unwrap();
+
+    assert_eq!(config.services.len(), 1);
+    assert_eq!(config.services[0].path, temp.path().join("services/api"));
+}
+
+#[test]
+fn test_discover_walks_up() {
+    let temp = setup_config_test();
+    write_file(temp.path(), "pybia.toml", "");
+    write_file(temp.path(), "services/api/pyproject.toml", "[project]\nname = \"api\"\n");
+    fs::create_dir_all(temp.path().join("services/api/src")).unwrap();
+
+    let found = discover(&temp.path().join("services/api/src")).unwrap();
+
+    // pyproject.toml without [tool.pybia] is skipped
+    assert_eq!(found, temp.path().join("pybia.toml"));
+}
+
+#[test]
+fn test_discover_pyproject_with_tool_pybia() {
+    let temp = setup_config_test();
+    write_file(temp.path(), "pyproject.toml", "[tool.pybia]\nignore = [\"build\"]\n");
+    fs::create_dir_all(temp.path().join("pkg")).unwrap();
+
+    assert_eq!(discover(&temp.path().join("pkg")).unwrap(), temp.path().join("pyproject.toml"));
+}
+
+#[test]
+fn test_invalid_config_reports_path() {
+    let temp = setup_config_test();
+    write_file(temp.path(), "pybia.toml", "services = 3\n");
+
+    let err = load(&temp.path().join("pybia.toml")).unwrap_err();
+
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("pybia.toml"));
+}
+
+fn validate_config(temp: &TempDir, content: &str) -> Vec<validate::Diagnostic> {
+    write_file(temp.path(), "pybia.toml", content);
+    validate::validate_file(&temp.path().join("pybia.toml")).unwrap()
+}
+
+#[test]
+fn test_validate_clean_config() {
+    let temp = setup_config_test();
+    fs::create_dir_all(temp.path().join("services/auth/src")).unwrap();
+
+    let diagnostics = validate_config(&temp, r#"
+[[services]]
+name = "auth"
+path = "services/auth"
+include_paths = ["src"]
+alias = "auth-api"
+"#);
+
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+}
+
+#[test]
+fn test_validate_alias_needs_a_single_directory() {
+    let temp = setup_config_test();
+    fs::create_dir_all(temp.path().join("services/auth")).unwrap();
+    fs::create_dir_all(temp.path().join("services/billing")).unwrap();
+
+    let diagnostics = validate_config(&temp, r#"
+[[services]]
+name = "{dir}"
+path = "services/*"
+alias = "api"
+"#);
+
+    assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+    assert_eq!(diagnostics[0].severity, validate::Severity::Error);
+    assert!(diagnostics[0].message.contains("cannot share the alias 'api'"));
+    assert_eq!(diagnostics[0].location, Some((5, 9)));
+}
+
+#[test]
+fn test_validate_syntax_error_location() {
+    let temp = setup_config_test();
+
+    let diagnostics = validate_config(&temp, "ignore = [\n  \"a\"\n");
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, validate::Severity::Error);
+    assert_eq!(diagnostics[0].location.unwrap().0, 3);
+}
+
+#[test]
+fn test_validate_type_error_location() {
+    let temp = setup_config_test();
+
+    let diagnostics = validate_config(&temp, "[[services]]\nname = 1\npath = \"x\"\n");
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].location, Some((2, 8)));
+}
+
+#[test]
+fn test_validate_unknown_keys() {
+    let temp = setup_config_test();
+    fs::create_dir_all(temp.path().join("api")).unwrap();
+
+    let diagnostics = validate_config(&temp, r#"ignor = ["build"]
+
+[watcher]
+debounce = 10
+
+[[services]]
+name = "api"
+path = "api"
+detection = { indicator_file = ["app.py"] }
+"#);
+
+    let unknown: Vec<_> = diagnostics.iter()
+        .filter(|d| d.message.starts_with("unknown key"))
+        .map(|d| (d.message.as_str(), d.location.unwrap()))
+        .collect();
+    assert_eq!(unknown, vec![
+        ("unknown key 'ignor'", (1, 1)),
+        ("unknown key 'debounce' in [watcher]", (4, 1)),
+        ("unknown key 'indicator_file' in [services.detection]", (9, 15)),
+    ]);
+}
+
+#[test]
+fn test_validate_paths_and_overlaps() {
+    let temp = setup_config_test();
+    fs::create_dir_all(temp.path().join("services/auth")).unwrap();
+    fs::create_dir_all(temp.path().join("docs")).unwrap();
+
+    let diagnostics = validate_config(&temp, r#"
+[[services]]
+name = "auth"
+path = "services/auth"
+exclude_paths = ["../../docs"]
+
+[[services]]
+name = "services"
+path = "services"
+
+[[services]]
+name = "missing"
+path = "services/missing"
+"#);
+
+    let messages: Vec<(validate::Severity, &str)> = diagnostics.iter()
+        .map(|d| (d.severity, d.message.as_str()))
+        .collect();
+    assert_eq!(messages.len(), 3, "{:?}", messages);
+    assert!(messages.iter().any(|(s, m)| *s == validate::Severity::Warning && m.contains("never applies")));
+    assert!(messages.iter().any(|(s, m)| *s == validate::Severity::Warning && m.contains("overlap")));
+    assert!(messages.iter().any(|(s, m)| *s == validate::Severity::Error && m.contains("'missing'")));
+    let missing = diagnostics.iter().find(|d| d.message.contains("'missing'")).unwrap();
+    assert_eq!(missing.location, Some((13, 8)));
+}
+
+#[test]
+fn test_init_renders_detected_services() {
+    let temp = setup_config_test();
+    write_file(temp.path(), "services/a/pyproject.toml", "[project]\nname = \"template\"\n");
+    write_file(temp.path(), "services/b/pyproject.toml", "[project]\nname = \"template\"\n");
+    write_file(temp.path(), "libs/core/pyproject.toml", "[project]\nname = \"core\"\n");
+    write_file(temp.path(), "libs/core/core/__init__.py", "");
+
+    let detection = crate::service::detector::ServiceDetector::new(vec![])
+        .detect(temp.path())
+        .unwrap();
+
+    let summary = init::render_summary(&detection, temp.path());
+    assert!(summary.starts_with("NAME"));
+    assert!(summary.lines().any(|l| l.starts_with("core ") && l.contains("libs/core/core") && l.ends_with("pyproject.toml")));
+
+    let rendered = init::render_config(&detection, temp.path());
+    assert!(rendered.contains("# NOTE: service name 'template' is used by services/a, services/b"));
+    write_file(temp.path(), "pybia.toml", &rendered);
+    let config = load(&temp.path().join("pybia.toml")).unwrap();
+
+    let mut services: Vec<(String, PathBuf)> = config.services.iter()
+        .map(|s| (s.name.clone(), s.path.clone()))
+        .collect();
+    services.sort();
+    assert_eq!(services, vec![
+        ("core".to_string(), temp.path().join("libs/core")),
+        ("template@services/a".to_string(), temp.path().join("services/a")),
+        ("template@services/b".to_string(), temp.path().join("services/b")),
+    ]);
+    assert_eq!(config.ignore.len(), init::DEFAULT_IGNORE.len());
+    assert!(validate::validate_file(&temp.path().join("pybia.toml")).unwrap().is_empty());
+}
+
+#[test]
+fn test_validate_import_rules() {
+    let temp = setup_config_test();
+    let diagnostics = validate_config(&temp, r#"
+[[import_rules]]
+from = "services/{name}"
+to = "services/{name}"
+action = "allow"
+
+[[import_rules]]
+from = "services/*"
+to = "services/{other}"
+"#);
+
+    assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+    assert_eq!(diagnostics[0].severity, validate::Severity::Error);
+    assert!(diagnostics[0].message.contains("'{other}'"));
+    assert_eq!(diagnostics[0].location, Some((7, 1)));
+}
@@ -0,0 +1,455 @@
+use super::models::PybiaConfig;
+use crate::dependency::deps_check::DepsCheck;
+use crate::dependency::rules::CompiledImportRule;
+use crate::package_deps::specifier::SpecifierSet;
+use crate::service::detector::ServiceDetector;
+use crate::utils::PathPatterns;
+use std::path::Path;
+use toml_edit::{ImDocument, Item, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// 1-based line and column in a config file
+pub type Location = (usize, usize);
+
+/// A problem found in a config file, with its location when known
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// Position of the offending key or value
+    pub location: Option<Location>,
+}
+
+impl Diagnostic {
+    fn new(severity: Severity, message: String, location: Option<Location>) -> Self {
+        Self { severity, message, location }
+    }
+
+    /// Render as `file:line:column: severity: message`
+    pub fn render(&self, file: &Path) -> String {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match self.location {
+            Some((line, column)) => format!("{}:{}:{}: {}: {}", file.display(), line, column, severity, self.message),
+            None => format!("{}: {}: {}", file.display(), severity, self.message),
+        }
+    }
+}
+
+/// Keys accepted in each config table, addressed by dotted path without array indices
+fn known_keys(table: &str) -> Option<&'static [&'static str]> {
+    match table {
+        "" => Some(&[
+            "services", "source_roots", "ignore", "trigger_files", "impact_rules", "asset_rules", "import_rules",
+            "deps_check", "version_baseline", "tests", "name_collisions", "max_depth", "output", "watcher", "cache",
+        ]),
+        "services" => Some(&["name", "path", "include_paths", "exclude_paths", "detection", "tags", "alias"]),
+        "impact_rules" => Some(&["paths", "all", "services", "tags", "reason"]),
+        "asset_rules" => Some(&["assets", "modules"]),
+        "import_rules" => Some(&["from", "to", "action", "reason"]),
+        "deps_check" => Some(&["import_names", "allow_unused", "allow_undeclared"]),
+        "tests" => Some(&["patterns", "coverage"]),
+        "services.detection" => Some(&["detect_setup_py", "detect_pyproject", "indicator_files"]),
+        "output" => Some(&["service_format"]),
+        "cache" => Some(&["enabled", "dir"]),
+        "watcher" => Some(&[
+            "debounce_ms", "run_mode", "command_policy", "max_parallel", "backend", "poll_interval_ms",
+        ]),
+        _ => None,
+    }
+}
+
+/// Validate the config file at `path`, returning every problem found
+pub fn validate_file(path: &Path) -> std::io::Result<Vec<Diagnostic>> {
+    let content = std::fs::read_to_string(path)?;
+    let is_pyproject = path.file_name().is_some_and(|n| n == "pyproject.toml");
+    let root = path.parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    Ok(validate(&content, is_pyproject, root))
+}
+
+/// Validate config `content`, resolving relative paths against `root`
+pub fn validate(content: &str, is_pyproject: bool, root: &Path) -> Vec<Diagnostic> {
+    let document = match ImDocument::parse(content) {
+        Ok(document) => document,
+        Err(e) => {
+            let location = e.span().map(|span| line_column(content, span.start));
+            return vec![Diagnostic::new(Severity::Error, single_line(e.message()), location)];
+        }
+    };
+
+    let prefix: &[&str] = if is_pyproject { &["tool", "pybia"] } else { &[] };
+    let mut table = document.as_item();
+    for key in prefix {
+        match table.get(key) {
+            Some(item) => table = item,
+            None => return Vec::new(),
+        }
+    }
+
+    let mut diagnostics = Vec::new();
+    check_unknown_keys(content, table, "", &mut diagnostics);
+
+    let config = match super::parse(content, is_pyproject) {
+        Ok(config) => config,
+        Err(e) => {
+            let location = e.span().map(|span| line_column(content, span.start));
+            diagnostics.push(Diagnostic::new(Severity::Error, single_line(e.message()), location));
+            return diagnostics;
+        }
+    };
+
+    let locate = |path: &[PathSegment]| {
+        lookup(table, path)
+            .and_then(Item::span)
+            .map(|span| line_column(content, span.start))
+    };
+    check_semantics(config, root, &locate, &mut diagnostics);
+    diagnostics
+}
+
+/// Step in a path through the config document
+#[derive(Debug, Clone, Copy)]
+pub enum PathSegment<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+fn lookup<'a>(mut item: &'a Item, path: &[PathSegment]) -> Option<&'a Item> {
+    for segment in path {
+        item = match segment {
+            PathSegment::Key(key) => item.get(key)?,
+            PathSegment::Index(index) => item.get(index)?,
+        };
+    }
+    Some(item)
+}
+
+fn check_unknown_keys(content: &str, item: &Item, schema_path: &str, diagnostics: &mut Vec<Diagnostic>) {
+    match item {
+        Item::Table(table) => {
+            let entries = table.iter().filter_map(|(k, v)| Some((table.key(k)?, Node::Item(v))));
+            check_table_keys(content, entries, schema_path, diagnostics);
+        }
+        Item::ArrayOfTables(array) => {
+            for table in array.iter() {
+                let entries = table.iter().filter_map(|(k, v)| Some((table.key(k)?, Node::Item(v))));
+                check_table_keys(content, entries, schema_path, diagnostics);
+            }
+        }
+        Item::Value(value) => check_value_keys(content, value, schema_path, diagnostics),
+        Item::None => {}
+    }
+}
+
+fn check_value_keys(content: &str, value: &Value, schema_path: &str, diagnostics: &mut Vec<Diagnostic>) {
+    match value {
+        Value::InlineTable(table) => {
+            let entries = table.iter().filter_map(|(k, v)| Some((table.key(k)?, Node::Value(v))));
+            check_table_keys(content, entries, schema_path, diagnostics);
+        }
+        Value::Array(array) => {
+            for value in array.iter() {
+                check_value_keys(content, value, schema_path, diagnostics);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// A child of a table, which is an `Item` in regular tables and a `Value` in inline tables
+enum Node<'a> {
+    Item(&'a Item),
+    Value(&'a Value),
+}
+
+fn check_table_keys<'a>(
+    content: &str,
+    entries: impl Iterator<Item = (&'a toml_edit::Key, Node<'a>)>,
+    schema_path: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let Some(known) = known_keys(schema_path) else {
+        return;
+    };
+
+    for (key, node) in entries {
+        let name = key.get();
+        if !known.contains(&name) {
+            let location = key.span().map(|span| line_column(content, span.start));
+            let message = match schema_path {
+                "" => format!("unknown key '{}'", name),
+                table => format!("unknown key '{}' in [{}]", name, table),
+            };
+            diagnostics.push(Diagnostic::new(Severity::Error, message, location));
+            continue;
+        }
+
+        let child_path = if schema_path.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", schema_path, name)
+        };
+        match node {
+            Node::Item(item) => check_unknown_keys(content, item, &child_path, diagnostics),
+            Node::Value(value) => check_value_keys(content, value, &child_path, diagnostics),
+        }
+    }
+}
+
+fn check_semantics(
+    mut config: PybiaConfig,
+    root: &Path,
+    locate: &dyn Fn(&[PathSegment]) -> Option<Location>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    config.resolve_paths(root);
+    use PathSegment::{Index, Key};
+
+    for (index, source_root) in config.source_roots.iter().enumerate() {
+        if !source_root.exists() {
+            diagnostics.push(Diagnostic::new(
+                Severity::Error,
+                format!("source root '{}' does not exist", source_root.display()),
+                locate(&[Key("source_roots"), Index(index)]),
+            ));
+        }
+    }
+
+    let ignore = match PathPatterns::new(&config.root, &config.ignore) {
+        Ok(ignore) => ignore,
+        Err(e) => {
+            diagnostics.push(Diagnostic::new(
+                Severity::Error,
+                format!("invalid ignore glob: {}", e),
+                locate(&[Key("ignore")]),
+            ));
+            PathPatterns::default()
+        }
+    };
+    if let Err(e) = PathPatterns::new(&config.root, &config.trigger_files) {
+        diagnostics.push(Diagnostic::new(
+            Severity::Error,
+            format!("invalid trigger_files glob: {}", e),
+            locate(&[Key("trigger_files")]),
+        ));
+    }
+
+    for (index, rule) in config.impact_rules.iter().enumerate() {
+        if let Err(e) = PathPatterns::new(&config.root, &rule.paths) {
+            diagnostics.push(Diagnostic::new(
+                Severity::Error,
+                format!("invalid impact rule glob: {}", e),
+                locate(&[Key("impact_rules"), Index(index), Key("paths")]),
+            ));
+        }
+        if !rule.all && rule.services.is_empty() && rule.tags.is_empty() {
+            diagnostics.push(Diagnostic::new(
+                Severity::Warning,
+                "impact rule affects no services; set all, services or tags".to_string(),
+                locate(&[Key("impact_rules"), Index(index)]),
+            ));
+        }
+    }
+
+    for (index, rule) in config.asset_rules.iter().enumerate() {
+        for (key, patterns) in [("assets", &rule.assets), ("modules", &rule.modules)] {
+            if let Err(e) = PathPatterns::new(&config.root, patterns) {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Error,
+                    format!("invalid asset rule glob: {}", e),
+                    locate(&[Key("asset_rules"), Index(index), Key(key)]),
+                ));
+            }
+        }
+    }
+
+    for (index, rule) in config.import_rules.iter().enumerate() {
+        if let Err(e) = CompiledImportRule::new(rule.clone()) {
+            diagnostics.push(Diagnostic::new(
+                Severity::Error,
+                format!("invalid import rule: {}", e),
+                locate(&[Key("import_rules"), Index(index)]),
+            ));
+        }
+    }
+
+    if let Err(e) = DepsCheck::new(&config.deps_check) {
+        diagnostics.push(Diagnostic::new(
+            Severity::Error,
+            format!("invalid deps_check glob: {}", e),
+            locate(&[Key("deps_check")]),
+        ));
+    }
+
+    if let Err(e) = PathPatterns::new(&config.root, &config.tests.patterns) {
+        diagnostics.push(Diagnostic::new(
+            Severity::Error,
+            format!("invalid test file glob: {}", e),
+            locate(&[Key("tests"), Key("patterns")]),
+        ));
+    }
+
+    for (name, specifier) in &config.version_baseline {
+        if let Err(e) = specifier.parse::<SpecifierSet>() {
+            diagnostics.push(Diagnostic::new(
+                Severity::Error,
+                format!("invalid version baseline for '{}': {}", name, e),
+                locate(&[Key("version_baseline"), Key(name)]),
+            ));
+        }
+    }
+
+    let detector = ServiceDetector::new(Vec::new());
+    let mut expanded = Vec::new();
+    for (index, service_config) in config.services.iter().enumerate() {
+        let location = locate(&[Key("services"), Index(index), Key("path")]);
+        let services = match detector.expand_config(service_config, &config.root) {
+            Ok(services) => services,
+            Err(e) => {
+                diagnostics.push(Diagnostic::new(Severity::Error, e.to_string(), location));
+                continue;
+            }
+        };
+
+        if services.is_empty() {
+            diagnostics.push(Diagnostic::new(
+                Severity::Warning,
+                format!("service pattern '{}' matches no directories", service_config.path.display()),
+                location,
+            ));
+        }
+        if let Some(alias) = service_config.alias.as_ref().filter(|_| services.len() > 1) {
+            diagnostics.push(Diagnostic::new(
+                Severity::Error,
+                format!(
+                    "service pattern '{}' matches {} directories, which cannot share the alias '{}'",
+                    service_config.path.display(),
+                    services.len(),
+                    alias,
+                ),
+                locate(&[Key("services"), Index(index), Key("alias")]),
+            ));
+        }
+
+        for service in services {
+            if !service.root_path.exists() {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Error,
+                    format!("service '{}' path '{}' does not exist", service.name, service.root_path.display()),
+                    location,
+                ));
+                continue;
+            } else if ignore.matches(&service.root_path) {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Warning,
+                    format!("service '{}' path '{}' is ignored", service.name, service.root_path.display()),
+                    location,
+                ));
+            }
+
+            for (key, paths) in [("include_paths", &service.include_paths), ("exclude_paths", &service.exclude_paths)] {
+                for (path_index, path) in paths.iter().enumerate() {
+                    let location = locate(&[Key("services"), Index(index), Key(key), Index(path_index)]);
+                    if !path.exists() {
+                        diagnostics.push(Diagnostic::new(
+                            Severity::Error,
+                            format!("service '{}' {} entry '{}' does not exist", service.name, key, path.display()),
+                            location,
+                        ));
+                    }
+                }
+            }
+
+            for (path_index, exclude) in service.exclude_paths.iter().enumerate() {
+                let reachable = exclude.starts_with(&service.root_path)
+                    || service.include_paths.iter().any(|p| exclude.starts_with(p));
+                if !reachable {
+                    diagnostics.push(Diagnostic::new(
+                        Severity::Warning,
+                        format!(
+                            "service '{}' exclude path '{}' is outside the service and its include paths, so it never applies",
+                            service.name,
+                            exclude.display()
+                        ),
+                        locate(&[Key("services"), Index(index), Key("exclude_paths"), Index(path_index)]),
+                    ));
+                }
+            }
+
+            expanded.push((index, service));
+        }
+    }
+
+    let configured_tags: Vec<&String> = expanded.iter().flat_map(|(_, s)| &s.tags).collect();
+    for (index, rule) in config.impact_rules.iter().enumerate() {
+        for (key, values) in [("services", &rule.services), ("tags", &rule.tags)] {
+            for (value_index, value) in values.iter().enumerate() {
+                let known = match key {
+                    "services" => expanded.iter().any(|(_, s)| &s.name == value),
+                    _ => configured_tags.contains(&value),
+                };
+                if !known {
+                    let kind = if key == "services" { "service" } else { "tag" };
+                    diagnostics.push(Diagnostic::new(
+                        Severity::Warning,
+                        format!("impact rule refers to {} '{}' which no configured service has", kind, value),
+                        locate(&[Key("impact_rules"), Index(index), Key(key), Index(value_index)]),
+                    ));
+                }
+            }
+        }
+    }
+
+    for (i, (index_a, a)) in expanded.iter().enumerate() {
+        for (index_b, b) in &expanded[i + 1..] {
+            let location = locate(&[Key("services"), Index(*index_b), Key("path")]);
+            if a.name == b.name {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Error,
+                    format!(
+                        "service name '{}' is used by services {} and {} ({} and {})",
+                        a.name, index_a, index_b, a.root_path.display(), b.root_path.display()
+                    ),
+                    location,
+                ));
+            }
+            if a.root_path == b.root_path {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Error,
+                    format!("services '{}' and '{}' share the path '{}'", a.name, b.name, a.root_path.display()),
+                    location,
+                ));
+            } else if a.root_path.starts_with(&b.root_path) || b.root_path.starts_with(&a.root_path) {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Warning,
+                    format!(
+                        "services '{}' ({}) and '{}' ({}) overlap",
+                        a.name, a.root_path.display(), b.name, b.root_path.display()
+                    ),
+                    location,
+                ));
+            }
+        }
+    }
+}
+
+fn single_line(message: &str) -> String {
+    message.lines().map(str::trim).filter(|l| !l.is_empty()).collect::<Vec<_>>().join(": ")
+}
+
+/// Convert a byte offset into a 1-based line and column
+fn line_column(content: &str, offset: usize) -> Location {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+    (line, column)
+}
@@ -0,0 +1,250 @@
+use crate::utils::PathPatterns;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+lazy_static! {
+    static ref PACKAGE_DATA_ENTRY: Regex =
+        Regex::new(r#"['"]([^'"]*)['"]\s*:\s*\[([^\]]*)\]"#).unwrap();
+    static ref QUOTED_STRING: Regex = Regex::new(r#"['"]([^'"]+)['"]"#).unwrap();
+}
+
+/// A configured rule attributing non-Python files to the modules that load them
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AssetRule {
+    /// Globs, relative to the config directory, for the asset files
+    pub assets: Vec<String>,
+    /// Globs, relative to the config directory, for the Python modules that load them
+    pub modules: Vec<String>,
+}
+
+/// An asset rule with its globs compiled against a root directory
+#[derive(Debug, Clone)]
+pub struct CompiledAssetRule {
+    assets: PathPatterns,
+    modules: PathPatterns,
+}
+
+impl CompiledAssetRule {
+    pub fn new(root: &Path, rule: &AssetRule) -> Result<Self, glob::PatternError> {
+        Ok(Self {
+            assets: PathPatterns::new(root, &rule.assets)?,
+            modules: PathPatterns::new(root, &rule.modules)?,
+        })
+    }
+
+    pub fn matches_asset(&self, path: &Path) -> bool {
+        self.assets.matches(path)
+    }
+
+    pub fn matches_module(&self, path: &Path) -> bool {
+        self.modules.matches(path)
+    }
+}
+
+pub fn is_asset_file(path: &Path) -> bool {
+    path.is_file() && !crate::utils::is_python_file(path)
+}
+
+/// The `__init__.py` of the innermost package containing `path`, if any
+pub fn owning_package(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .skip(1)
+        .map(|dir| dir.join("__init__.py"))
+        .find(|init| init.is_file())
+}
+
+/// Parse `package_data` from a `setup.py` into `(package, patterns)` pairs
+pub fn parse_setup_py_package_data(content: &str) -> Vec<(String, Vec<String>)> {
+    let Some(start) = content.find("package_data") else {
+        return Vec::new();
+    };
+    let Some(open) = content[start..].find('{').map(|i| start + i) else {
+        return Vec::new();
+    };
+
+    // Find the matching closing brace of the dict literal
+    let mut depth = 0;
+    let mut end = content.len();
+    for (i, c) in content[open..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    end = open + i;
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    PACKAGE_DATA_ENTRY.captures_iter(&content[open..end])
+        .map(|captures| {
+            let patterns = QUOTED_STRING.captures_iter(&captures[2])
+                .map(|c| c[1].to_string())
+                .collect();
+            (captures[1].to_string(), patterns)
+        })
+        .collect()
+}
+
+/// Parse `[tool.setuptools.package-data]` from a `pyproject.toml` into `(package, patterns)` pairs
+pub fn parse_pyproject_package_data(content: &str) -> Vec<(String, Vec<String>)> {
+    let Ok(toml) = content.parse::<toml::Table>() else {
+        return Vec::new();
+    };
+    let Some(package_data) = toml.get("tool")
+        .and_then(|t| t.get("setuptools"))
+        .and_then(|s| s.get("package-data"))
+        .and_then(|p| p.as_table())
+    else {
+        return Vec::new();
+    };
+
+    package_data.iter()
+        .map(|(package, patterns)| {
+            let patterns = patterns.as_array()
+                .map(|a| a.iter().filter_map(|p| p.as_str().map(String::from)).collect())
+                .unwrap_or_default();
+            (package.clone(), patterns)
+        })
+        .collect()
+}
+
+/// Resolve `package_data` entries of the project in `project_dir` to `(module, asset)` pairs,
+/// where the module is the `__init__.py` of the package owning the data
+pub fn package_data_assets(project_dir: &Path, package_data: &[(String, Vec<String>)]) -> Vec<(PathBuf, PathBuf)> {
+    let mut pairs = Vec::new();
+    for (package, patterns) in package_data {
+        for package_dir in find_package_dirs(project_dir, package) {
+            for pattern in patterns {
+                let pattern = package_dir.join(pattern);
+                let Ok(matches) = glob::glob(&pattern.to_string_lossy()) else {
+                    continue;
+                };
+                for asset in matches.filter_map(|m| m.ok()).filter(|m| is_asset_file(m)) {
+                    pairs.push((package_dir.join("__init__.py"), asset));
+                }
+            }
+        }
+    }
+    pairs
+}
+
+/// Directories of the package named `package` (or of every package, for `""` and `"*"`)
+fn find_package_dirs(project_dir: &Path, package: &str) -> Vec<PathBuf> {
+    if package.is_empty() || package == "*" {
+        return WalkDir::new(project_dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name() == "__init__.py")
+            .filter_map(|e| e.path().parent().map(Path::to_path_buf))
+            .collect();
+    }
+
+    let relative = PathBuf::from(package.replace('.', "/"));
+    [project_dir.join(&relative), project_dir.join("src").join(&relative)]
+        .into_iter()
+        .filter(|dir| dir.join("__init__.py").is_file())
+        .collect()
+}
+
+/// Files included in a source distribution by the `MANIFEST.in` in `project_dir`, leaving
+/// out `ignore`d paths
+pub fn manifest_files(project_dir: &Path, content: &str, ignore: &PathPatterns) -> BTreeSet<PathBuf> {
+    let mut files = BTreeSet::new();
+    // Walked once; every directive selects from the same list
+    let all_files: Vec<PathBuf> = WalkDir::new(project_dir)
+        .into_iter()
+        .filter_entry(|e| !ignore.matches(e.path()))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+        .collect();
+    let name_matches = |path: &Path, patterns: &[glob::Pattern]| {
+        let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        patterns.iter().any(|p| p.matches(&name))
+    };
+    let compile = |patterns: &[&str]| -> Vec<glob::Pattern> {
+        patterns.iter().filter_map(|p| glob::Pattern::new(p).ok()).collect()
+    };
+
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            continue;
+        };
+        let args: Vec<&str> = words.collect();
+
+        match command {
+            "include" | "exclude" => {
+                let patterns = compile(&args);
+                let matched: Vec<PathBuf> = all_files
+                    .iter()
+                    .filter(|f| {
+                        let relative = crate::utils::relative_path_string(f, project_dir).unwrap_or_default();
+                        patterns.iter().any(|p| p.matches(&relative))
+                    })
+                    .cloned()
+                    .collect();
+                apply(&mut files, command == "include", matched);
+            }
+            "recursive-include" | "recursive-exclude" => {
+                let Some((dir, patterns)) = args.split_first() else {
+                    continue;
+                };
+                let patterns = compile(patterns);
+                let base = project_dir.join(dir);
+                let matched: Vec<PathBuf> = all_files
+                    .iter()
+                    .filter(|f| f.starts_with(&base) && name_matches(f, &patterns))
+                    .cloned()
+                    .collect();
+                apply(&mut files, command == "recursive-include", matched);
+            }
+            "global-include" | "global-exclude" => {
+                let patterns = compile(&args);
+                let matched: Vec<PathBuf> =
+                    all_files.iter().filter(|f| name_matches(f, &patterns)).cloned().collect();
+                apply(&mut files, command == "global-include", matched);
+            }
+            "graft" | "prune" => {
+                let bases: Vec<PathBuf> = args.iter().map(|d| project_dir.join(d)).collect();
+                let matched: Vec<PathBuf> = all_files
+                    .iter()
+                    .filter(|f| bases.iter().any(|b| f.starts_with(b)))
+                    .cloned()
+                    .collect();
+                apply(&mut files, command == "graft", matched);
+            }
+            _ => {}
+        }
+    }
+
+    files
+}
+
+fn apply(files: &mut BTreeSet<PathBuf>, include: bool, matched: Vec<PathBuf>) {
+    for file in matched {
+        if include {
+            files.insert(file);
+        } else {
+            files.remove(&file);
+        }
+    }
+}
+
+/// Attribute `MANIFEST.in` assets to the packages that contain them
+pub fn manifest_assets(project_dir: &Path, content: &str, ignore: &PathPatterns) -> Vec<(PathBuf, PathBuf)> {
+    manifest_files(project_dir, content, ignore)
+        .into_iter()
+        .filter(|f| !crate::utils::is_python_file(f))
+        .filter_map(|asset| Some((owning_package(&asset)?, asset)))
+        .collect()
+}
@@ -0,0 +1,156 @@
+use super::DependencyGraph;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+/// Which tests executed each source file, from per-test coverage data
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoverageMap {
+    /// Directory holding the data, where coverage ran; relative file paths start here
+    pub directory: PathBuf,
+    /// Node IDs of the tests executing each file, such as `tests/test_api.py::test_get`
+    pub files: BTreeMap<PathBuf, BTreeSet<String>>,
+}
+
+/// The pytest node ID of a coverage context. pytest-cov's `--cov-context=test` records the
+/// node ID followed by the phase, as in `tests/test_api.py::test_get|run`; lines run outside
+/// any test have an empty context.
+fn node_id(context: &str) -> Option<&str> {
+    let node_id = context.rsplit_once('|')
+        .filter(|(_, phase)| matches!(*phase, "setup" | "run" | "teardown"))
+        .map_or(context, |(node_id, _)| node_id);
+    (!node_id.is_empty()).then_some(node_id)
+}
+
+/// A test that executed a file, identified by its pytest node ID
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct CoveredTest {
+    /// The test file
+    pub file: PathBuf,
+    /// The rest of the node ID, such as `TestApi::test_get[json]`
+    pub name: String,
+}
+
+impl CoveredTest {
+    /// The test a node ID names, with its file relative to `rootdir`
+    pub fn from_node_id(rootdir: &Path, node_id: &str) -> Self {
+        let (file, name) = node_id.split_once("::").unwrap_or((node_id, ""));
+        Self { file: crate::utils::clean_path(&rootdir.join(file)), name: name.to_string() }
+    }
+
+    /// The node ID, with the test file written as `file`
+    pub fn node_id(&self, file: &str) -> String {
+        if self.name.is_empty() { file.to_string() } else { format!("{}::{}", file, self.name) }
+    }
+}
+
+impl CoverageMap {
+    /// Read coverage data recorded with per-test contexts: a `.coverage` SQLite database or
+    /// the output of `coverage json --show-contexts`. Relative file paths are resolved against
+    /// the directory holding the data; node IDs are kept as recorded.
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+        let bytes = std::fs::read(path)?;
+        let directory = crate::utils::clean_path(&std::path::absolute(path.parent().unwrap_or(Path::new("")))?);
+        let contexts = if bytes.starts_with(SQLITE_HEADER) {
+            Self::sqlite_contexts(path)
+                .map_err(|e| invalid(format!("Invalid coverage database {}: {}", path.display(), e)))?
+        } else {
+            let json: serde_json::Value = serde_json::from_slice(&bytes)
+                .map_err(|e| invalid(format!("Invalid coverage data {}: {}", path.display(), e)))?;
+            Self::json_contexts(&json).ok_or_else(|| invalid(format!(
+                "{} has no per-test contexts; write it with `coverage json --show-contexts`",
+                path.display(),
+            )))?
+        };
+
+        let map = Self::from_contexts(&directory, contexts);
+        if map.files.is_empty() {
+            return Err(invalid(format!(
+                "{} records no tests; run pytest with --cov-context=test",
+                path.display(),
+            )));
+        }
+        Ok(map)
+    }
+
+    /// Pairs of file and context from a coverage JSON report
+    fn json_contexts(json: &serde_json::Value) -> Option<Vec<(String, String)>> {
+        let files = json.get("files")?.as_object()?;
+        let mut pairs = Vec::new();
+        for (file, data) in files {
+            let contexts = data.get("contexts")?.as_object()?;
+            let names: BTreeSet<&str> = contexts.values()
+                .filter_map(|c| c.as_array())
+                .flatten()
+                .filter_map(|c| c.as_str())
+                .collect();
+            pairs.extend(names.into_iter().map(|context| (file.clone(), context.to_string())));
+        }
+        Some(pairs)
+    }
+
+    /// Pairs of file and context from a coverage database, with line or branch data
+    fn sqlite_contexts(path: &Path) -> rusqlite::Result<Vec<(String, String)>> {
+        let connection = rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let mut statement = connection.prepare(
+            "SELECT DISTINCT file.path, context.context \
+             FROM (SELECT file_id, context_id FROM line_bits UNION SELECT file_id, context_id FROM arc) AS measured \
+             JOIN file ON file.id = measured.file_id \
+             JOIN context ON context.id = measured.context_id",
+        )?;
+        let rows = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
+    }
+
+    fn from_contexts(directory: &Path, contexts: Vec<(String, String)>) -> Self {
+        let mut files: BTreeMap<PathBuf, BTreeSet<String>> = BTreeMap::new();
+        for (file, context) in contexts {
+            if let Some(node_id) = node_id(&context) {
+                let file = crate::utils::clean_path(&directory.join(file));
+                files.entry(file).or_default().insert(node_id.to_string());
+            }
+        }
+        Self { directory: directory.to_path_buf(), files }
+    }
+
+    /// Node IDs of the tests that executed `file`
+    pub fn node_ids(&self, file: &Path) -> impl Iterator<Item = &str> {
+        self.files.get(file).into_iter().flatten().map(String::as_str)
+    }
+
+    /// The same map with every path passed through `f`
+    pub(super) fn map_paths(self, f: impl Fn(&Path) -> PathBuf) -> Self {
+        Self {
+            directory: f(&self.directory),
+            files: self.files.into_iter().map(|(file, tests)| (f(&file), tests)).collect(),
+        }
+    }
+}
+
+impl DependencyGraph {
+    /// Combine static analysis with the tests that executed each file in `coverage`
+    pub fn with_coverage(mut self, coverage: CoverageMap) -> Self {
+        self.coverage = Some(coverage);
+        self
+    }
+
+    pub fn coverage(&self) -> Option<&CoverageMap> {
+        self.coverage.as_ref()
+    }
+
+    /// The tests that executed `file`, when coverage data is loaded. Node IDs are relative to
+    /// the pytest rootdir of the directory coverage ran in, or to that directory when no pytest
+    /// configuration is found above it.
+    pub fn tests_covering(&self, file: &Path) -> Vec<CoveredTest> {
+        let Some(coverage) = &self.coverage else {
+            return Vec::new();
+        };
+        let rootdir = coverage.directory.ancestors()
+            .find(|dir| self.pytest_roots.contains_key(*dir))
+            .unwrap_or(&coverage.directory);
+        coverage.node_ids(file).map(|node_id| CoveredTest::from_node_id(rootdir, node_id)).collect()
+    }
+}
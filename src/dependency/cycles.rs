@@ -0,0 +1,292 @@
+use super::DependencyGraph;
+use crate::scan::import_statements;
+use crate::utils::is_python_file;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// What the nodes of a cycle are
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CycleLevel {
+    File,
+    /// The directory containing each file
+    Package,
+    Service,
+}
+
+impl CycleLevel {
+    pub const ALL: [CycleLevel; 3] = [CycleLevel::File, CycleLevel::Package, CycleLevel::Service];
+}
+
+impl std::str::FromStr for CycleLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "file" => Ok(CycleLevel::File),
+            "package" => Ok(CycleLevel::Package),
+            "service" => Ok(CycleLevel::Service),
+            _ => Err("Invalid cycle level".into()),
+        }
+    }
+}
+
+impl fmt::Display for CycleLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CycleLevel::File => write!(f, "file"),
+            CycleLevel::Package => write!(f, "package"),
+            CycleLevel::Service => write!(f, "service"),
+        }
+    }
+}
+
+/// An import statement behind an edge of a cycle
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportSite {
+    pub file: PathBuf,
+    /// 1-based line number
+    pub line: usize,
+    pub text: String,
+    /// The file the statement resolves to
+    pub target: PathBuf,
+}
+
+/// An edge between two members of a cycle, with the imports that create it
+#[derive(Debug, Clone)]
+pub struct CycleEdge {
+    pub from: String,
+    pub to: String,
+    pub imports: Vec<ImportSite>,
+}
+
+/// A strongly connected component of the import graph at some level
+#[derive(Debug, Clone)]
+pub struct ImportCycle {
+    pub level: CycleLevel,
+    /// Members in sorted order
+    pub members: Vec<String>,
+    pub edges: Vec<CycleEdge>,
+}
+
+/// Strongly connected components with more than one node, or a node importing itself,
+/// found with an iterative Tarjan's algorithm. Components and their members are sorted.
+pub fn strongly_connected_components<N: Ord + Clone>(edges: &BTreeMap<N, BTreeSet<N>>) -> Vec<Vec<N>> {
+    let nodes: BTreeSet<&N> = edges.iter()
+        .flat_map(|(from, tos)| std::iter::once(from).chain(tos))
+        .collect();
+    let index_of: BTreeMap<&N, usize> = nodes.iter().enumerate().map(|(i, n)| (*n, i)).collect();
+    let nodes: Vec<&N> = nodes.into_iter().collect();
+    let successors: Vec<Vec<usize>> = nodes.iter()
+        .map(|n| edges.get(*n).map(|tos| tos.iter().map(|t| index_of[t]).collect()).unwrap_or_default())
+        .collect();
+
+    const UNVISITED: usize = usize::MAX;
+    let mut index = vec![UNVISITED; nodes.len()];
+    let mut low_link = vec![0; nodes.len()];
+    let mut on_stack = vec![false; nodes.len()];
+    let mut stack = Vec::new();
+    let mut next_index = 0;
+    let mut components = Vec::new();
+
+    for root in 0..nodes.len() {
+        if index[root] != UNVISITED {
+            continue;
+        }
+        // Each frame is a node and the position of the next successor to visit
+        let mut frames = vec![(root, 0)];
+        index[root] = next_index;
+        low_link[root] = next_index;
+        next_index += 1;
+        stack.push(root);
+        on_stack[root] = true;
+
+        while let Some(&mut (node, ref mut next)) = frames.last_mut() {
+            if let Some(&successor) = successors[node].get(*next) {
+                *next += 1;
+                if index[successor] == UNVISITED {
+                    index[successor] = next_index;
+                    low_link[successor] = next_index;
+                    next_index += 1;
+                    stack.push(successor);
+                    on_stack[successor] = true;
+                    frames.push((successor, 0));
+                } else if on_stack[successor] {
+                    low_link[node] = low_link[node].min(index[successor]);
+                }
+                continue;
+            }
+
+            frames.pop();
+            if let Some(&(parent, _)) = frames.last() {
+                low_link[parent] = low_link[parent].min(low_link[node]);
+            }
+            if low_link[node] == index[node] {
+                let mut component = Vec::new();
+                while let Some(member) = stack.pop() {
+                    on_stack[member] = false;
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                let self_loop = successors[node].contains(&node);
+                if component.len() > 1 || self_loop {
+                    let mut component: Vec<N> = component.into_iter().map(|i| nodes[i].clone()).collect();
+                    component.sort();
+                    components.push(component);
+                }
+            }
+        }
+    }
+    components.sort();
+    components
+}
+
+impl DependencyGraph {
+    /// Import cycles at `level`, each with the import statements behind its edges
+    pub fn cycles(&self, level: CycleLevel) -> Vec<ImportCycle> {
+        // Group import edges between Python files by the nodes at this level
+        let mut witnesses: BTreeMap<(String, String), Vec<(PathBuf, PathBuf)>> = BTreeMap::new();
+        for (from, targets) in &self.deps {
+            if !is_python_file(from) {
+                continue;
+            }
+            for to in targets.iter().filter(|to| is_python_file(to)) {
+                for from_node in self.nodes_for(level, from) {
+                    for to_node in self.nodes_for(level, to) {
+                        if level != CycleLevel::File && from_node == to_node {
+                            continue;
+                        }
+                        witnesses.entry((from_node.clone(), to_node))
+                            .or_default()
+                            .push((from.clone(), to.clone()));
+                    }
+                }
+            }
+        }
+
+        let mut edges: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for (from, to) in witnesses.keys() {
+            edges.entry(from.clone()).or_default().insert(to.clone());
+        }
+
+        strongly_connected_components(&edges)
+            .into_iter()
+            .map(|members| {
+                let member_set: BTreeSet<&String> = members.iter().collect();
+                let edges = witnesses.iter()
+                    .filter(|((from, to), _)| member_set.contains(from) && member_set.contains(to))
+                    .map(|((from, to), files)| {
+                        let mut files = files.clone();
+                        files.sort();
+                        let mut imports: Vec<ImportSite> = files.iter()
+                            .flat_map(|(f, t)| self.import_sites(f, t))
+                            .collect();
+                        // A statement importing a submodule also resolves to its package's __init__.py
+                        imports.sort_by_key(|site| {
+                            (site.file.clone(), site.line, site.target.ends_with("__init__.py"))
+                        });
+                        imports.dedup_by(|a, b| a.file == b.file && a.line == b.line);
+                        CycleEdge { from: from.clone(), to: to.clone(), imports }
+                    })
+                    .collect();
+                ImportCycle { level, members, edges }
+            })
+            .collect()
+    }
+
+    /// The import statements in `from` that resolve to `to`
+    pub fn import_sites(&self, from: &Path, to: &Path) -> Vec<ImportSite> {
+        let Ok(content) = std::fs::read_to_string(from) else {
+            return Vec::new();
+        };
+        let root = self.root_of(from).unwrap_or_else(|| from.parent().unwrap_or(from).to_path_buf());
+        import_statements(&content)
+            .filter(|(_, module, _)| self.resolve_module(&root, module).iter().any(|target| target == to))
+            .map(|(line, _, text)| ImportSite {
+                file: from.to_path_buf(),
+                line,
+                text: text.trim().to_string(),
+                target: to.to_path_buf(),
+            })
+            .collect()
+    }
+
+    fn nodes_for(&self, level: CycleLevel, file: &Path) -> Vec<String> {
+        match level {
+            CycleLevel::File => vec![file.display().to_string()],
+            CycleLevel::Package => file.parent().map(|p| p.display().to_string()).into_iter().collect(),
+            CycleLevel::Service => self.services_for_file(file).into_iter().map(|s| s.name.clone()).collect(),
+        }
+    }
+}
+
+/// Format version of the cycle baseline file
+pub const BASELINE_VERSION: u32 = 1;
+
+/// Service-level cycles that are already known, so CI only fails on new ones
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CycleBaseline {
+    pub version: u32,
+    /// Sorted member names of each accepted cycle
+    pub service_cycles: Vec<Vec<String>>,
+}
+
+impl Default for CycleBaseline {
+    fn default() -> Self {
+        Self { version: BASELINE_VERSION, service_cycles: Vec::new() }
+    }
+}
+
+impl CycleBaseline {
+    pub fn from_cycles(cycles: &[ImportCycle]) -> Self {
+        let mut service_cycles: Vec<Vec<String>> = cycles.iter()
+            .filter(|cycle| cycle.level == CycleLevel::Service)
+            .map(|cycle| cycle.members.clone())
+            .collect();
+        service_cycles.sort();
+        Self { version: BASELINE_VERSION, service_cycles }
+    }
+
+    /// Load a baseline, treating a missing file as an empty one
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        let baseline: Self = serde_json::from_str(&content).map_err(|e| std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Invalid cycle baseline {}: {}", path.display(), e),
+        ))?;
+        if baseline.version != BASELINE_VERSION {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Unsupported cycle baseline version {} in {}", baseline.version, path.display()),
+            ));
+        }
+        Ok(baseline)
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        std::fs::write(path, json + "\n")
+    }
+
+    /// Service-level cycles in `cycles` whose member set is not in the baseline
+    pub fn new_cycles<'a>(&self, cycles: &'a [ImportCycle]) -> Vec<&'a ImportCycle> {
+        cycles.iter()
+            .filter(|cycle| cycle.level == CycleLevel::Service)
+            .filter(|cycle| {
+                !self.service_cycles.iter().any(|known| {
+                    let mut known = known.clone();
+                    known.sort();
+                    known == cycle.members
+                })
+            })
+            .collect()
+    }
+}
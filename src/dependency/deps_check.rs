@@ -0,0 +1,231 @@
+use super::DependencyGraph;
+use crate::package_deps::models::DependencyFile;
+use crate::package_deps::names::{import_names, normalize_name};
+use crate::package_deps::stdlib::is_stdlib_module;
+use crate::service::models::DetectedService;
+use crate::utils::relative_path_string;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Text,
+    Json,
+}
+
+impl std::str::FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err("Invalid report format".into()),
+        }
+    }
+}
+
+/// The `[deps_check]` configuration section
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DepsCheckConfig {
+    /// Top-level modules of distributions that are not imported under their own name,
+    /// e.g. `{ "my-sdk" = ["mysdk"] }`
+    #[serde(default)]
+    pub import_names: BTreeMap<String, Vec<String>>,
+    /// Globs for distributions never reported as unused, such as plugins and servers that are
+    /// only run, never imported
+    #[serde(default)]
+    pub allow_unused: Vec<String>,
+    /// Globs for top-level modules never reported as undeclared
+    #[serde(default)]
+    pub allow_undeclared: Vec<String>,
+}
+
+/// A `[deps_check]` section with its names normalized and globs compiled
+#[derive(Debug, Clone, Default)]
+pub struct DepsCheck {
+    import_names: BTreeMap<String, Vec<String>>,
+    allow_unused: Vec<glob::Pattern>,
+    allow_undeclared: Vec<glob::Pattern>,
+}
+
+impl DepsCheck {
+    pub fn new(config: &DepsCheckConfig) -> Result<Self, glob::PatternError> {
+        let compile = |patterns: &[String], normalize: bool| {
+            patterns.iter()
+                .map(|p| glob::Pattern::new(&if normalize { normalize_name(p) } else { p.clone() }))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(Self {
+            import_names: config.import_names.iter()
+                .map(|(distribution, modules)| (normalize_name(distribution), modules.clone()))
+                .collect(),
+            allow_unused: compile(&config.allow_unused, true)?,
+            allow_undeclared: compile(&config.allow_undeclared, false)?,
+        })
+    }
+}
+
+/// A third-party module a service imports without declaring a distribution providing it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UndeclaredImport {
+    pub module: String,
+    /// Importing files, relative to the root
+    pub files: Vec<String>,
+}
+
+/// A declared distribution none of the service's files import
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnusedRequirement {
+    pub package: String,
+    /// Dependency files declaring it, relative to the root
+    pub declared_in: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ServiceDependencies {
+    pub service: String,
+    /// The service's own dependency files followed by those in directories above it
+    pub dependency_files: Vec<String>,
+    pub undeclared: Vec<UndeclaredImport>,
+    pub unused: Vec<UnusedRequirement>,
+}
+
+impl ServiceDependencies {
+    pub fn is_clean(&self) -> bool {
+        self.undeclared.is_empty() && self.unused.is_empty()
+    }
+}
+
+/// Declared and imported third-party dependencies compared for every service
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DepsCheckReport {
+    pub services: Vec<ServiceDependencies>,
+}
+
+impl DepsCheckReport {
+    pub fn is_clean(&self) -> bool {
+        self.services.iter().all(ServiceDependencies::is_clean)
+    }
+
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Text => self.to_text(),
+            ReportFormat::Json => serde_json::to_string_pretty(self).expect("reports always serialize") + "\n",
+        }
+    }
+
+    /// Services with findings only
+    pub fn to_text(&self) -> String {
+        if self.is_clean() {
+            return "No undeclared or unused dependencies\n".to_string();
+        }
+        let mut out = String::new();
+        for service in self.services.iter().filter(|s| !s.is_clean()) {
+            let _ = writeln!(out, "{}:", service.service);
+            for import in &service.undeclared {
+                let _ = writeln!(out, "  undeclared {} (imported by {})", import.module, import.files.join(", "));
+            }
+            for requirement in &service.unused {
+                let _ = writeln!(out, "  unused {} (declared in {})", requirement.package, requirement.declared_in.join(", "));
+            }
+        }
+        out
+    }
+}
+
+impl DependencyGraph {
+    /// For each service, third-party modules imported but not declared in its own dependency
+    /// files or those of the directories above it, and distributions declared in its own
+    /// dependency files but never imported
+    pub fn check_dependencies(&self, check: &DepsCheck, root: &Path) -> DepsCheckReport {
+        let relative = |path: &Path| relative_path_string(path, root).unwrap_or_else(|| path.display().to_string());
+        // Other services are first-party even when installed as distributions
+        let first_party: BTreeSet<String> = self.services.values()
+            .flat_map(|s| [normalize_name(&s.name), normalize_name(&s.name).replace('-', "_")])
+            .collect();
+
+        let mut files_by_service: BTreeMap<&str, Vec<&PathBuf>> = BTreeMap::new();
+        let mut python_files: Vec<&PathBuf> = self.module_imports.keys().collect();
+        python_files.sort();
+        for file in python_files {
+            for service in self.services_for_file(file) {
+                files_by_service.entry(service.name.as_str()).or_default().push(file);
+            }
+        }
+
+        let mut dependency_files: Vec<&DependencyFile> = self.package_deps.dependency_files().values().collect();
+        dependency_files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let mut services: Vec<&DetectedService> = self.services.values().collect();
+        services.sort_by(|a, b| a.name.cmp(&b.name));
+        let services = services.into_iter()
+            .map(|service| {
+                let files = files_by_service.get(service.name.as_str()).cloned().unwrap_or_default();
+                let (own, inherited): (Vec<&DependencyFile>, Vec<&DependencyFile>) = dependency_files.iter()
+                    .filter(|dep| service.contains_path(&dep.path) || self.is_inherited(service, &dep.path))
+                    .partition(|dep| service.contains_path(&dep.path));
+
+                let declared: BTreeSet<String> = own.iter()
+                    .chain(&inherited)
+                    .flat_map(|dep| &dep.dependencies)
+                    .flat_map(|req| import_names(&req.name, &check.import_names))
+                    .collect();
+
+                let mut undeclared: BTreeMap<String, Vec<String>> = BTreeMap::new();
+                let mut imported: BTreeSet<String> = BTreeSet::new();
+                for file in &files {
+                    let external = self.external_packages(file);
+                    for module in external.iter().filter(|m| !is_stdlib_module(m)) {
+                        let allowed = check.allow_undeclared.iter().any(|p| p.matches(module));
+                        if !declared.contains(module) && !allowed && !first_party.contains(module) {
+                            undeclared.entry(module.clone()).or_default().push(relative(file));
+                        }
+                    }
+                    imported.extend(external);
+                    // Imports inside functions and try blocks count as uses too
+                    let nested = self.package_deps.imports().get(*file).into_iter().flatten();
+                    imported.extend(nested.map(|import| import.package_name.clone()));
+                }
+
+                let mut unused: BTreeMap<String, Vec<String>> = BTreeMap::new();
+                for dep in &own {
+                    for req in &dep.dependencies {
+                        let name = normalize_name(&req.name);
+                        let allowed = check.allow_unused.iter().any(|p| p.matches(&name));
+                        let used = import_names(&req.name, &check.import_names).iter().any(|m| imported.contains(m));
+                        if !used && !allowed && !first_party.contains(&name) {
+                            unused.entry(name).or_default().push(relative(&dep.path));
+                        }
+                    }
+                }
+
+                ServiceDependencies {
+                    service: service.name.clone(),
+                    dependency_files: own.iter().chain(&inherited).map(|dep| relative(&dep.path)).collect(),
+                    undeclared: undeclared.into_iter()
+                        .map(|(module, files)| UndeclaredImport { module, files })
+                        .collect(),
+                    unused: unused.into_iter()
+                        .map(|(package, mut declared_in)| {
+                            declared_in.dedup();
+                            UnusedRequirement { package, declared_in }
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+        DepsCheckReport { services }
+    }
+
+    /// Whether a dependency file outside `service` sits in a directory above it, within the
+    /// directories the graph was built from
+    fn is_inherited(&self, service: &DetectedService, dependency_file: &Path) -> bool {
+        let Some(dir) = dependency_file.parent() else {
+            return false;
+        };
+        service.root_path.starts_with(dir) && self.roots.iter().any(|root| dir.starts_with(root))
+    }
+}
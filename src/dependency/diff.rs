@@ -0,0 +1,261 @@
+use super::cycles::CycleLevel;
+use super::DependencyGraph;
+use crate::package_deps::stdlib::is_stdlib_module;
+use crate::utils::{is_python_file, relative_path_string};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffFormat {
+    Text,
+    Json,
+    Markdown,
+}
+
+impl std::str::FromStr for DiffFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(DiffFormat::Text),
+            "json" => Ok(DiffFormat::Json),
+            "markdown" => Ok(DiffFormat::Markdown),
+            _ => Err("Invalid diff format".into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct DiffEdge {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct EdgeChanges {
+    pub added: Vec<DiffEdge>,
+    pub removed: Vec<DiffEdge>,
+}
+
+impl EdgeChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// A third-party package imported in head but nowhere in base
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NewPackage {
+    pub name: String,
+    /// Files importing it, relative to the root
+    pub files: Vec<String>,
+}
+
+/// A cycle in head whose member set is not a cycle in base
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NewCycle {
+    pub level: CycleLevel,
+    pub members: Vec<String>,
+}
+
+/// How the dependency graph changed between a base and a head revision. Files and packages
+/// are named relative to each graph's root, so graphs of different checkouts compare.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct GraphDiff {
+    pub service_edges: EdgeChanges,
+    pub package_edges: EdgeChanges,
+    pub file_edges: EdgeChanges,
+    pub new_packages: Vec<NewPackage>,
+    pub new_cycles: Vec<NewCycle>,
+}
+
+/// The parts of a graph that are compared, with paths relative to its root
+#[derive(Default)]
+struct Summary {
+    edges: BTreeMap<CycleLevel, BTreeSet<DiffEdge>>,
+    packages: BTreeMap<String, BTreeSet<String>>,
+    cycles: BTreeMap<CycleLevel, BTreeSet<Vec<String>>>,
+}
+
+fn relative(path: &Path, root: &Path) -> String {
+    match relative_path_string(path, root) {
+        Some(relative) if relative.is_empty() => ".".to_string(),
+        Some(relative) => relative,
+        None => path.display().to_string(),
+    }
+}
+
+impl Summary {
+    fn of(graph: &DependencyGraph, root: &Path) -> Self {
+        let mut summary = Summary::default();
+        for (from, targets) in &graph.deps {
+            if !is_python_file(from) {
+                continue;
+            }
+            for to in targets.iter().filter(|to| is_python_file(to)) {
+                let mut add = |level: CycleLevel, from: String, to: String| {
+                    if level == CycleLevel::File || from != to {
+                        summary.edges.entry(level).or_default().insert(DiffEdge { from, to });
+                    }
+                };
+                add(CycleLevel::File, relative(from, root), relative(to, root));
+                if let (Some(from_dir), Some(to_dir)) = (from.parent(), to.parent()) {
+                    add(CycleLevel::Package, relative(from_dir, root), relative(to_dir, root));
+                }
+                for from_service in graph.services_for_file(from) {
+                    for to_service in graph.services_for_file(to) {
+                        add(CycleLevel::Service, from_service.name.clone(), to_service.name.clone());
+                    }
+                }
+            }
+        }
+
+        for file in graph.module_imports.keys() {
+            for package in graph.external_packages(file).into_iter().filter(|p| !is_stdlib_module(p)) {
+                summary.packages.entry(package).or_default().insert(relative(file, root));
+            }
+        }
+
+        for level in CycleLevel::ALL {
+            for cycle in graph.cycles(level) {
+                let mut members: Vec<String> = match level {
+                    CycleLevel::Service => cycle.members,
+                    CycleLevel::File | CycleLevel::Package => {
+                        cycle.members.iter().map(|m| relative(Path::new(m), root)).collect()
+                    }
+                };
+                members.sort();
+                summary.cycles.entry(level).or_default().insert(members);
+            }
+        }
+        summary
+    }
+}
+
+impl GraphDiff {
+    /// Compare `base`, built from `base_root`, with `head`, built from `head_root`
+    pub fn between(base: &DependencyGraph, base_root: &Path, head: &DependencyGraph, head_root: &Path) -> Self {
+        let base = Summary::of(base, base_root);
+        let head = Summary::of(head, head_root);
+        let empty = BTreeSet::new();
+        let changes = |level: CycleLevel| {
+            let before = base.edges.get(&level).unwrap_or(&empty);
+            let after = head.edges.get(&level).unwrap_or(&empty);
+            EdgeChanges {
+                added: after.difference(before).cloned().collect(),
+                removed: before.difference(after).cloned().collect(),
+            }
+        };
+
+        GraphDiff {
+            service_edges: changes(CycleLevel::Service),
+            package_edges: changes(CycleLevel::Package),
+            file_edges: changes(CycleLevel::File),
+            new_packages: head.packages.iter()
+                .filter(|(name, _)| !base.packages.contains_key(*name))
+                .map(|(name, files)| NewPackage { name: name.clone(), files: files.iter().cloned().collect() })
+                .collect(),
+            new_cycles: CycleLevel::ALL.iter()
+                .flat_map(|level| {
+                    let known = base.cycles.get(level);
+                    head.cycles.get(level)
+                        .into_iter()
+                        .flatten()
+                        .filter(move |members| !known.is_some_and(|known| known.contains(*members)))
+                        .map(|members| NewCycle { level: *level, members: members.clone() })
+                })
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.service_edges.is_empty()
+            && self.package_edges.is_empty()
+            && self.file_edges.is_empty()
+            && self.new_packages.is_empty()
+            && self.new_cycles.is_empty()
+    }
+
+    fn edge_sections(&self) -> [(&'static str, &EdgeChanges); 3] {
+        [
+            ("Service edges", &self.service_edges),
+            ("Package edges", &self.package_edges),
+            ("File edges", &self.file_edges),
+        ]
+    }
+
+    pub fn render(&self, format: DiffFormat) -> String {
+        match format {
+            DiffFormat::Text => self.to_text(),
+            DiffFormat::Json => serde_json::to_string_pretty(self).expect("diffs always serialize") + "\n",
+            DiffFormat::Markdown => self.to_markdown(),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        if self.is_empty() {
+            return "No dependency changes\n".to_string();
+        }
+        let mut out = String::new();
+        for (title, changes) in self.edge_sections().into_iter().filter(|(_, c)| !c.is_empty()) {
+            let _ = writeln!(out, "{}: {} added, {} removed", title, changes.added.len(), changes.removed.len());
+            for edge in &changes.added {
+                let _ = writeln!(out, "  + {} -> {}", edge.from, edge.to);
+            }
+            for edge in &changes.removed {
+                let _ = writeln!(out, "  - {} -> {}", edge.from, edge.to);
+            }
+        }
+        if !self.new_packages.is_empty() {
+            let _ = writeln!(out, "New third-party packages: {}", self.new_packages.len());
+            for package in &self.new_packages {
+                let _ = writeln!(out, "  + {} ({})", package.name, package.files.join(", "));
+            }
+        }
+        if !self.new_cycles.is_empty() {
+            let _ = writeln!(out, "New cycles: {}", self.new_cycles.len());
+            for cycle in &self.new_cycles {
+                let _ = writeln!(out, "  + {}: {}", cycle.level, cycle.members.join(", "));
+            }
+        }
+        out
+    }
+
+    /// A report suitable for a pull request comment
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::from("## Dependency changes\n\n");
+        if self.is_empty() {
+            out.push_str("No dependency changes.\n");
+            return out;
+        }
+        for (title, changes) in self.edge_sections().into_iter().filter(|(_, c)| !c.is_empty()) {
+            let _ = writeln!(out, "### {} ({} added, {} removed)\n", title, changes.added.len(), changes.removed.len());
+            out.push_str("| Change | From | To |\n|---|---|---|\n");
+            for (change, edges) in [("added", &changes.added), ("removed", &changes.removed)] {
+                for edge in edges {
+                    let _ = writeln!(out, "| {} | `{}` | `{}` |", change, edge.from, edge.to);
+                }
+            }
+            out.push('\n');
+        }
+        if !self.new_packages.is_empty() {
+            out.push_str("### New third-party packages\n\n");
+            for package in &self.new_packages {
+                let files: Vec<String> = package.files.iter().map(|f| format!("`{}`", f)).collect();
+                let _ = writeln!(out, "- `{}` imported by {}", package.name, files.join(", "));
+            }
+            out.push('\n');
+        }
+        if !self.new_cycles.is_empty() {
+            out.push_str("### New cycles\n\n");
+            for cycle in &self.new_cycles {
+                let members: Vec<String> = cycle.members.iter().map(|m| format!("`{}`", m)).collect();
+                let _ = writeln!(out, "- {}: {}", cycle.level, members.join(", "));
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
@@ -0,0 +1,372 @@
+use super::DependencyGraph;
+use crate::package_deps::stdlib::is_stdlib_module;
+use crate::utils::{is_python_file, relative_path_string};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// What each node of an exported graph stands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Granularity {
+    File,
+    /// Dotted Python module names
+    Module,
+    /// The directory containing each file
+    Package,
+    Service,
+}
+
+impl std::str::FromStr for Granularity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "file" => Ok(Granularity::File),
+            "module" => Ok(Granularity::Module),
+            "package" => Ok(Granularity::Package),
+            "service" => Ok(Granularity::Service),
+            _ => Err("Invalid granularity".into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Dot,
+    Mermaid,
+    Json,
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(ExportFormat::Dot),
+            "mermaid" => Ok(ExportFormat::Mermaid),
+            "json" => Ok(ExportFormat::Json),
+            _ => Err("Invalid export format".into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NodeKind {
+    FirstParty,
+    /// Non-Python files loaded by modules
+    Asset,
+    /// Imported packages that do not resolve to a file in the repository
+    External,
+}
+
+/// Which part of the graph to export and how
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    pub granularity: Granularity,
+    /// Directory file and package labels are relative to
+    pub root: PathBuf,
+    /// Only export what this service's files import, directly or transitively
+    pub service: Option<String>,
+    /// Leave out external packages
+    pub first_party_only: bool,
+    /// Import steps to follow from the service's files
+    pub max_depth: Option<usize>,
+    /// Changed files to highlight along with everything they affect
+    pub changed: Vec<PathBuf>,
+}
+
+impl ExportOptions {
+    pub fn new(granularity: Granularity) -> Self {
+        Self {
+            granularity,
+            root: PathBuf::new(),
+            service: None,
+            first_party_only: false,
+            max_depth: None,
+            changed: Vec::new(),
+        }
+    }
+
+    pub fn with_root(mut self, root: &Path) -> Self {
+        self.root = root.to_path_buf();
+        self
+    }
+
+    pub fn with_service(mut self, service: Option<String>) -> Self {
+        self.service = service;
+        self
+    }
+
+    pub fn with_first_party_only(mut self, first_party_only: bool) -> Self {
+        self.first_party_only = first_party_only;
+        self
+    }
+
+    pub fn with_max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn with_changed(mut self, changed: Vec<PathBuf>) -> Self {
+        self.changed = changed;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphNode {
+    pub id: String,
+    pub label: String,
+    pub kind: NodeKind,
+    /// Contains a changed file
+    pub changed: bool,
+    /// Contains a file affected by the changed files
+    pub affected: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphEdge {
+    pub from: String,
+    pub to: String,
+    /// Number of file-level imports the edge stands for
+    pub imports: usize,
+}
+
+/// A dependency graph collapsed to one granularity, ready to render
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportedGraph {
+    pub granularity: Granularity,
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+impl ExportedGraph {
+    pub fn render(&self, format: ExportFormat) -> String {
+        match format {
+            ExportFormat::Dot => self.to_dot(),
+            ExportFormat::Mermaid => self.to_mermaid(),
+            ExportFormat::Json => self.to_json(),
+        }
+    }
+
+    /// Graphviz DOT, with changed nodes in red and affected ones in orange
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph dependencies {\n    rankdir=LR;\n    node [shape=box];\n");
+        for node in &self.nodes {
+            let mut attributes = vec![format!("label={}", dot_quote(&node.label))];
+            match node.kind {
+                NodeKind::FirstParty => {}
+                NodeKind::Asset => attributes.push("shape=note".into()),
+                NodeKind::External => attributes.push("shape=ellipse, style=dashed".into()),
+            }
+            if let Some(color) = highlight_color(node) {
+                attributes.push(format!("style=filled, fillcolor=\"{}\"", color));
+            }
+            let _ = writeln!(out, "    {} [{}];", dot_quote(&node.id), attributes.join(", "));
+        }
+        for edge in &self.edges {
+            let _ = writeln!(out, "    {} -> {};", dot_quote(&edge.from), dot_quote(&edge.to));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// A Mermaid flowchart; node ids are positional since Mermaid ids cannot hold paths
+    pub fn to_mermaid(&self) -> String {
+        let ids: HashMap<&str, String> = self.nodes.iter()
+            .enumerate()
+            .map(|(index, node)| (node.id.as_str(), format!("n{}", index)))
+            .collect();
+        let mut out = String::from("graph LR\n");
+        for node in &self.nodes {
+            let label = node.label.replace('"', "#quot;");
+            let shape = match node.kind {
+                NodeKind::External => format!("([\"{}\"])", label),
+                NodeKind::FirstParty | NodeKind::Asset => format!("[\"{}\"]", label),
+            };
+            let _ = writeln!(out, "    {}{}", ids[node.id.as_str()], shape);
+        }
+        for edge in &self.edges {
+            let _ = writeln!(out, "    {} --> {}", ids[edge.from.as_str()], ids[edge.to.as_str()]);
+        }
+        for (class, color) in [("changed", CHANGED_COLOR), ("affected", AFFECTED_COLOR)] {
+            let members: Vec<&str> = self.nodes.iter()
+                .filter(|node| highlight_color(node) == Some(color))
+                .map(|node| ids[node.id.as_str()].as_str())
+                .collect();
+            if !members.is_empty() {
+                let _ = writeln!(out, "    classDef {} fill:{}", class, color);
+                let _ = writeln!(out, "    class {} {}", members.join(","), class);
+            }
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("exported graphs always serialize") + "\n"
+    }
+}
+
+const CHANGED_COLOR: &str = "#f4a6a6";
+const AFFECTED_COLOR: &str = "#fde3a7";
+
+fn highlight_color(node: &GraphNode) -> Option<&'static str> {
+    if node.changed {
+        Some(CHANGED_COLOR)
+    } else if node.affected {
+        Some(AFFECTED_COLOR)
+    } else {
+        None
+    }
+}
+
+fn dot_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl DependencyGraph {
+    /// Collapse the graph to `options.granularity`, keeping only the part selected by the
+    /// service, depth and first-party filters
+    pub fn export(&self, options: &ExportOptions) -> ExportedGraph {
+        let depths = self.export_depths(options);
+        let changed: HashSet<&Path> = options.changed.iter().map(PathBuf::as_path).collect();
+        let mut affected: HashSet<PathBuf> = HashSet::new();
+        let mut affected_services: HashSet<&str> = HashSet::new();
+        for file in &options.changed {
+            affected.extend(self.get_affected_files_within(file, self.max_depth).into_keys());
+            affected_services.extend(self.explain(file).into_iter().map(|impact| impact.name));
+        }
+
+        let mut nodes: BTreeMap<String, GraphNode> = BTreeMap::new();
+        let mut file_nodes: HashMap<&Path, Vec<String>> = HashMap::new();
+        for file in depths.keys() {
+            let kind = if is_python_file(file) { NodeKind::FirstParty } else { NodeKind::Asset };
+            let ids = self.export_nodes(options, file);
+            for (id, label) in &ids {
+                let node = nodes.entry(id.clone()).or_insert_with(|| GraphNode {
+                    id: id.clone(),
+                    label: label.clone(),
+                    kind,
+                    changed: false,
+                    affected: false,
+                });
+                node.kind = node.kind.min(kind);
+                node.changed |= changed.contains(file.as_path());
+                node.affected |= affected.contains(file.as_path())
+                    || (options.granularity == Granularity::Service && affected_services.contains(id.as_str()));
+            }
+            file_nodes.insert(file.as_path(), ids.into_iter().map(|(id, _)| id).collect());
+        }
+
+        let mut edges: BTreeMap<(String, String), usize> = BTreeMap::new();
+        for (file, depth) in &depths {
+            for target in self.deps.get(file).into_iter().flatten() {
+                let Some(target_ids) = file_nodes.get(target.as_path()) else {
+                    continue;
+                };
+                for from in &file_nodes[file.as_path()] {
+                    for to in target_ids.iter().filter(|to| *to != from) {
+                        *edges.entry((from.clone(), to.clone())).or_default() += 1;
+                    }
+                }
+            }
+
+            if options.first_party_only || options.max_depth.is_some_and(|max| *depth >= max) {
+                continue;
+            }
+            for package in self.external_packages(file).into_iter().filter(|p| !is_stdlib_module(p)) {
+                let id = format!("external:{}", package);
+                nodes.entry(id.clone()).or_insert_with(|| GraphNode {
+                    id: id.clone(),
+                    label: package,
+                    kind: NodeKind::External,
+                    changed: false,
+                    affected: false,
+                });
+                for from in &file_nodes[file.as_path()] {
+                    *edges.entry((from.clone(), id.clone())).or_default() += 1;
+                }
+            }
+        }
+
+        ExportedGraph {
+            granularity: options.granularity,
+            nodes: nodes.into_values().collect(),
+            edges: edges.into_iter()
+                .map(|((from, to), imports)| GraphEdge { from, to, imports })
+                .collect(),
+        }
+    }
+
+    /// Files to export with their import distance from the selected service, or every known
+    /// file at depth 0 when no service is selected
+    fn export_depths(&self, options: &ExportOptions) -> HashMap<PathBuf, usize> {
+        let known: BTreeSet<&PathBuf> = self.deps.keys()
+            .chain(self.reverse_deps.keys())
+            .chain(self.module_imports.keys())
+            .chain(self.asset_files.iter())
+            .collect();
+        let roots: Vec<&PathBuf> = match &options.service {
+            Some(service) => known.into_iter()
+                .filter(|file| self.services_for_file(file).iter().any(|s| &s.name == service))
+                .collect(),
+            None => known.into_iter().collect(),
+        };
+
+        let mut depths: HashMap<PathBuf, usize> = roots.iter().map(|file| ((*file).clone(), 0)).collect();
+        let mut queue: VecDeque<PathBuf> = roots.into_iter().cloned().collect();
+        while let Some(file) = queue.pop_front() {
+            let depth = depths[&file];
+            if options.max_depth.is_some_and(|max| depth >= max) {
+                continue;
+            }
+            for target in self.deps.get(&file).into_iter().flatten() {
+                if !depths.contains_key(target) {
+                    depths.insert(target.clone(), depth + 1);
+                    queue.push_back(target.clone());
+                }
+            }
+        }
+        depths
+    }
+
+    /// Node ids and labels `file` belongs to at the requested granularity
+    fn export_nodes(&self, options: &ExportOptions, file: &Path) -> Vec<(String, String)> {
+        let relative = |path: &Path| match relative_path_string(path, &options.root) {
+            Some(relative) if relative.is_empty() => ".".to_string(),
+            Some(relative) => relative,
+            None => path.display().to_string(),
+        };
+        let node = |name: String| vec![(name.clone(), name)];
+        match options.granularity {
+            Granularity::File => node(relative(file)),
+            // The shortest name is the one relative to the innermost source root
+            Granularity::Module => match self.module_names(file).into_iter().min_by_key(|name| name.len()) {
+                Some(module) if is_python_file(file) => node(module),
+                _ => node(relative(file)),
+            },
+            Granularity::Package => node(relative(file.parent().unwrap_or(file))),
+            Granularity::Service => self.services_for_file(file)
+                .into_iter()
+                .map(|service| (service.name.clone(), service.name.clone()))
+                .collect(),
+        }
+    }
+
+    /// Top-level names of the modules `file` imports that do not resolve to a repository file
+    pub(super) fn external_packages(&self, file: &Path) -> BTreeSet<String> {
+        let Some(modules) = self.module_imports.get(file) else {
+            return BTreeSet::new();
+        };
+        let root = self.root_of(file).unwrap_or_else(|| file.parent().unwrap_or(file).to_path_buf());
+        modules.iter()
+            .filter(|module| !module.starts_with('.'))
+            .filter(|module| self.resolve_module(&root, module).is_empty())
+            .filter_map(|module| module.split('.').next())
+            .map(str::to_string)
+            .collect()
+    }
+}
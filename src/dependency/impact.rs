@@ -0,0 +1,115 @@
+use super::coverage::CoveredTest;
+use crate::service::models::DetectedService;
+use crate::utils::PathPatterns;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// A configured rule mapping changed paths to the services they affect
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImpactRule {
+    /// Globs, relative to the config directory, for files the rule applies to
+    pub paths: Vec<String>,
+    /// Affect every service
+    #[serde(default)]
+    pub all: bool,
+    /// Names of affected services
+    #[serde(default)]
+    pub services: Vec<String>,
+    /// Tags of affected services
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Optional explanation shown in explain output
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+impl ImpactRule {
+    /// A rule that makes `paths` affect every service
+    pub fn all_services(paths: Vec<String>) -> Self {
+        Self {
+            paths,
+            all: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn affects(&self, service: &DetectedService) -> bool {
+        self.all
+            || self.services.contains(&service.name)
+            || service.tags.iter().any(|tag| self.tags.contains(tag))
+    }
+
+    /// Short description of the rule for explain output
+    pub fn describe(&self) -> String {
+        match &self.reason {
+            Some(reason) => format!("{} ({})", self.paths.join(", "), reason),
+            None => self.paths.join(", "),
+        }
+    }
+}
+
+/// An impact rule with its path globs compiled against a root directory
+#[derive(Debug, Clone)]
+pub struct CompiledImpactRule {
+    pub rule: ImpactRule,
+    patterns: PathPatterns,
+}
+
+impl CompiledImpactRule {
+    pub fn new(root: &Path, rule: ImpactRule) -> Result<Self, glob::PatternError> {
+        Ok(Self {
+            patterns: PathPatterns::new(root, &rule.paths)?,
+            rule,
+        })
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        self.patterns.matches(path)
+    }
+}
+
+/// Why a service is affected by a change
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImpactReason {
+    /// The changed file belongs to the service
+    ContainsChange,
+    /// A file in the service imports the changed file, possibly transitively;
+    /// the chain runs from the changed file to the importing file
+    Imports { chain: Vec<PathBuf> },
+    /// A file in the service imports a package declared in the changed dependency file
+    UsesPackageFrom { dependency_file: PathBuf, file: PathBuf },
+    /// A configured impact rule matched the changed file
+    Rule { description: String },
+    /// Coverage data shows a test of the service executing the changed file
+    ExecutedBy { test: CoveredTest },
+}
+
+impl std::fmt::Display for ImpactReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImpactReason::ContainsChange => write!(f, "contains the changed file"),
+            ImpactReason::Imports { chain } => {
+                let chain: Vec<String> = chain.iter().rev().map(|p| p.display().to_string()).collect();
+                write!(f, "imports: {}", chain.join(" -> "))
+            }
+            ImpactReason::UsesPackageFrom { dependency_file, file } => write!(
+                f,
+                "{} uses a package declared in {}",
+                file.display(),
+                dependency_file.display()
+            ),
+            ImpactReason::Rule { description } => write!(f, "impact rule: {}", description),
+            ImpactReason::ExecutedBy { test } => {
+                write!(f, "{} executes it (coverage)", test.node_id(&test.file.display().to_string()))
+            }
+        }
+    }
+}
+
+/// A service affected by a change, with every reason found
+#[derive(Debug, Clone)]
+pub struct ServiceImpact<'a> {
+    pub name: &'a str,
+    pub root_path: &'a Path,
+    pub reasons: Vec<ImpactReason>,
+}
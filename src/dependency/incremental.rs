@@ -0,0 +1,273 @@
+use super::pytest::is_pytest_config_file;
+use super::{is_project_file, DependencyGraph};
+use crate::package_deps::is_dependency_file;
+use crate::service::models::DetectedService;
+use crate::utils::is_python_file;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+impl DependencyGraph {
+    pub fn services(&self) -> &HashMap<String, DetectedService> {
+        &self.services
+    }
+
+    /// Whether `path` falls under a configured ignore glob
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        self.ignore.matches(path)
+    }
+
+    /// Replace the service map, e.g. after a service was added or removed
+    pub fn set_services(&mut self, services: HashMap<String, DetectedService>) {
+        self.services = services;
+    }
+
+    /// Re-index a created or modified file or directory
+    pub fn update_file(&mut self, path: &Path) -> std::io::Result<()> {
+        if !path.exists() {
+            return self.remove_file(path);
+        }
+        if self.ignore.matches(path) {
+            return Ok(());
+        }
+        let Some(root) = self.root_of(path) else {
+            return Ok(());
+        };
+
+        if path.is_dir() {
+            let ignore = self.ignore.clone();
+            let files: Vec<PathBuf> = walkdir::WalkDir::new(path)
+                .into_iter()
+                .filter_entry(|e| !ignore.matches(e.path()))
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_file())
+                .map(|e| e.into_path())
+                .collect();
+            for file in files {
+                self.update_file(&file)?;
+            }
+            return Ok(());
+        }
+
+        self.package_deps.remove_file(path);
+        let is_new = !self.module_imports.contains_key(path);
+        if is_python_file(path) {
+            self.remove_import_edges(path);
+        }
+        if is_python_file(path) || is_dependency_file(path) {
+            let content = fs::read_to_string(path)?;
+            let parsed = self.parse(path, &content);
+            self.index_parsed_file(&root, path, parsed);
+        }
+
+        if is_python_file(path) {
+            if is_new {
+                self.link_importers(path);
+                self.link_asset_rules_for_module(path);
+            }
+        } else if self.asset_files.insert(path.to_path_buf()) {
+            self.link_new_asset(path);
+        }
+
+        if is_project_file(path) {
+            if let Some(project_dir) = path.parent() {
+                self.refresh_project_assets(project_dir);
+            }
+        }
+        self.refresh_pytest_scoping(path);
+        Ok(())
+    }
+
+    /// Drop a deleted file, or every known file under a deleted directory
+    pub fn remove_file(&mut self, path: &Path) -> std::io::Result<()> {
+        let known: Vec<PathBuf> = self.module_imports.keys()
+            .chain(self.asset_files.iter())
+            .chain(self.deps.keys())
+            .chain(self.reverse_deps.keys())
+            .filter(|f| f.starts_with(path))
+            .cloned()
+            .collect();
+
+        for file in known {
+            self.package_deps.remove_file(&file);
+            self.module_imports.remove(&file);
+            self.asset_files.remove(&file);
+            if let Some(targets) = self.deps.remove(&file) {
+                for target in targets {
+                    self.remove_reverse_edge(&target, &file);
+                }
+            }
+            if let Some(sources) = self.reverse_deps.remove(&file) {
+                for source in sources {
+                    if let Some(targets) = self.deps.get_mut(&source) {
+                        targets.remove(&file);
+                    }
+                }
+            }
+        }
+        self.package_deps.remove_file(path);
+
+        if is_project_file(path) {
+            if let Some(project_dir) = path.parent() {
+                self.refresh_project_assets(project_dir);
+            }
+        }
+        self.refresh_pytest_scoping(path);
+        Ok(())
+    }
+
+    /// Move a file or directory from `from` to `to`
+    pub fn rename_file(&mut self, from: &Path, to: &Path) -> std::io::Result<()> {
+        self.remove_file(from)?;
+        self.update_file(to)
+    }
+
+    /// Update which conftest.py files tests load after `path` changed; a re-indexed Python
+    /// file loses its conftest edges along with its imports
+    fn refresh_pytest_scoping(&mut self, path: &Path) {
+        let removed_dir = !path.exists() && self.pytest_roots.keys().any(|dir| dir.starts_with(path));
+        if removed_dir {
+            self.pytest_roots.retain(|dir, _| !dir.starts_with(path));
+        } else if is_pytest_config_file(path) {
+            if let Some(dir) = path.parent() {
+                self.refresh_pytest_root(dir);
+            }
+        }
+        if is_python_file(path) || is_pytest_config_file(path) || !path.exists() {
+            self.link_conftests();
+        }
+    }
+
+    pub(super) fn root_of(&self, path: &Path) -> Option<PathBuf> {
+        self.roots.iter()
+            .filter(|root| path.starts_with(root))
+            .max_by_key(|root| root.components().count())
+            .cloned()
+    }
+
+    /// Remove edges from `path` to the Python modules it imports, keeping its asset edges
+    fn remove_import_edges(&mut self, path: &Path) {
+        let Some(targets) = self.deps.get_mut(path) else {
+            return;
+        };
+        let imports: Vec<PathBuf> = targets.iter().filter(|t| is_python_file(t)).cloned().collect();
+        for target in &imports {
+            targets.remove(target);
+        }
+        for target in imports {
+            self.remove_reverse_edge(&target, path);
+        }
+    }
+
+    pub(super) fn remove_reverse_edge(&mut self, target: &Path, source: &Path) {
+        if let Some(sources) = self.reverse_deps.get_mut(target) {
+            sources.remove(source);
+            if sources.is_empty() {
+                self.reverse_deps.remove(target);
+            }
+        }
+    }
+
+    /// Dotted module names a Python file can be imported as, relative to each root
+    pub(super) fn module_names(&self, path: &Path) -> Vec<String> {
+        self.roots.iter()
+            .chain(&self.source_roots)
+            .filter_map(|root| path.strip_prefix(root).ok())
+            .filter_map(|relative| {
+                let relative = relative.with_extension("");
+                let mut parts: Vec<String> = relative.components()
+                    .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                    .collect();
+                if parts.last().is_some_and(|p| p == "__init__") {
+                    parts.pop();
+                }
+                (!parts.is_empty()).then(|| parts.join("."))
+            })
+            .collect()
+    }
+
+    /// Link files whose imports now resolve to a newly created module
+    fn link_importers(&mut self, path: &Path) {
+        let names = self.module_names(path);
+        let importers: Vec<(PathBuf, String)> = self.module_imports.iter()
+            .filter(|(importer, _)| importer.as_path() != path)
+            .flat_map(|(importer, modules)| {
+                modules.iter()
+                    .filter(|module| {
+                        names.iter().any(|name| {
+                            module == &name || module.starts_with(&format!("{}.", name))
+                        })
+                    })
+                    .map(move |module| (importer.clone(), module.clone()))
+            })
+            .collect();
+
+        for (importer, module) in importers {
+            let Some(root) = self.root_of(&importer) else {
+                continue;
+            };
+            for target in self.resolve_module(&root, &module) {
+                self.add_dependency(importer.clone(), target);
+            }
+        }
+    }
+
+    fn python_files(&self) -> Vec<PathBuf> {
+        self.module_imports.keys().cloned().collect()
+    }
+
+    fn project_dir_of(&self, path: &Path) -> Option<PathBuf> {
+        path.ancestors()
+            .skip(1)
+            .take_while(|dir| self.root_of(dir).is_some())
+            .find(|dir| {
+                ["setup.py", "pyproject.toml", "MANIFEST.in"].iter().any(|f| dir.join(f).is_file())
+            })
+            .map(Path::to_path_buf)
+    }
+
+    fn link_new_asset(&mut self, asset: &Path) {
+        let project_dirs: Vec<PathBuf> = self.project_dir_of(asset).into_iter().collect();
+        let edges = self.asset_edges(&project_dirs, &self.python_files(), &[asset.to_path_buf()]);
+        for (module, target) in edges {
+            if target == asset {
+                self.add_dependency(module, target);
+            }
+        }
+    }
+
+    fn link_asset_rules_for_module(&mut self, module: &Path) {
+        let edges: Vec<(PathBuf, PathBuf)> = self.asset_rules.iter()
+            .filter(|rule| rule.matches_module(module))
+            .flat_map(|rule| self.asset_files.iter().filter(|asset| rule.matches_asset(asset)))
+            .map(|asset| (module.to_path_buf(), asset.clone()))
+            .collect();
+        for (from, to) in edges {
+            self.add_dependency(from, to);
+        }
+    }
+
+    /// Recompute asset edges for every asset under `project_dir` after its packaging metadata changed
+    fn refresh_project_assets(&mut self, project_dir: &Path) {
+        let assets: Vec<PathBuf> = self.asset_files.iter()
+            .filter(|asset| asset.starts_with(project_dir))
+            .cloned()
+            .collect();
+        for asset in &assets {
+            if let Some(sources) = self.reverse_deps.remove(asset) {
+                for source in sources {
+                    if let Some(targets) = self.deps.get_mut(&source) {
+                        targets.remove(asset);
+                    }
+                }
+            }
+        }
+
+        let project_dirs = if project_dir.exists() { vec![project_dir.to_path_buf()] } else { Vec::new() };
+        for (module, asset) in self.asset_edges(&project_dirs, &self.python_files(), &assets) {
+            if asset.starts_with(project_dir) {
+                self.add_dependency(module, asset);
+            }
+        }
+    }
+}
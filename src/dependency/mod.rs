@@ -1,8 +1,30 @@
-use std::collections::{HashMap, HashSet};
+pub mod assets;
+pub mod coverage;
+pub mod impact;
+pub mod pytest;
+pub mod rules;
+pub mod selection;
+mod incremental;
+pub mod cycles;
+pub mod deps_check;
+pub mod diff;
+pub mod export;
+pub mod snapshot;
+pub mod traversal;
+pub mod versions;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::fs;
+use crate::cache::{models::ParsedFile, AnalysisCache};
 use crate::package_deps::PackageDependencyManager;
+use crate::scan::{self, RepositoryScan};
 use crate::service::models::DetectedService;
+use crate::utils::{is_python_file, PathPatterns};
+use assets::CompiledAssetRule;
+use impact::{CompiledImpactRule, ImpactReason, ImpactRule, ServiceImpact};
+use coverage::CoverageMap;
+use selection::TestSelector;
 
 #[derive(Debug)]
 pub struct DependencyGraph {
@@ -14,6 +36,32 @@ pub struct DependencyGraph {
     package_deps: PackageDependencyManager,
     // Services
     services: HashMap<String, DetectedService>,
+    // Additional directories absolute imports are resolved against
+    source_roots: Vec<PathBuf>,
+    // Paths excluded from scanning
+    ignore: PathPatterns,
+    // Rules mapping changed paths to services, evaluated alongside imports
+    impact_rules: Vec<CompiledImpactRule>,
+    // Rules attributing non-Python files to the modules that load them
+    asset_rules: Vec<CompiledAssetRule>,
+    // Directories the graph was built from
+    roots: Vec<PathBuf>,
+    // Module names imported by each Python file, kept to re-link files as they appear
+    module_imports: HashMap<PathBuf, Vec<String>>,
+    // Non-Python files seen while scanning
+    asset_files: HashSet<PathBuf>,
+    // Parse results from earlier runs
+    cache: Option<AnalysisCache>,
+    // How many import steps impact analysis follows; `None` for the full transitive closure
+    max_depth: Option<usize>,
+    // Recognizes test files, which depend on the conftest.py files above them
+    test_selector: TestSelector,
+    // Directories with a pytest config file, with their configured testpaths
+    pytest_roots: HashMap<PathBuf, Vec<PathBuf>>,
+    // Edges added from test and conftest files to the conftest.py files pytest loads for them
+    conftest_links: BTreeSet<(PathBuf, PathBuf)>,
+    // Tests that executed each file, from per-test coverage data
+    coverage: Option<CoverageMap>,
 }
 
 impl Default for DependencyGraph {
@@ -29,7 +77,82 @@ impl DependencyGraph {
             reverse_deps: HashMap::new(),
             package_deps: PackageDependencyManager::new(),
             services: HashMap::new(),
+            source_roots: Vec::new(),
+            ignore: PathPatterns::default(),
+            impact_rules: Vec::new(),
+            asset_rules: Vec::new(),
+            roots: Vec::new(),
+            module_imports: HashMap::new(),
+            asset_files: HashSet::new(),
+            cache: None,
+            max_depth: None,
+            test_selector: TestSelector::default(),
+            pytest_roots: HashMap::new(),
+            conftest_links: BTreeSet::new(),
+            coverage: None,
+        }
+    }
+
+    pub fn with_source_roots(mut self, source_roots: Vec<PathBuf>) -> Self {
+        self.source_roots = source_roots;
+        self
+    }
+
+    pub fn with_ignore(mut self, ignore: PathPatterns) -> Self {
+        self.package_deps = self.package_deps.with_ignore(ignore.clone());
+        self.ignore = ignore;
+        self
+    }
+
+    /// Reuse parse results from `cache` for files whose contents are unchanged
+    pub fn with_cache(mut self, cache: AnalysisCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Cache hits and misses so far, if a cache is in use
+    pub fn cache_stats(&self) -> Option<(usize, usize)> {
+        self.cache.as_ref().map(AnalysisCache::stats)
+    }
+
+    /// Write the cache, if any, and stop using it
+    pub fn save_cache(&mut self) -> std::io::Result<()> {
+        match self.cache.take() {
+            Some(mut cache) => cache.save(),
+            None => Ok(()),
+        }
+    }
+
+    /// Only follow importers up to `max_depth` import steps when explaining impact
+    pub fn with_max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Recognize test files by `test_selector` instead of pytest's default file names
+    pub fn with_test_selector(mut self, test_selector: TestSelector) -> Self {
+        self.test_selector = test_selector;
+        self.link_conftests();
+        self
+    }
+
+    pub fn with_impact_rules(mut self, impact_rules: Vec<CompiledImpactRule>) -> Self {
+        self.impact_rules.extend(impact_rules);
+        self
+    }
+
+    pub fn with_asset_rules(mut self, asset_rules: Vec<CompiledAssetRule>) -> Self {
+        self.asset_rules.extend(asset_rules);
+        self
+    }
+
+    /// Make changes to any of `trigger_files` affect every service
+    pub fn with_trigger_files(self, root: &Path, trigger_files: &[String]) -> Result<Self, glob::PatternError> {
+        if trigger_files.is_empty() {
+            return Ok(self);
         }
+        let rule = CompiledImpactRule::new(root, ImpactRule::all_services(trigger_files.to_vec()))?;
+        Ok(self.with_impact_rules(vec![rule]))
     }
 
     pub fn add_dependency(&mut self, from: PathBuf, to: PathBuf) {
@@ -45,71 +168,265 @@ impl DependencyGraph {
     }
 
     pub fn get_affected_files(&self, changed_file: &Path) -> HashSet<PathBuf> {
-        let mut affected = HashSet::new();
-        
+        self.get_affected_files_within(changed_file, None).into_keys().collect()
+    }
+
+    /// Affected files with the number of import steps from the changed file, following
+    /// importers at most `max_depth` steps; files using a changed dependency file are at depth 1
+    pub fn get_affected_files_within(&self, changed_file: &Path, max_depth: Option<usize>) -> HashMap<PathBuf, usize> {
+        let mut affected = self.traverse_importers(changed_file, max_depth).depths;
+
         // Check if it's a dependency file
-        if self.package_deps.is_dependency_file(changed_file) {
-            affected.extend(self.package_deps.get_affected_by_dependency_change(changed_file));
+        if self.package_deps.is_dependency_file(changed_file) && max_depth != Some(0) {
+            for file in self.package_deps.get_affected_by_dependency_change(changed_file) {
+                affected.entry(file).or_insert(1);
+            }
         }
-        
-        // Get regular file dependencies
-        self.collect_affected_files(changed_file, &mut affected);
         affected
     }
 
-    fn collect_affected_files(&self, file: &Path, affected: &mut HashSet<PathBuf>) {
-        if affected.insert(file.to_path_buf()) {
-            if let Some(dependents) = self.reverse_deps.get(file) {
-                for dependent in dependents {
-                    self.collect_affected_files(dependent, affected);
+    pub fn get_affected_services(&self, changed_file: &Path) -> Vec<(&str, &Path)> {
+        self.explain(changed_file)
+            .into_iter()
+            .map(|impact| (impact.name, impact.root_path))
+            .collect()
+    }
+
+    /// Affected services for a changed file together with the reasons each is affected
+    pub fn explain(&self, changed_file: &Path) -> Vec<ServiceImpact<'_>> {
+        let mut impacts: BTreeMap<&str, ServiceImpact> = BTreeMap::new();
+
+        for rule in self.impact_rules.iter().filter(|r| r.matches(changed_file)) {
+            for service in self.services.values().filter(|s| rule.rule.affects(s)) {
+                add_reason(&mut impacts, service, ImpactReason::Rule { description: rule.rule.describe() });
+            }
+        }
+
+        if self.package_deps.is_dependency_file(changed_file) {
+            let mut users: Vec<PathBuf> = self.package_deps
+                .get_affected_by_dependency_change(changed_file)
+                .into_iter()
+                .collect();
+            users.sort();
+            for file in users {
+                for service in self.services_for_file(&file) {
+                    add_reason(&mut impacts, service, ImpactReason::UsesPackageFrom {
+                        dependency_file: changed_file.to_path_buf(),
+                        file: file.clone(),
+                    });
                 }
             }
         }
-    }
 
-    pub fn get_affected_services(&self, changed_file: &Path) -> Vec<(&str, &Path)> {
-        let affected_files = self.get_affected_files(changed_file);
-        let mut affected_services = HashMap::new();
-
-        for file in affected_files {
-            for (name, service) in &self.services {
-                if file.starts_with(&service.root_path) {
-                    affected_services.insert(name.as_str(), service.root_path.as_path());
-                    break;
+        // Importers in breadth-first order, so each service gets its shortest import chain
+        let traversal = self.traverse_importers(changed_file, self.max_depth);
+        for file in &traversal.order {
+            let reason = if file == changed_file {
+                ImpactReason::ContainsChange
+            } else {
+                ImpactReason::Imports { chain: traversal.chain(file) }
+            };
+            for service in self.services_for_file(file) {
+                let already_imports = impacts.get(service.name.as_str()).is_some_and(|i| {
+                    i.reasons.iter().any(|r| matches!(r, ImpactReason::Imports { .. } | ImpactReason::ContainsChange))
+                });
+                if !already_imports {
+                    add_reason(&mut impacts, service, reason.clone());
                 }
             }
         }
 
-        affected_services.into_iter().collect()
+        // Tests that executed the file catch what imports miss, such as dynamic dispatch
+        for test in self.tests_covering(changed_file) {
+            for service in self.services_for_file(&test.file) {
+                if !impacts.contains_key(service.name.as_str()) {
+                    add_reason(&mut impacts, service, ImpactReason::ExecutedBy { test: test.clone() });
+                }
+            }
+        }
+
+        impacts.into_values().collect()
+    }
+
+    /// Services a file belongs to: the innermost service whose root contains it,
+    /// plus every service that includes it through `include_paths`
+    pub fn services_for_file(&self, file: &Path) -> Vec<&DetectedService> {
+        let mut owner: Option<&DetectedService> = None;
+        let mut services = Vec::new();
+        for service in self.services.values() {
+            if !service.contains_path(file) {
+                continue;
+            }
+            if file.starts_with(&service.root_path) {
+                let deeper = owner.is_none_or(|o| {
+                    service.root_path.components().count() > o.root_path.components().count()
+                });
+                if deeper {
+                    owner = Some(service);
+                }
+            } else {
+                services.push(service);
+            }
+        }
+        services.extend(owner);
+        services
     }
 
     pub fn build_from_directory(&mut self, dir: &Path, services: HashMap<String, DetectedService>) -> std::io::Result<()> {
+        let scan = RepositoryScan::scan(dir, &self.ignore, self.cache.as_mut());
+        self.build_from_scan(&scan, services)
+    }
+
+    /// Build the graph from an already parsed scan of a directory
+    pub fn build_from_scan(&mut self, scan: &RepositoryScan, services: HashMap<String, DetectedService>) -> std::io::Result<()> {
+        let dir = scan.root.as_path();
         self.services = services;
-        self.package_deps.scan_directory(dir)?;
+        if !self.roots.iter().any(|root| root == dir) {
+            self.roots.push(dir.to_path_buf());
+        }
 
-        let walker = walkdir::WalkDir::new(dir)
-            .into_iter()
-            .filter_map(|e| e.ok());
-
-        for entry in walker {
-            let path = entry.path();
-            if path.extension().is_some_and(|ext| ext == "py") {
-                if let Ok(content) = fs::read_to_string(path) {
-                    // Scan for regular Python imports
-                    for line in content.lines() {
-                        if line.starts_with("from ") || line.starts_with("import ") {
-                            let imported = line.split_whitespace().nth(1).unwrap();
-                            let import_path = dir.join(imported.replace(".", "/") + ".py");
-                            if import_path.exists() {
-                                self.add_dependency(path.to_path_buf(), import_path);
-                            }
-                        }
+        let mut python_files = Vec::new();
+        let mut asset_files = Vec::new();
+        let mut project_dirs = Vec::new();
+        for entry in scan.files() {
+            let path = entry.path.as_path();
+            if let Some(parsed) = &entry.parsed {
+                self.index_parsed_file(dir, path, parsed.clone());
+            }
+            if is_python_file(path) {
+                python_files.push(path.to_path_buf());
+            } else {
+                asset_files.push(path.to_path_buf());
+            }
+
+            if is_project_file(path) {
+                if let Some(parent) = path.parent() {
+                    if !project_dirs.contains(&parent.to_path_buf()) {
+                        project_dirs.push(parent.to_path_buf());
                     }
                 }
             }
+            if pytest::is_pytest_config_file(path) {
+                if let Some(parent) = path.parent() {
+                    self.refresh_pytest_root(parent);
+                }
+            }
+        }
+
+        for (from, to) in self.asset_edges(&project_dirs, &python_files, &asset_files) {
+            self.add_dependency(from, to);
         }
+        self.asset_files.extend(asset_files);
+        self.link_conftests();
         Ok(())
     }
+
+    /// Parse a file's contents, reusing the cached result when they are unchanged
+    fn parse(&mut self, path: &Path, content: &str) -> ParsedFile {
+        match &mut self.cache {
+            Some(cache) => cache.parsed(path, content, || scan::parse_file(path, content)),
+            None => scan::parse_file(path, content),
+        }
+    }
+
+    /// Record a parsed file's package imports and requirements, and link a Python file
+    /// to the modules it imports
+    fn index_parsed_file(&mut self, root: &Path, path: &Path, parsed: ParsedFile) {
+        if let Some(dep_file) = parsed.dependency_file {
+            self.package_deps.add_dependency_file(dep_file);
+        }
+        if !is_python_file(path) {
+            return;
+        }
+        self.package_deps.add_imports(path, parsed.imports);
+        for module in &parsed.modules {
+            for import_path in self.resolve_module(root, module) {
+                self.add_dependency(path.to_path_buf(), import_path);
+            }
+        }
+        self.module_imports.insert(path.to_path_buf(), parsed.modules);
+    }
+
+    /// Edges from Python modules to the non-Python files they load, from `package_data`,
+    /// `MANIFEST.in` and configured asset rules
+    fn asset_edges(&self, project_dirs: &[PathBuf], python_files: &[PathBuf], asset_files: &[PathBuf]) -> Vec<(PathBuf, PathBuf)> {
+        let mut edges = Vec::new();
+        for project_dir in project_dirs {
+            let mut package_data = Vec::new();
+            if let Ok(content) = fs::read_to_string(project_dir.join("setup.py")) {
+                package_data.extend(assets::parse_setup_py_package_data(&content));
+            }
+            if let Ok(content) = fs::read_to_string(project_dir.join("pyproject.toml")) {
+                package_data.extend(assets::parse_pyproject_package_data(&content));
+            }
+            edges.extend(assets::package_data_assets(project_dir, &package_data));
+
+            if let Ok(content) = fs::read_to_string(project_dir.join("MANIFEST.in")) {
+                edges.extend(assets::manifest_assets(project_dir, &content, &self.ignore));
+            }
+        }
+
+        for rule in &self.asset_rules {
+            let modules: Vec<&PathBuf> = python_files.iter().filter(|f| rule.matches_module(f)).collect();
+            for asset in asset_files.iter().filter(|f| rule.matches_asset(f)) {
+                edges.extend(modules.iter().map(|module| ((*module).clone(), asset.clone())));
+            }
+        }
+
+        edges.retain(|(module, asset)| !self.ignore.matches(module) && !self.ignore.matches(asset));
+        edges
+    }
+
+    /// Resolve a dotted module name to a file under the scanned directory or a source root,
+    /// along with the `__init__.py` of each parent package Python imports on the way
+    fn resolve_module(&self, dir: &Path, module: &str) -> Vec<PathBuf> {
+        let parts: Vec<&str> = module.split('.').collect();
+        let relative = PathBuf::from(parts.join("/"));
+        let root = std::iter::once(dir)
+            .chain(self.source_roots.iter().map(PathBuf::as_path))
+            .find(|root| {
+                root.join(&relative).with_extension("py").is_file()
+                    || root.join(&relative).join("__init__.py").is_file()
+            });
+        let Some(root) = root else {
+            return Vec::new();
+        };
+
+        let mut resolved: Vec<PathBuf> = (1..parts.len())
+            .map(|depth| root.join(parts[..depth].join("/")).join("__init__.py"))
+            .filter(|init| init.is_file())
+            .collect();
+        let module_file = root.join(&relative).with_extension("py");
+        if module_file.is_file() {
+            resolved.push(module_file);
+        } else {
+            resolved.push(root.join(&relative).join("__init__.py"));
+        }
+        resolved
+    }
+}
+
+/// Files that describe a Python project and its packaged data
+fn is_project_file(path: &Path) -> bool {
+    matches!(
+        path.file_name().and_then(|n| n.to_str()),
+        Some("setup.py" | "pyproject.toml" | "MANIFEST.in")
+    )
+}
+
+fn add_reason<'a>(
+    impacts: &mut BTreeMap<&'a str, ServiceImpact<'a>>,
+    service: &'a DetectedService,
+    reason: ImpactReason,
+) {
+    let impact = impacts.entry(service.name.as_str()).or_insert_with(|| ServiceImpact {
+        name: service.name.as_str(),
+        root_path: service.root_path.as_path(),
+        reasons: Vec::new(),
+    });
+    if !impact.reasons.contains(&reason) {
+        impact.reasons.push(reason);
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,175 @@
+use super::DependencyGraph;
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Files pytest reads its configuration from, in the order it looks for them
+const PYTEST_CONFIG_FILES: &[&str] = &["pytest.ini", ".pytest.ini", "pyproject.toml", "tox.ini", "setup.cfg"];
+
+pub fn is_pytest_config_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|name| PYTEST_CONFIG_FILES.contains(&name))
+}
+
+fn is_conftest(path: &Path) -> bool {
+    path.file_name().is_some_and(|n| n == "conftest.py")
+}
+
+/// The `testpaths` of a pytest config file, or `None` if the file does not configure pytest.
+/// `pytest.ini` configures pytest even when empty; the other files need their pytest section.
+pub fn parse_pytest_config(path: &Path, content: &str) -> Option<Vec<String>> {
+    let name = path.file_name().and_then(|n| n.to_str())?;
+    if name == "pyproject.toml" {
+        let options = content.parse::<toml::Value>().ok()?;
+        let options = options.get("tool")?.get("pytest")?.get("ini_options")?;
+        return Some(match options.get("testpaths") {
+            Some(toml::Value::String(path)) => vec![path.clone()],
+            Some(toml::Value::Array(paths)) => paths.iter().filter_map(|p| p.as_str().map(str::to_string)).collect(),
+            _ => Vec::new(),
+        });
+    }
+
+    let section = match name {
+        "pytest.ini" | ".pytest.ini" | "tox.ini" => "pytest",
+        "setup.cfg" => "tool:pytest",
+        _ => return None,
+    };
+    let testpaths = match ini_section_value(content, section, "testpaths") {
+        Some(value) => value.unwrap_or_default(),
+        None if section == "pytest" && name != "tox.ini" => String::new(),
+        None => return None,
+    };
+    Some(testpaths.split_whitespace().map(str::to_string).collect())
+}
+
+/// The value of `key` in `section` of ini `content`, joining indented continuation lines;
+/// `None` when the section is missing
+fn ini_section_value(content: &str, section: &str, key: &str) -> Option<Option<String>> {
+    let mut in_section = false;
+    let mut found_section = false;
+    let mut value: Option<String> = None;
+    let mut continuing = false;
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if let Some(name) = trimmed.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+            in_section = name.trim() == section;
+            found_section |= in_section;
+            continuing = false;
+            continue;
+        }
+        if !in_section || trimmed.starts_with('#') || trimmed.starts_with(';') {
+            continue;
+        }
+        if continuing && line.starts_with(char::is_whitespace) && !trimmed.is_empty() {
+            if let Some(value) = &mut value {
+                value.push(' ');
+                value.push_str(trimmed);
+            }
+            continue;
+        }
+        continuing = false;
+        if let Some((name, rest)) = trimmed.split_once(['=', ':']) {
+            if name.trim() == key {
+                value = Some(rest.trim().to_string());
+                continuing = true;
+            }
+        }
+    }
+    found_section.then_some(value)
+}
+
+/// The pytest configuration of a directory, from the first of its config files that
+/// configures pytest
+fn read_pytest_root(dir: &Path) -> Option<Vec<PathBuf>> {
+    PYTEST_CONFIG_FILES.iter()
+        .map(|name| dir.join(name))
+        .find_map(|path| {
+            let content = std::fs::read_to_string(&path).ok()?;
+            parse_pytest_config(&path, &content)
+        })
+        .map(|testpaths| testpaths.iter().map(|p| dir.join(p)).collect())
+}
+
+impl DependencyGraph {
+    /// Re-read the pytest configuration of `dir` after one of its config files changed
+    pub(super) fn refresh_pytest_root(&mut self, dir: &Path) {
+        match read_pytest_root(dir) {
+            Some(testpaths) => self.pytest_roots.insert(dir.to_path_buf(), testpaths),
+            None => self.pytest_roots.remove(dir),
+        };
+    }
+
+    /// The directory pytest uses as rootdir for `file`: the nearest one above it holding a
+    /// pytest config file
+    pub fn pytest_rootdir<'a>(&self, file: &'a Path) -> Option<&'a Path> {
+        file.ancestors()
+            .skip(1)
+            .find(|dir| self.pytest_roots.contains_key(*dir))
+    }
+
+    /// Whether pytest collects `file`: it matches the test file patterns and lies within the
+    /// `testpaths` of its rootdir, if any are configured
+    pub fn is_test_file(&self, file: &Path) -> bool {
+        if !self.test_selector.is_test_file(file) {
+            return false;
+        }
+        match self.pytest_rootdir(file).map(|dir| &self.pytest_roots[dir]) {
+            Some(testpaths) if !testpaths.is_empty() => testpaths.iter().any(|p| file.starts_with(p)),
+            _ => true,
+        }
+    }
+
+    /// Edges from each test file and `conftest.py` to the `conftest.py` files pytest loads
+    /// before it: those in its directory and the directories above, up to its rootdir
+    fn conftest_edges(&self) -> Vec<(PathBuf, PathBuf)> {
+        let conftests: HashSet<&Path> = self.module_imports.keys()
+            .map(PathBuf::as_path)
+            .filter(|f| is_conftest(f))
+            .collect();
+        if conftests.is_empty() {
+            return Vec::new();
+        }
+
+        let mut edges = Vec::new();
+        for file in self.module_imports.keys() {
+            let is_conftest = is_conftest(file);
+            if !is_conftest && !self.is_test_file(file) {
+                continue;
+            }
+            let limit = self.pytest_rootdir(file).map(Path::to_path_buf).or_else(|| self.root_of(file));
+            for dir in file.ancestors().skip(if is_conftest { 2 } else { 1 }) {
+                if limit.as_deref().is_none_or(|limit| !dir.starts_with(limit)) {
+                    break;
+                }
+                let conftest = dir.join("conftest.py");
+                if conftests.contains(conftest.as_path()) {
+                    edges.push((file.clone(), conftest));
+                }
+            }
+        }
+        edges
+    }
+
+    /// Bring the conftest edges up to date after files or pytest configuration changed,
+    /// keeping edges that are also imports
+    pub(super) fn link_conftests(&mut self) {
+        let edges: BTreeSet<(PathBuf, PathBuf)> = self.conftest_edges().into_iter().collect();
+        let stale: Vec<(PathBuf, PathBuf)> = self.conftest_links.difference(&edges).cloned().collect();
+        for (from, to) in stale {
+            let imported = self.module_imports.get(&from).is_some_and(|modules| {
+                let root = self.root_of(&from).unwrap_or_default();
+                modules.iter().any(|m| self.resolve_module(&root, m).contains(&to))
+            });
+            if !imported {
+                if let Some(targets) = self.deps.get_mut(&from) {
+                    targets.remove(&to);
+                }
+                self.remove_reverse_edge(&to, &from);
+            }
+        }
+        for (from, to) in &edges {
+            self.add_dependency(from.clone(), to.clone());
+        }
+        self.conftest_links = edges;
+    }
+}
@@ -0,0 +1,163 @@
+use super::DependencyGraph;
+use crate::utils::{is_python_file, relative_path_string};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RuleAction {
+    Allow,
+    #[default]
+    Deny,
+}
+
+/// A configured architecture rule allowing or forbidding imports between parts of the repository.
+///
+/// Rules are checked in order and the first one matching an import decides; imports no rule
+/// matches are allowed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportRule {
+    /// Glob, relative to the config directory, for importing files; `{name}` matches one path
+    /// component and captures it
+    pub from: String,
+    /// Glob for imported files; `{name}` only matches the component captured by `from`
+    pub to: String,
+    #[serde(default)]
+    pub action: RuleAction,
+    /// Optional explanation shown with violations
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// An import rule with its globs compiled to regular expressions over relative paths
+#[derive(Debug, Clone)]
+pub struct CompiledImportRule {
+    pub rule: ImportRule,
+    from: Regex,
+    to: Regex,
+    // Captures named in both globs, which must match the same component
+    shared: Vec<String>,
+}
+
+impl CompiledImportRule {
+    pub fn new(rule: ImportRule) -> Result<Self, String> {
+        let (from, from_captures) = glob_regex(&rule.from)?;
+        let (to, to_captures) = glob_regex(&rule.to)?;
+        if let Some(unknown) = to_captures.iter().find(|c| !from_captures.contains(c)) {
+            return Err(format!("'{{{}}}' in to = \"{}\" is not captured by from", unknown, rule.to));
+        }
+        Ok(Self { rule, from, to, shared: to_captures })
+    }
+
+    /// Whether the rule applies to an import from `from` of `to`, both relative to the config directory
+    pub fn matches(&self, from: &str, to: &str) -> bool {
+        let (Some(from), Some(to)) = (self.from.captures(from), self.to.captures(to)) else {
+            return false;
+        };
+        self.shared.iter().all(|name| from.name(name).map(|m| m.as_str()) == to.name(name).map(|m| m.as_str()))
+    }
+}
+
+/// Translate a glob into an anchored regex matching the path or anything beneath it,
+/// along with the names of its `{name}` captures
+fn glob_regex(glob: &str) -> Result<(Regex, Vec<String>), String> {
+    let glob = glob.trim_end_matches('/');
+    let mut pattern = String::from("^");
+    let mut captures = Vec::new();
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    pattern.push_str("(?:.*/)?");
+                } else {
+                    pattern.push_str(".*");
+                }
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            '{' => {
+                let name: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                let valid = !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+                if !valid || name.starts_with(|c: char| c.is_ascii_digit()) {
+                    return Err(format!("invalid capture '{{{}}}' in \"{}\"", name, glob));
+                }
+                if captures.contains(&name) {
+                    return Err(format!("capture '{{{}}}' appears twice in \"{}\"", name, glob));
+                }
+                pattern.push_str(&format!("(?P<{}>[^/]+)", name));
+                captures.push(name);
+            }
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push_str("(?:/.*)?$");
+    let regex = Regex::new(&pattern).map_err(|e| format!("invalid glob \"{}\": {}", glob, e))?;
+    Ok((regex, captures))
+}
+
+/// An import forbidden by a rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleViolation {
+    pub file: PathBuf,
+    pub target: PathBuf,
+    /// 1-based line of the import statement, when it could be found
+    pub line: Option<usize>,
+    pub text: Option<String>,
+    /// Position of the violated rule in the configuration
+    pub rule: usize,
+}
+
+impl DependencyGraph {
+    /// Imports between Python files that the first matching rule denies, with paths matched
+    /// relative to `root`
+    pub fn check_rules(&self, rules: &[CompiledImportRule], root: &Path) -> Vec<RuleViolation> {
+        if rules.is_empty() {
+            return Vec::new();
+        }
+        let relative = |path: &Path| relative_path_string(path, root).unwrap_or_else(|| path.display().to_string());
+
+        let mut files: Vec<&PathBuf> = self.deps.keys().filter(|f| is_python_file(f)).collect();
+        files.sort();
+        let mut violations = Vec::new();
+        for file in files {
+            let mut targets: Vec<&PathBuf> = self.deps[file].iter().filter(|t| is_python_file(t)).collect();
+            // A submodule import also reaches its package's __init__.py; report the submodule
+            targets.sort_by_key(|target| (target.ends_with("__init__.py"), target.to_path_buf()));
+
+            let mut reported: BTreeSet<(Option<usize>, usize)> = BTreeSet::new();
+            for target in targets {
+                let (from, to) = (relative(file), relative(target));
+                let Some(index) = rules.iter().position(|rule| rule.matches(&from, &to)) else {
+                    continue;
+                };
+                if rules[index].rule.action == RuleAction::Allow {
+                    continue;
+                }
+                let sites = self.import_sites(file, target);
+                let lines: Vec<(Option<usize>, Option<String>)> = if sites.is_empty() {
+                    vec![(None, None)]
+                } else {
+                    sites.into_iter().map(|site| (Some(site.line), Some(site.text))).collect()
+                };
+                for (line, text) in lines {
+                    if reported.insert((line, index)) {
+                        violations.push(RuleViolation {
+                            file: file.clone(),
+                            target: target.clone(),
+                            line,
+                            text,
+                            rule: index,
+                        });
+                    }
+                }
+            }
+        }
+        violations.sort_by(|a, b| (&a.file, a.line, a.rule).cmp(&(&b.file, b.line, b.rule)));
+        violations
+    }
+}
@@ -0,0 +1,192 @@
+use super::coverage::CoveredTest;
+use super::DependencyGraph;
+use crate::utils::{is_python_file, relative_path_string, PathPatterns};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// The `[tests]` configuration section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestConfig {
+    /// Globs, relative to the config directory, for test files; a directory matches every
+    /// Python file beneath it
+    #[serde(default = "default_test_patterns")]
+    pub patterns: Vec<String>,
+    /// Per-test coverage data, relative to the config directory, whose tests are selected
+    /// along with those found by static analysis
+    #[serde(default)]
+    pub coverage: Option<PathBuf>,
+}
+
+fn default_test_patterns() -> Vec<String> {
+    vec!["**/test_*.py".to_string(), "**/*_test.py".to_string()]
+}
+
+impl Default for TestConfig {
+    fn default() -> Self {
+        Self { patterns: default_test_patterns(), coverage: None }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestFormat {
+    /// One test file per line
+    Files,
+    /// Arguments for a pytest command line
+    Pytest,
+    Json,
+}
+
+impl std::str::FromStr for TestFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "files" => Ok(TestFormat::Files),
+            "pytest" => Ok(TestFormat::Pytest),
+            "json" => Ok(TestFormat::Json),
+            _ => Err("Invalid test output format".into()),
+        }
+    }
+}
+
+/// Recognizes test files by the configured patterns, or by pytest's default file names
+/// when none are configured
+#[derive(Debug, Clone, Default)]
+pub struct TestSelector {
+    patterns: Option<PathPatterns>,
+}
+
+impl TestSelector {
+    pub fn new(root: &Path, config: &TestConfig) -> Result<Self, glob::PatternError> {
+        Ok(Self { patterns: Some(PathPatterns::new(root, &config.patterns)?) })
+    }
+
+    /// Whether `path` matches the test file patterns; `conftest.py` and `__init__.py` never do
+    pub fn is_test_file(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            return false;
+        };
+        if !is_python_file(path) || name == "conftest.py" || name == "__init__.py" {
+            return false;
+        }
+        match &self.patterns {
+            Some(patterns) => patterns.matches(path),
+            None => name.starts_with("test_") || name.ends_with("_test.py"),
+        }
+    }
+}
+
+/// A changed file matching a rule that affects every service
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GlobalTrigger {
+    pub file: PathBuf,
+    pub rule: String,
+}
+
+/// Test files to run for a set of changed files
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TestSelection {
+    /// Set when every test must run, because a change matched a global trigger
+    pub run_all: Option<GlobalTrigger>,
+    /// Affected test files, sorted; every test file when `run_all` is set
+    pub files: Vec<PathBuf>,
+    /// Tests that executed a changed file according to coverage data, outside the selected files
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub covered: Vec<CoveredTest>,
+}
+
+impl TestSelection {
+    /// Render with paths relative to `root`. With pytest arguments, running everything is
+    /// expressed as `root` itself, so it cannot be confused with an empty selection.
+    pub fn render(&self, format: TestFormat, root: &Path) -> String {
+        let relative = |path: &Path| relative_path_string(path, root).unwrap_or_else(|| path.display().to_string());
+        match format {
+            TestFormat::Files => {
+                let files: BTreeSet<&PathBuf> = self.files.iter().chain(self.covered.iter().map(|t| &t.file)).collect();
+                files.into_iter().map(|f| relative(f) + "\n").collect()
+            }
+            TestFormat::Pytest if self.run_all.is_some() => ".\n".to_string(),
+            TestFormat::Pytest if self.files.is_empty() && self.covered.is_empty() => String::new(),
+            TestFormat::Pytest => {
+                let args: Vec<String> = self.files.iter().map(|f| relative(f))
+                    .chain(self.covered.iter().map(|t| t.node_id(&relative(&t.file))))
+                    .map(|arg| shell_quote(&arg))
+                    .collect();
+                args.join(" ") + "\n"
+            }
+            TestFormat::Json => {
+                let relative = TestSelection {
+                    run_all: self.run_all.as_ref().map(|trigger| GlobalTrigger {
+                        file: PathBuf::from(relative(&trigger.file)),
+                        rule: trigger.rule.clone(),
+                    }),
+                    files: self.files.iter().map(|f| PathBuf::from(relative(f))).collect(),
+                    covered: self.covered.iter()
+                        .map(|t| CoveredTest { file: PathBuf::from(relative(&t.file)), ..t.clone() })
+                        .collect(),
+                };
+                serde_json::to_string_pretty(&relative).expect("selections always serialize") + "\n"
+            }
+        }
+    }
+}
+
+/// Quote an argument for a POSIX shell unless it only has characters that never need it
+fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty() && arg.chars().all(|c| c.is_ascii_alphanumeric() || "/._-+=:@,".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+impl DependencyGraph {
+    /// Python files of the graph pytest collects, sorted
+    pub fn test_files(&self) -> Vec<PathBuf> {
+        let files: BTreeSet<&PathBuf> = self.module_imports.keys().filter(|f| self.is_test_file(f)).collect();
+        files.into_iter().cloned().collect()
+    }
+
+    /// Test files affected by `changed`: tests importing a changed file or loading a changed
+    /// `conftest.py` or plugin, directly or transitively, tests of the services named by
+    /// matching impact rules, and tests that coverage data shows executing a changed file. A
+    /// change matching a rule for every service, including configured trigger files, selects
+    /// every test.
+    pub fn select_tests(&self, changed: &[PathBuf]) -> TestSelection {
+        let trigger = changed.iter().find_map(|file| {
+            self.impact_rules.iter()
+                .find(|rule| rule.rule.all && rule.matches(file))
+                .map(|rule| GlobalTrigger { file: file.clone(), rule: rule.rule.describe() })
+        });
+        if let Some(trigger) = trigger {
+            return TestSelection { run_all: Some(trigger), files: self.test_files(), covered: Vec::new() };
+        }
+
+        let all_tests = self.test_files();
+        let mut selected: BTreeSet<PathBuf> = BTreeSet::new();
+        for file in changed {
+            // A deleted test file is among the changed files but can no longer run
+            let affected = self.get_affected_files_within(file, self.max_depth);
+            selected.extend(affected.into_keys().filter(|f| {
+                self.is_test_file(f) && (self.module_imports.contains_key(f) || f.exists())
+            }));
+
+            let services: Vec<_> = self.impact_rules.iter()
+                .filter(|rule| rule.matches(file))
+                .flat_map(|rule| self.services.values().filter(|s| rule.rule.affects(s)))
+                .collect();
+            for service in services {
+                selected.extend(all_tests.iter().filter(|t| service.contains_path(t)).cloned());
+            }
+        }
+
+        // Tests that executed a changed file, unless their whole file already runs
+        let covered: BTreeSet<CoveredTest> = changed.iter()
+            .flat_map(|file| self.tests_covering(file))
+            .filter(|test| !selected.contains(&test.file))
+            .collect();
+        TestSelection { run_all: None, files: selected.into_iter().collect(), covered: covered.into_iter().collect() }
+    }
+}
@@ -0,0 +1,188 @@
+use super::coverage::CoverageMap;
+use super::DependencyGraph;
+use crate::package_deps::models::{DependencyFile, ImportInfo};
+use crate::service::models::DetectedService;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// Bumped whenever the snapshot layout changes; older or newer snapshots are rejected
+pub const SNAPSHOT_FORMAT: u32 = 4;
+
+/// A built dependency graph in a stable, serializable form.
+///
+/// Paths under the snapshot root are stored relative to it, so a snapshot taken in one
+/// checkout can be loaded in another. Configuration that is not an analysis result, such as
+/// impact rules and ignore globs, is not included and is applied again after loading.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphSnapshot {
+    pub format: u32,
+    /// Version of pybia that wrote the snapshot
+    pub generator: String,
+    /// Directories the graph was built from
+    pub roots: Vec<PathBuf>,
+    pub source_roots: Vec<PathBuf>,
+    pub max_depth: Option<usize>,
+    pub services: BTreeMap<String, DetectedService>,
+    /// Each file's direct dependencies, sorted
+    pub edges: BTreeMap<PathBuf, Vec<PathBuf>>,
+    /// Dotted module names each Python file imports, as written
+    pub module_imports: BTreeMap<PathBuf, Vec<String>>,
+    pub asset_files: Vec<PathBuf>,
+    /// Imports of each Python file, by top-level package
+    pub package_imports: BTreeMap<PathBuf, Vec<ImportInfo>>,
+    pub dependency_files: Vec<DependencyFile>,
+    /// Directories with a pytest config file, with their configured testpaths
+    pub pytest_roots: BTreeMap<PathBuf, Vec<PathBuf>>,
+    /// Edges in `edges` that link test and conftest files to the conftest.py files pytest
+    /// loads for them, so they can be dropped again when pytest configuration changes
+    pub conftest_links: Vec<(PathBuf, PathBuf)>,
+    /// Tests that executed each file, when the graph was built with coverage data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coverage: Option<CoverageMap>,
+}
+
+impl GraphSnapshot {
+    /// The same snapshot with every path passed through `f`
+    fn map_paths(self, f: impl Fn(&Path) -> PathBuf) -> Self {
+        let paths = |paths: Vec<PathBuf>| paths.iter().map(|p| f(p)).collect::<Vec<_>>();
+        Self {
+            roots: paths(self.roots),
+            source_roots: paths(self.source_roots),
+            services: self.services.into_iter()
+                .map(|(name, service)| (name, DetectedService {
+                    root_path: f(&service.root_path),
+                    package_root: f(&service.package_root),
+                    include_paths: paths(service.include_paths),
+                    exclude_paths: paths(service.exclude_paths),
+                    ..service
+                }))
+                .collect(),
+            edges: self.edges.into_iter().map(|(file, targets)| (f(&file), paths(targets))).collect(),
+            module_imports: self.module_imports.into_iter().map(|(file, modules)| (f(&file), modules)).collect(),
+            asset_files: paths(self.asset_files),
+            package_imports: self.package_imports.into_iter().map(|(file, imports)| (f(&file), imports)).collect(),
+            dependency_files: self.dependency_files.into_iter()
+                .map(|file| DependencyFile { path: f(&file.path), ..file })
+                .collect(),
+            pytest_roots: self.pytest_roots.into_iter().map(|(dir, testpaths)| (f(&dir), paths(testpaths))).collect(),
+            conftest_links: self.conftest_links.iter().map(|(from, to)| (f(from), f(to))).collect(),
+            coverage: self.coverage.map(|coverage| coverage.map_paths(&f)),
+            ..self
+        }
+    }
+}
+
+impl DependencyGraph {
+    /// Capture the analysis result, with paths under `root` stored relative to it
+    pub fn snapshot(&self, root: &Path) -> GraphSnapshot {
+        let sorted = |paths: &mut dyn Iterator<Item = &PathBuf>| {
+            paths.cloned().collect::<BTreeSet<_>>().into_iter().collect::<Vec<_>>()
+        };
+        let mut dependency_files: Vec<DependencyFile> = self.package_deps.dependency_files().values().cloned().collect();
+        dependency_files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let snapshot = GraphSnapshot {
+            format: SNAPSHOT_FORMAT,
+            generator: env!("CARGO_PKG_VERSION").to_string(),
+            roots: self.roots.clone(),
+            source_roots: self.source_roots.clone(),
+            max_depth: self.max_depth,
+            services: self.services.iter().map(|(name, s)| (name.clone(), s.clone())).collect(),
+            edges: self.deps.iter()
+                .map(|(file, targets)| (file.clone(), sorted(&mut targets.iter())))
+                .collect(),
+            module_imports: self.module_imports.iter().map(|(f, m)| (f.clone(), m.clone())).collect(),
+            asset_files: sorted(&mut self.asset_files.iter()),
+            package_imports: self.package_deps.imports().iter().map(|(f, i)| (f.clone(), i.clone())).collect(),
+            dependency_files,
+            pytest_roots: self.pytest_roots.iter().map(|(d, t)| (d.clone(), t.clone())).collect(),
+            conftest_links: self.conftest_links.iter().cloned().collect(),
+            coverage: self.coverage.clone(),
+        };
+        snapshot.map_paths(|path| match path.strip_prefix(root) {
+            Ok(relative) if relative.as_os_str().is_empty() => PathBuf::from("."),
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => path.to_path_buf(),
+        })
+    }
+
+    /// Rebuild a graph from a snapshot, resolving its relative paths against `root`
+    pub fn from_snapshot(snapshot: GraphSnapshot, root: &Path) -> Self {
+        let snapshot = snapshot.map_paths(|path| {
+            if path == Path::new(".") { root.to_path_buf() } else { root.join(path) }
+        });
+        let mut graph = DependencyGraph::new()
+            .with_source_roots(snapshot.source_roots)
+            .with_max_depth(snapshot.max_depth);
+        graph.roots = snapshot.roots;
+        graph.services = snapshot.services.into_iter().collect();
+        for (file, targets) in snapshot.edges {
+            for target in targets {
+                graph.add_dependency(file.clone(), target);
+            }
+        }
+        graph.module_imports = snapshot.module_imports.into_iter().collect();
+        graph.asset_files = snapshot.asset_files.into_iter().collect();
+        graph.pytest_roots = snapshot.pytest_roots.into_iter().collect();
+        graph.conftest_links = snapshot.conftest_links.into_iter().collect();
+        graph.coverage = snapshot.coverage;
+        for (file, imports) in snapshot.package_imports {
+            graph.package_deps.add_imports(&file, imports);
+        }
+        for dependency_file in snapshot.dependency_files {
+            graph.package_deps.add_dependency_file(dependency_file);
+        }
+        graph
+    }
+
+    /// Write the graph to `path` as JSON, with paths under `root` stored relative to it
+    pub fn save(&self, path: &Path, root: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(&self.snapshot(root)).map_err(std::io::Error::other)?;
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, json + "\n")
+    }
+
+    /// Read a graph written by `save`, resolving its relative paths against `root`
+    pub fn load(path: &Path, root: &Path) -> std::io::Result<Self> {
+        let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+        let bytes = std::fs::read(path)?;
+        // Check the format first so an incompatible snapshot gets a clear error
+        let header: serde_json::Value = serde_json::from_slice(&bytes)
+            .map_err(|e| invalid(format!("Invalid graph snapshot {}: {}", path.display(), e)))?;
+        let format = header.get("format").and_then(serde_json::Value::as_u64);
+        if format != Some(SNAPSHOT_FORMAT as u64) {
+            return Err(invalid(format!(
+                "Unsupported graph snapshot format {} in {}; expected {}",
+                format.map_or("(missing)".to_string(), |f| f.to_string()),
+                path.display(),
+                SNAPSHOT_FORMAT,
+            )));
+        }
+        let snapshot: GraphSnapshot = serde_json::from_value(header)
+            .map_err(|e| invalid(format!("Invalid graph snapshot {}: {}", path.display(), e)))?;
+        Ok(Self::from_snapshot(snapshot, root))
+    }
+}
+
+/// Serializes as a `GraphSnapshot` with absolute paths
+impl Serialize for DependencyGraph {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.snapshot(Path::new("")).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DependencyGraph {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let snapshot = GraphSnapshot::deserialize(deserializer)?;
+        if snapshot.format != SNAPSHOT_FORMAT {
+            return Err(serde::de::Error::custom(format!(
+                "unsupported graph snapshot format {}; expected {}",
+                snapshot.format, SNAPSHOT_FORMAT,
+            )));
+        }
+        Ok(Self::from_snapshot(snapshot, Path::new("")))
+    }
+}
@@ -1,6 +1,18 @@
 use super::*;
+use super::assets::{AssetRule, CompiledAssetRule};
+use super::cycles::{strongly_connected_components, CycleBaseline, CycleLevel};
+use super::deps_check::{DepsCheck, DepsCheckConfig, ReportFormat};
+use super::diff::{DiffEdge, DiffFormat, GraphDiff};
+use super::versions::{Drift, VersionBaseline, VersionStatus};
+use super::selection::{TestConfig, TestFormat, TestSelector};
+use super::pytest::parse_pytest_config;
+use super::coverage::{CoverageMap, CoveredTest};
+use super::rules::{CompiledImportRule, ImportRule, RuleAction};
+use super::export::{ExportFormat, ExportOptions, Granularity, NodeKind};
+use super::impact::{CompiledImpactRule, ImpactReason, ImpactRule};
 use crate::service::models::{DetectedService, ServiceDetectionType};
-use std::collections::HashMap;
+use crate::test_support::write_file;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use tempfile::TempDir;
 
 fn setup_test_env() -> (TempDir, DependencyGraph) {
@@ -15,6 +27,9 @@ fn setup_test_env() -> (TempDir, DependencyGraph) {
             root_path: temp.path().join("service1"),
             package_root: temp.path().join("service1/src"),
             detection_type: ServiceDetectionType::SetupPy,
+            include_paths: vec![],
+            exclude_paths: vec![],
+            tags: vec![],
         },
     );
     
@@ -25,6 +40,9 @@ fn setup_test_env() -> (TempDir, DependencyGraph) {
             root_path: temp.path().join("service2"),
             package_root: temp.path().join("service2/src"),
             detection_type: ServiceDetectionType::SetupPy,
+            include_paths: vec![],
+            exclude_paths: vec![],
+            tags: vec![],
         },
     );
 
@@ -96,4 +114,870 @@ fn test_package_dependencies() {
     
     assert_eq!(affected.len(), 1);
     assert_eq!(affected[0].0, "service1");
-} 
\ No newline at end of file
+} 
+
+#[test]
+fn test_include_and_exclude_paths() {
+    let temp = tempfile::tempdir().unwrap();
+    let service = |name: &str, include: Vec<PathBuf>, exclude: Vec<PathBuf>| DetectedService {
+        name: name.to_string(),
+        root_path: temp.path().join(name),
+        package_root: temp.path().join(name),
+        detection_type: ServiceDetectionType::ConfigurationDefined,
+        include_paths: include,
+        exclude_paths: exclude,
+        tags: vec![],
+    };
+    let mut services = HashMap::new();
+    services.insert("a".to_string(), service("a", vec![temp.path().join("shared")], vec![temp.path().join("a/docs")]));
+    services.insert("b".to_string(), service("b", vec![temp.path().join("shared")], vec![]));
+
+    let mut graph = DependencyGraph::new();
+    graph.build_from_directory(temp.path(), services).unwrap();
+
+    let mut shared: Vec<&str> = graph.get_affected_services(&temp.path().join("shared/util.py"))
+        .into_iter().map(|(name, _)| name).collect();
+    shared.sort();
+    assert_eq!(shared, vec!["a", "b"]);
+    assert!(graph.get_affected_services(&temp.path().join("a/docs/index.py")).is_empty());
+}
+
+#[test]
+fn test_source_roots_ignore_and_trigger_files() {
+    let temp = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(temp.path().join("libs/shared")).unwrap();
+    std::fs::create_dir_all(temp.path().join("service1/src")).unwrap();
+    std::fs::create_dir_all(temp.path().join("build")).unwrap();
+    std::fs::write(temp.path().join("libs/shared/__init__.py"), "").unwrap();
+    std::fs::write(temp.path().join("service1/src/app.py"), "import shared").unwrap();
+    std::fs::write(temp.path().join("build/app.py"), "import shared").unwrap();
+
+    let (_, graph) = setup_test_env();
+    let services = graph.services.clone();
+    let mut graph = DependencyGraph::new()
+        .with_source_roots(vec![temp.path().join("libs")])
+        .with_ignore(PathPatterns::new(temp.path(), &["build".to_string()]).unwrap())
+        .with_trigger_files(temp.path(), &[".github/workflows/*".to_string()]).unwrap();
+    graph.build_from_directory(temp.path(), services).unwrap();
+
+    // Imports resolve to packages under source roots; ignored paths are not scanned
+    let affected = graph.get_affected_files(&temp.path().join("libs/shared/__init__.py"));
+    assert!(affected.contains(&temp.path().join("service1/src/app.py")));
+    assert!(!affected.contains(&temp.path().join("build/app.py")));
+
+    // Global trigger files affect every service
+    let affected = graph.get_affected_services(&temp.path().join(".github/workflows/ci.yml"));
+    assert_eq!(affected.len(), 2);
+}
+
+#[test]
+fn test_impact_rules_and_explain() {
+    let temp = tempfile::tempdir().unwrap();
+    let service = |name: &str, tags: Vec<String>| DetectedService {
+        name: name.to_string(),
+        root_path: temp.path().join(name),
+        package_root: temp.path().join(name),
+        detection_type: ServiceDetectionType::ConfigurationDefined,
+        include_paths: vec![],
+        exclude_paths: vec![],
+        tags,
+    };
+    let mut services = HashMap::new();
+    services.insert("api".to_string(), service("api", vec!["web".to_string()]));
+    services.insert("billing".to_string(), service("billing", vec![]));
+    services.insert("worker".to_string(), service("worker", vec![]));
+
+    std::fs::create_dir_all(temp.path().join("api")).unwrap();
+    std::fs::create_dir_all(temp.path().join("billing")).unwrap();
+    std::fs::create_dir_all(temp.path().join("libs")).unwrap();
+    std::fs::write(temp.path().join("libs/money.py"), "").unwrap();
+    std::fs::write(temp.path().join("billing/invoice.py"), "import money").unwrap();
+    std::fs::write(temp.path().join("api/views.py"), "import invoice").unwrap();
+
+    let rules = vec![
+        CompiledImpactRule::new(temp.path(), ImpactRule {
+            paths: vec!["schemas/*.json".to_string()],
+            services: vec!["billing".to_string()],
+            tags: vec!["web".to_string()],
+            reason: Some("generated models".to_string()),
+            ..Default::default()
+        }).unwrap(),
+    ];
+    let mut graph = DependencyGraph::new()
+        .with_source_roots(vec![temp.path().join("libs"), temp.path().join("billing")])
+        .with_impact_rules(rules)
+        .with_trigger_files(temp.path(), &["tox.ini".to_string()]).unwrap();
+    graph.build_from_directory(temp.path(), services).unwrap();
+
+    // Named services and tags
+    let names: Vec<&str> = graph.explain(&temp.path().join("schemas/user.json"))
+        .into_iter().map(|i| i.name).collect();
+    assert_eq!(names, vec!["api", "billing"]);
+    let impacts = graph.explain(&temp.path().join("schemas/user.json"));
+    assert_eq!(impacts[0].reasons, vec![ImpactReason::Rule {
+        description: "schemas/*.json (generated models)".to_string(),
+    }]);
+
+    // Global trigger files
+    assert_eq!(graph.explain(&temp.path().join("tox.ini")).len(), 3);
+    assert!(graph.explain(&temp.path().join("nested/tox.ini")).is_empty());
+
+    // Import chains, shortest first
+    let impacts = graph.explain(&temp.path().join("libs/money.py"));
+    let api = impacts.iter().find(|i| i.name == "api").unwrap();
+    assert_eq!(api.reasons, vec![ImpactReason::Imports {
+        chain: vec![
+            temp.path().join("libs/money.py"),
+            temp.path().join("billing/invoice.py"),
+            temp.path().join("api/views.py"),
+        ],
+    }]);
+    let billing = impacts.iter().find(|i| i.name == "billing").unwrap();
+    assert_eq!(billing.reasons.len(), 1);
+}
+
+fn service_at(root: &Path, name: &str) -> (String, DetectedService) {
+    (name.to_string(), DetectedService {
+        name: name.to_string(),
+        root_path: root.join(name),
+        package_root: root.join(name),
+        detection_type: ServiceDetectionType::ConfigurationDefined,
+        include_paths: vec![],
+        exclude_paths: vec![],
+        tags: vec![],
+    })
+}
+
+#[test]
+fn test_package_data_assets() {
+    let temp = tempfile::tempdir().unwrap();
+    write_file(temp.path(), "common/setup.py", r#"setup(
+    name="common",
+    package_data={"common": ["templates/*.html"], "common.sql": ["*.sql"]},
+)"#);
+    write_file(temp.path(), "common/common/__init__.py", "");
+    write_file(temp.path(), "common/common/render.py", "");
+    write_file(temp.path(), "common/common/templates/page.html", "");
+    write_file(temp.path(), "common/common/sql/__init__.py", "");
+    write_file(temp.path(), "common/common/sql/001.sql", "");
+    write_file(temp.path(), "api/views.py", "from common.render import page");
+    write_file(temp.path(), "worker/jobs.py", "import common.sql");
+
+    let services = HashMap::from([service_at(temp.path(), "api"), service_at(temp.path(), "worker")]);
+    let mut graph = DependencyGraph::new().with_source_roots(vec![temp.path().join("common")]);
+    graph.build_from_directory(temp.path(), services).unwrap();
+
+    let affected: Vec<&str> = graph.get_affected_services(&temp.path().join("common/common/templates/page.html"))
+        .into_iter().map(|(name, _)| name).collect();
+    assert_eq!(affected.len(), 2, "{:?}", affected);
+
+    let affected: Vec<&str> = graph.get_affected_services(&temp.path().join("common/common/sql/001.sql"))
+        .into_iter().map(|(name, _)| name).collect();
+    assert_eq!(affected, vec!["worker"]);
+}
+
+#[test]
+fn test_pyproject_package_data_and_manifest_assets() {
+    let temp = tempfile::tempdir().unwrap();
+    write_file(temp.path(), "libs/pyproject.toml", "[tool.setuptools.package-data]\nconfigs = [\"*.yaml\"]\n");
+    write_file(temp.path(), "libs/src/configs/__init__.py", "");
+    write_file(temp.path(), "libs/src/configs/base.yaml", "");
+    write_file(temp.path(), "libs/MANIFEST.in", "recursive-include src/fixtures *.json\nexclude src/fixtures/skip.json\n");
+    write_file(temp.path(), "libs/src/fixtures/__init__.py", "");
+    write_file(temp.path(), "libs/src/fixtures/users.json", "");
+    write_file(temp.path(), "libs/src/fixtures/skip.json", "");
+    write_file(temp.path(), "libs/src/fixtures/build/generated.json", "");
+    write_file(temp.path(), "api/app.py", "import configs\nimport fixtures\n");
+
+    let services = HashMap::from([service_at(temp.path(), "api")]);
+    let mut graph = DependencyGraph::new()
+        .with_source_roots(vec![temp.path().join("libs/src")])
+        .with_ignore(PathPatterns::new(temp.path(), &["**/build".to_string()]).unwrap());
+    graph.build_from_directory(temp.path(), services).unwrap();
+
+    assert_eq!(graph.get_affected_services(&temp.path().join("libs/src/configs/base.yaml")).len(), 1);
+    assert_eq!(graph.get_affected_services(&temp.path().join("libs/src/fixtures/users.json")).len(), 1);
+    assert!(graph.get_affected_services(&temp.path().join("libs/src/fixtures/skip.json")).is_empty());
+    assert!(graph.get_affected_services(&temp.path().join("libs/src/fixtures/build/generated.json")).is_empty());
+}
+
+#[test]
+fn test_asset_rules() {
+    let temp = tempfile::tempdir().unwrap();
+    write_file(temp.path(), "shared/loader.py", "");
+    write_file(temp.path(), "shared/migrations/0001.sql", "");
+    write_file(temp.path(), "billing/db.py", "import loader");
+
+    let rule = CompiledAssetRule::new(temp.path(), &AssetRule {
+        assets: vec!["shared/migrations/*.sql".to_string()],
+        modules: vec!["shared/loader.py".to_string()],
+    }).unwrap();
+    let services = HashMap::from([service_at(temp.path(), "billing")]);
+    let mut graph = DependencyGraph::new()
+        .with_source_roots(vec![temp.path().join("shared")])
+        .with_asset_rules(vec![rule]);
+    graph.build_from_directory(temp.path(), services).unwrap();
+
+    let impacts = graph.explain(&temp.path().join("shared/migrations/0001.sql"));
+    assert_eq!(impacts.len(), 1);
+    assert_eq!(impacts[0].reasons, vec![ImpactReason::Imports {
+        chain: vec![
+            temp.path().join("shared/migrations/0001.sql"),
+            temp.path().join("shared/loader.py"),
+            temp.path().join("billing/db.py"),
+        ],
+    }]);
+}
+
+#[test]
+fn test_incremental_import_changes() {
+    let temp = tempfile::tempdir().unwrap();
+    write_file(temp.path(), "api/views.py", "import helpers");
+    write_file(temp.path(), "api/helpers.py", "");
+    write_file(temp.path(), "api/models.py", "");
+
+    let services = HashMap::from([service_at(temp.path(), "api")]);
+    let mut graph = DependencyGraph::new().with_source_roots(vec![temp.path().join("api")]);
+    graph.build_from_directory(temp.path(), services).unwrap();
+    let views = temp.path().join("api/views.py");
+    assert!(graph.get_affected_files(&temp.path().join("api/helpers.py")).contains(&views));
+
+    // An import is swapped for another
+    write_file(temp.path(), "api/views.py", "import models");
+    graph.update_file(&views).unwrap();
+    assert!(!graph.get_affected_files(&temp.path().join("api/helpers.py")).contains(&views));
+    assert!(graph.get_affected_files(&temp.path().join("api/models.py")).contains(&views));
+
+    // Deleting the imported module drops the edge; recreating it restores it
+    std::fs::remove_file(temp.path().join("api/models.py")).unwrap();
+    graph.remove_file(&temp.path().join("api/models.py")).unwrap();
+    assert!(!graph.deps[&views].contains(&temp.path().join("api/models.py")));
+    write_file(temp.path(), "api/models.py", "");
+    graph.update_file(&temp.path().join("api/models.py")).unwrap();
+    assert!(graph.get_affected_files(&temp.path().join("api/models.py")).contains(&views));
+
+    // Renaming the importer keeps its edges under the new name
+    let renamed = temp.path().join("api/routes.py");
+    std::fs::rename(&views, &renamed).unwrap();
+    graph.rename_file(&views, &renamed).unwrap();
+    let affected = graph.get_affected_files(&temp.path().join("api/models.py"));
+    assert!(affected.contains(&renamed));
+    assert!(!affected.contains(&views));
+}
+
+#[test]
+fn test_incremental_dependency_files_and_assets() {
+    let temp = tempfile::tempdir().unwrap();
+    write_file(temp.path(), "api/requirements.txt", "flask==2.0.0\n");
+    write_file(temp.path(), "api/app.py", "import flask\n");
+    write_file(temp.path(), "api/pkg/__init__.py", "");
+    write_file(temp.path(), "api/setup.py", "setup(name='api')\n");
+
+    let services = HashMap::from([service_at(temp.path(), "api")]);
+    let mut graph = DependencyGraph::new().with_source_roots(vec![temp.path().join("api")]);
+    graph.build_from_directory(temp.path(), services).unwrap();
+    let requirements = temp.path().join("api/requirements.txt");
+    assert!(graph.get_affected_files(&requirements).contains(&temp.path().join("api/app.py")));
+
+    // The file stops using the package
+    write_file(temp.path(), "api/app.py", "import pkg\n");
+    graph.update_file(&temp.path().join("api/app.py")).unwrap();
+    assert!(!graph.get_affected_files(&requirements).contains(&temp.path().join("api/app.py")));
+
+    // New package data is attributed once setup.py declares it
+    write_file(temp.path(), "api/pkg/query.sql", "");
+    graph.update_file(&temp.path().join("api/pkg/query.sql")).unwrap();
+    assert!(!graph.get_affected_files(&temp.path().join("api/pkg/query.sql")).contains(&temp.path().join("api/app.py")));
+    write_file(temp.path(), "api/setup.py", "setup(name='api', package_data={'pkg': ['*.sql']})\n");
+    graph.update_file(&temp.path().join("api/setup.py")).unwrap();
+    assert!(graph.get_affected_files(&temp.path().join("api/pkg/query.sql")).contains(&temp.path().join("api/app.py")));
+
+    // Deleting a directory forgets everything beneath it
+    std::fs::remove_dir_all(temp.path().join("api/pkg")).unwrap();
+    graph.remove_file(&temp.path().join("api/pkg")).unwrap();
+    assert!(graph.deps.get(&temp.path().join("api/app.py")).is_none_or(|d| d.is_empty()));
+}
+
+#[test]
+fn test_traversal_depths_limits_and_cycles() {
+    let mut graph = DependencyGraph::new();
+    let file = |name: &str| PathBuf::from(format!("/repo/{}.py", name));
+    // c imports b imports a, d imports a, and a imports c, closing a cycle
+    graph.add_dependency(file("b"), file("a"));
+    graph.add_dependency(file("c"), file("b"));
+    graph.add_dependency(file("d"), file("a"));
+    graph.add_dependency(file("a"), file("c"));
+
+    let full = graph.traverse_importers(&file("a"), None);
+    assert_eq!(full.depth(&file("a")), Some(0));
+    assert_eq!(full.depth(&file("b")), Some(1));
+    assert_eq!(full.depth(&file("c")), Some(2));
+    assert_eq!(full.chain(&file("c")), vec![file("a"), file("b"), file("c")]);
+    assert_eq!(full.cycles, vec![vec![file("a"), file("b"), file("c")]]);
+    assert!(!full.truncated);
+
+    // x and y both import s and each other: a cycle between siblings of the traversal
+    graph.add_dependency(file("x"), file("s"));
+    graph.add_dependency(file("y"), file("s"));
+    graph.add_dependency(file("x"), file("y"));
+    graph.add_dependency(file("y"), file("x"));
+    assert_eq!(graph.traverse_importers(&file("s"), None).cycles, vec![vec![file("x"), file("y")]]);
+
+    let direct = graph.get_affected_files_within(&file("a"), Some(1));
+    assert_eq!(direct, HashMap::from([(file("a"), 0), (file("b"), 1), (file("d"), 1)]));
+    assert!(graph.traverse_importers(&file("a"), Some(1)).truncated);
+}
+
+#[test]
+fn test_traversal_handles_deep_chains() {
+    let mut graph = DependencyGraph::new();
+    let file = |n: usize| PathBuf::from(format!("/repo/m{}.py", n));
+    for n in 1..100_000 {
+        graph.add_dependency(file(n), file(n - 1));
+    }
+    let affected = graph.get_affected_files_within(&file(0), None);
+    assert_eq!(affected.len(), 100_000);
+    assert_eq!(affected[&file(99_999)], 99_999);
+}
+
+#[test]
+fn test_strongly_connected_components() {
+    let edges: BTreeMap<u32, BTreeSet<u32>> = BTreeMap::from([
+        (1, BTreeSet::from([2])),
+        (2, BTreeSet::from([3])),
+        (3, BTreeSet::from([1, 4])),
+        (4, BTreeSet::from([5])),
+        (5, BTreeSet::from([5])),
+        (6, BTreeSet::from([1])),
+    ]);
+    assert_eq!(strongly_connected_components(&edges), vec![vec![1, 2, 3], vec![5]]);
+
+    let chain: BTreeMap<u32, BTreeSet<u32>> = (1..100_000).map(|n| (n, BTreeSet::from([n - 1]))).collect();
+    assert!(strongly_connected_components(&chain).is_empty());
+}
+
+#[test]
+fn test_cycles_at_each_level_with_import_lines() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    write_file(root, "api/api/__init__.py", "");
+    write_file(root, "api/api/views.py", "import os\nfrom worker.jobs import run\n");
+    write_file(root, "worker/worker/__init__.py", "");
+    write_file(root, "worker/worker/jobs.py", "from api.views import render\n");
+    write_file(root, "api/api/a.py", "from api.b import x\n");
+    write_file(root, "api/api/b.py", "from api.a import y\n");
+
+    let mut graph = DependencyGraph::new()
+        .with_source_roots(vec![root.join("api"), root.join("worker")]);
+    let services = HashMap::from([service_at(root, "api"), service_at(root, "worker")]);
+    graph.build_from_directory(root, services).unwrap();
+
+    let files = graph.cycles(CycleLevel::File);
+    let members: Vec<Vec<String>> = files.iter().map(|c| c.members.clone()).collect();
+    let name = |p: &str| root.join(p).display().to_string();
+    assert_eq!(members, vec![
+        vec![name("api/api/a.py"), name("api/api/b.py")],
+        vec![name("api/api/views.py"), name("worker/worker/jobs.py")],
+    ]);
+
+    let packages = graph.cycles(CycleLevel::Package);
+    assert_eq!(packages.len(), 1);
+    assert_eq!(packages[0].members, vec![name("api/api"), name("worker/worker")]);
+
+    let services = graph.cycles(CycleLevel::Service);
+    assert_eq!(services.len(), 1);
+    assert_eq!(services[0].members, vec!["api".to_string(), "worker".to_string()]);
+    let edge = services[0].edges.iter().find(|e| e.from == "api").unwrap();
+    assert_eq!(edge.to, "worker");
+    assert_eq!(edge.imports.len(), 1);
+    assert_eq!(edge.imports[0].file, root.join("api/api/views.py"));
+    assert_eq!(edge.imports[0].line, 2);
+    assert_eq!(edge.imports[0].text, "from worker.jobs import run");
+    assert_eq!(edge.imports[0].target, root.join("worker/worker/jobs.py"));
+
+    let baseline_path = root.join("cycles.json");
+    assert_eq!(CycleBaseline::load(&baseline_path).unwrap(), CycleBaseline::default());
+    assert_eq!(CycleBaseline::default().new_cycles(&services).len(), 1);
+    CycleBaseline::from_cycles(&services).save(&baseline_path).unwrap();
+    assert!(CycleBaseline::load(&baseline_path).unwrap().new_cycles(&services).is_empty());
+}
+
+#[test]
+fn test_export_granularities_filters_and_highlighting() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    write_file(root, "api/api/__init__.py", "");
+    write_file(root, "api/api/views.py", "import requests\nfrom shared.util import helper\n");
+    write_file(root, "shared/shared/__init__.py", "");
+    write_file(root, "shared/shared/util.py", "from shared.base import Base\n");
+    write_file(root, "shared/shared/base.py", "import yaml\n");
+    write_file(root, "worker/worker/__init__.py", "");
+    write_file(root, "worker/worker/jobs.py", "import os\nimport celery\n");
+
+    let mut graph = DependencyGraph::new()
+        .with_source_roots(vec![root.join("api"), root.join("shared"), root.join("worker")]);
+    let services = HashMap::from([service_at(root, "api"), service_at(root, "shared"), service_at(root, "worker")]);
+    graph.build_from_directory(root, services).unwrap();
+
+    let services = graph.export(&ExportOptions::new(Granularity::Service).with_root(root)
+        .with_changed(vec![root.join("shared/shared/base.py")]));
+    let ids: Vec<&str> = services.nodes.iter().map(|n| n.id.as_str()).collect();
+    assert_eq!(ids, vec!["api", "external:celery", "external:requests", "external:yaml", "shared", "worker"]);
+    assert!(services.edges.iter().any(|e| e.from == "api" && e.to == "shared"));
+    let node = |id: &str| services.nodes.iter().find(|n| n.id == id).unwrap();
+    assert!(node("shared").changed);
+    assert!(node("api").affected && !node("api").changed);
+    assert!(!node("worker").affected);
+    assert_eq!(node("external:yaml").kind, NodeKind::External);
+
+    let modules = graph.export(&ExportOptions::new(Granularity::Module).with_root(root)
+        .with_service(Some("api".to_string()))
+        .with_first_party_only(true)
+        .with_max_depth(Some(1)));
+    let ids: Vec<&str> = modules.nodes.iter().map(|n| n.id.as_str()).collect();
+    assert_eq!(ids, vec!["api", "api.views", "shared", "shared.util"]);
+    assert!(modules.edges.iter().any(|e| e.from == "api.views" && e.to == "shared.util"));
+
+    let files = graph.export(&ExportOptions::new(Granularity::File).with_root(root).with_service(Some("worker".to_string())));
+    let dot = files.render(ExportFormat::Dot);
+    assert!(dot.contains("\"worker/worker/jobs.py\" -> \"external:celery\";"));
+    let mermaid = files.render(ExportFormat::Mermaid);
+    assert!(mermaid.starts_with("graph LR\n"));
+    assert!(mermaid.contains("([\"celery\"])"));
+    let json: serde_json::Value = serde_json::from_str(&files.render(ExportFormat::Json)).unwrap();
+    assert_eq!(json["granularity"], "file");
+    assert_eq!(json["edges"][0]["imports"], 1);
+
+    let packages = graph.export(&ExportOptions::new(Granularity::Package).with_root(root).with_first_party_only(true));
+    assert!(packages.edges.iter().any(|e| e.from == "api/api" && e.to == "shared/shared"));
+}
+
+#[test]
+fn test_snapshot_round_trip_and_relocation() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("repo");
+    write_file(&root, "api/api/views.py", "import requests\nfrom shared.util import helper\n");
+    write_file(&root, "api/requirements.txt", "requests==2.0.0\n");
+    write_file(&root, "shared/shared/util.py", "");
+    let mut graph = DependencyGraph::new()
+        .with_source_roots(vec![root.join("api"), root.join("shared")]);
+    graph.build_from_directory(&root, HashMap::from([service_at(&root, "api"), service_at(&root, "shared")])).unwrap();
+
+    let snapshot_path = temp_dir.path().join("graph.json");
+    graph.save(&snapshot_path, &root).unwrap();
+    let saved = std::fs::read_to_string(&snapshot_path).unwrap();
+    assert!(!saved.contains(root.to_str().unwrap()));
+
+    // Loaded against another checkout, every path resolves under the new root
+    let moved = temp_dir.path().join("moved");
+    let loaded = DependencyGraph::load(&snapshot_path, &moved).unwrap();
+    let affected: Vec<&str> = loaded.get_affected_services(&moved.join("shared/shared/util.py"))
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    assert_eq!(affected, vec!["api", "shared"]);
+    assert!(loaded.get_affected_files(&moved.join("api/requirements.txt")).contains(&moved.join("api/api/views.py")));
+    assert_eq!(loaded.services()["api"].root_path, moved.join("api"));
+
+    let json = serde_json::to_string(&graph).unwrap();
+    let decoded: DependencyGraph = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        decoded.get_affected_files(&root.join("shared/shared/util.py")),
+        graph.get_affected_files(&root.join("shared/shared/util.py")),
+    );
+
+    std::fs::write(&snapshot_path, saved.replacen(&format!("\"format\": {}", snapshot::SNAPSHOT_FORMAT), "\"format\": 99", 1)).unwrap();
+    let error = DependencyGraph::load(&snapshot_path, &root).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert!(error.to_string().contains("Unsupported graph snapshot format 99"));
+}
+
+#[test]
+fn test_snapshot_keeps_conftest_links() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    write_file(root, "api/pytest.ini", "[pytest]\n");
+    write_file(root, "api/tests/conftest.py", "");
+    write_file(root, "api/tests/unit/test_models.py", "");
+    let mut graph = DependencyGraph::new();
+    graph.build_from_directory(root, HashMap::new()).unwrap();
+
+    let conftest = root.join("api/tests/conftest.py");
+    let mut loaded = DependencyGraph::from_snapshot(graph.snapshot(root), root);
+    assert_eq!(loaded.select_tests(std::slice::from_ref(&conftest)).files, vec![root.join("api/tests/unit/test_models.py")]);
+
+    // A rootdir below the conftest.py cuts the link the snapshot recorded
+    write_file(root, "api/tests/unit/pytest.ini", "[pytest]\n");
+    loaded.update_file(&root.join("api/tests/unit/pytest.ini")).unwrap();
+    assert!(loaded.select_tests(&[conftest]).files.is_empty());
+}
+
+#[test]
+fn test_diff_between_checkouts() {
+    let temp_dir = TempDir::new().unwrap();
+    let build = |root: &Path, files: &[(&str, &str)]| {
+        for (path, content) in files {
+            write_file(root, path, content);
+        }
+        let mut graph = DependencyGraph::new()
+            .with_source_roots(vec![root.join("auth"), root.join("billing"), root.join("shared")]);
+        let services = HashMap::from([service_at(root, "auth"), service_at(root, "billing"), service_at(root, "shared")]);
+        graph.build_from_directory(root, services).unwrap();
+        graph
+    };
+    let base_root = temp_dir.path().join("base");
+    let base = build(&base_root, &[
+        ("auth/auth/models.py", "import requests\n"),
+        ("billing/billing/pay.py", "from shared.money import Money\n"),
+        ("shared/shared/money.py", ""),
+    ]);
+    let head_root = temp_dir.path().join("head");
+    let head = build(&head_root, &[
+        ("auth/auth/models.py", "import requests\nfrom billing.pay import charge\n"),
+        ("billing/billing/pay.py", "import os\nimport stripe\nfrom auth.models import User\n"),
+        ("shared/shared/money.py", ""),
+    ]);
+
+    let diff = GraphDiff::between(&base, &base_root, &head, &head_root);
+    let edge = |from: &str, to: &str| DiffEdge { from: from.to_string(), to: to.to_string() };
+    assert_eq!(diff.service_edges.added, vec![edge("auth", "billing"), edge("billing", "auth")]);
+    assert_eq!(diff.service_edges.removed, vec![edge("billing", "shared")]);
+    assert_eq!(diff.package_edges.removed, vec![edge("billing/billing", "shared/shared")]);
+    assert!(diff.file_edges.added.contains(&edge("billing/billing/pay.py", "auth/auth/models.py")));
+    assert_eq!(diff.new_packages.len(), 1);
+    assert_eq!(diff.new_packages[0].name, "stripe");
+    assert_eq!(diff.new_packages[0].files, vec!["billing/billing/pay.py".to_string()]);
+    let service_cycles: Vec<&Vec<String>> = diff.new_cycles.iter()
+        .filter(|c| c.level == CycleLevel::Service)
+        .map(|c| &c.members)
+        .collect();
+    assert_eq!(service_cycles, vec![&vec!["auth".to_string(), "billing".to_string()]]);
+
+    assert!(diff.render(DiffFormat::Text).contains("  + billing -> auth\n"));
+    assert!(diff.render(DiffFormat::Markdown).contains("| added | `billing` | `auth` |"));
+    let json: serde_json::Value = serde_json::from_str(&diff.render(DiffFormat::Json)).unwrap();
+    assert_eq!(json["new_packages"][0]["name"], "stripe");
+
+    assert!(GraphDiff::between(&base, &base_root, &base, &base_root).is_empty());
+}
+
+#[test]
+fn test_import_rules() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    write_file(root, "services/auth/auth/__init__.py", "");
+    write_file(root, "services/auth/auth/models.py", "from auth.tokens import sign\nfrom billing.pay import charge\nimport money\n");
+    write_file(root, "services/auth/auth/tokens.py", "");
+    write_file(root, "services/billing/billing/__init__.py", "");
+    write_file(root, "services/billing/billing/pay.py", "import money\n");
+    write_file(root, "libs/money/__init__.py", "");
+    let mut graph = DependencyGraph::new().with_source_roots(vec![
+        root.join("services/auth"),
+        root.join("services/billing"),
+        root.join("libs"),
+    ]);
+    graph.build_from_directory(root, HashMap::new()).unwrap();
+
+    let rule = |from: &str, to: &str, action: RuleAction| CompiledImportRule::new(ImportRule {
+        from: from.to_string(),
+        to: to.to_string(),
+        action,
+        reason: None,
+    }).unwrap();
+    let rules = vec![
+        rule("services/{name}", "services/{name}", RuleAction::Allow),
+        rule("services/*", "libs/**", RuleAction::Allow),
+        rule("services/*", "**", RuleAction::Deny),
+    ];
+    assert!(rules[0].matches("services/auth/auth/models.py", "services/auth/auth/tokens.py"));
+    assert!(!rules[0].matches("services/auth/auth/models.py", "services/billing/billing/pay.py"));
+    assert!(rules[1].matches("services/auth/x.py", "libs/money/__init__.py"));
+
+    let violations = graph.check_rules(&rules, root);
+    assert_eq!(violations.len(), 1, "{:?}", violations);
+    assert_eq!(violations[0].file, root.join("services/auth/auth/models.py"));
+    assert_eq!(violations[0].target, root.join("services/billing/billing/pay.py"));
+    assert_eq!(violations[0].line, Some(2));
+    assert_eq!(violations[0].text.as_deref(), Some("from billing.pay import charge"));
+    assert_eq!(violations[0].rule, 2);
+
+    assert!(CompiledImportRule::new(ImportRule {
+        from: "services/*".to_string(),
+        to: "services/{name}".to_string(),
+        ..Default::default()
+    }).is_err());
+}
+
+#[test]
+fn test_undeclared_and_unused_dependencies() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    write_file(root, "requirements.txt", "requests==2.31\n");
+    write_file(root, "auth/requirements.txt", "PyYAML>=6\nflask\nbilling\ngunicorn\nmy-sdk\n");
+    write_file(root, "auth/auth/app.py", "import os\nimport yaml\nimport requests\nimport mysdk\nimport stripe\nimport billing\n");
+    write_file(root, "auth/auth/views.py", "def handler():\n    import flask\n");
+    write_file(root, "billing/billing/pay.py", "import stripe\n");
+    let mut graph = DependencyGraph::new();
+    let services = HashMap::from([service_at(root, "auth"), service_at(root, "billing")]);
+    graph.build_from_directory(root, services).unwrap();
+
+    let check = DepsCheck::new(&DepsCheckConfig {
+        import_names: BTreeMap::from([("My_SDK".to_string(), vec!["mysdk".to_string()])]),
+        allow_unused: vec!["gunicorn".to_string()],
+        allow_undeclared: vec![],
+    }).unwrap();
+    let report = graph.check_dependencies(&check, root);
+    let auth = &report.services[0];
+    assert_eq!(auth.service, "auth");
+    assert_eq!(auth.dependency_files, vec!["auth/requirements.txt".to_string(), "requirements.txt".to_string()]);
+    assert_eq!(auth.undeclared.len(), 1, "{:?}", auth.undeclared);
+    assert_eq!(auth.undeclared[0].module, "stripe");
+    assert_eq!(auth.undeclared[0].files, vec!["auth/auth/app.py".to_string()]);
+    assert!(auth.unused.is_empty(), "{:?}", auth.unused);
+
+    let billing = &report.services[1];
+    assert_eq!(billing.dependency_files, vec!["requirements.txt".to_string()]);
+    assert_eq!(billing.undeclared[0].module, "stripe");
+    assert!(report.render(ReportFormat::Text).contains("  undeclared stripe (imported by billing/billing/pay.py)\n"));
+
+    let check = DepsCheck::new(&DepsCheckConfig {
+        allow_undeclared: vec!["str*".to_string()],
+        ..Default::default()
+    }).unwrap();
+    let report = graph.check_dependencies(&check, root);
+    let auth = &report.services[0];
+    assert!(auth.undeclared.iter().any(|i| i.module == "mysdk"));
+    let unused: Vec<&str> = auth.unused.iter().map(|u| u.package.as_str()).collect();
+    assert_eq!(unused, vec!["gunicorn", "my-sdk"]);
+    assert!(report.services[1].is_clean());
+    let json: serde_json::Value = serde_json::from_str(&report.render(ReportFormat::Json)).unwrap();
+    assert_eq!(json["services"][0]["unused"][1]["declared_in"][0], "auth/requirements.txt");
+}
+
+#[test]
+fn test_version_conflicts_and_baseline_drift() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    write_file(root, "requirements.txt", "requests>=2.28\n");
+    write_file(root, "auth/requirements.txt", "Requests==2.28.0\nPyYAML>=6\nurllib3<2; python_version < \"3.8\"\n");
+    write_file(root, "billing/pyproject.toml", "[tool.poetry.dependencies]\npython = \"^3.11\"\nrequests = \"^2.31\"\npyyaml = \"^6.0\"\nurllib3 = \">=2\"\n");
+    let mut graph = DependencyGraph::new();
+    let services = HashMap::from([service_at(root, "auth"), service_at(root, "billing")]);
+    graph.build_from_directory(root, services).unwrap();
+
+    let baseline = VersionBaseline::new(&BTreeMap::from([("pyyaml".to_string(), ">=6,<7".to_string())])).unwrap();
+    let report = graph.version_report(&baseline, root);
+    let names: Vec<&str> = report.packages.iter().map(|p| p.package.as_str()).collect();
+    assert_eq!(names, vec!["pyyaml", "requests", "urllib3"]);
+
+    let pyyaml = &report.packages[0];
+    assert_eq!(pyyaml.status, VersionStatus::Differs);
+    assert_eq!(pyyaml.constraints[0].service.as_deref(), Some("auth"));
+    assert_eq!(pyyaml.constraints[0].drift, Some(Drift::Compatible));
+    assert_eq!(pyyaml.constraints[1].specifier.as_deref(), Some("^6.0"));
+    assert_eq!(pyyaml.constraints[1].drift, None);
+
+    let requests = &report.packages[1];
+    assert_eq!(requests.status, VersionStatus::Conflict);
+    let files: Vec<&str> = requests.constraints.iter().map(|c| c.file.as_str()).collect();
+    assert_eq!(files, vec!["auth/requirements.txt", "billing/pyproject.toml", "requirements.txt"]);
+    assert_eq!(requests.constraints[2].service, None);
+
+    // The marker limits the auth pin to old interpreters, so it does not conflict
+    assert_eq!(report.packages[2].status, VersionStatus::Consistent);
+    assert!(!report.is_clean());
+    let text = report.render(ReportFormat::Text);
+    assert!(text.contains("requests: conflict\n  auth: ==2.28.0 in auth/requirements.txt\n"), "{}", text);
+    assert!(text.contains("  auth: >=6 in auth/requirements.txt (drifted)\n"), "{}", text);
+
+    let baseline = VersionBaseline::new(&BTreeMap::from([("requests".to_string(), ">=2.31,<3".to_string())])).unwrap();
+    let report = graph.version_report(&baseline, root);
+    let requests = report.packages.iter().find(|p| p.package == "requests").unwrap();
+    assert_eq!(requests.constraints[0].drift, Some(Drift::Incompatible));
+    assert_eq!(requests.constraints[1].drift, None);
+
+    // A typo in a specifier is reported rather than ignored
+    write_file(root, "billing/requirements.txt", "flask>=2.0,<=latest\n");
+    graph.update_file(&root.join("billing/requirements.txt")).unwrap();
+    let report = graph.version_report(&VersionBaseline::default(), root);
+    let flask = report.packages.iter().find(|p| p.package == "flask").unwrap();
+    assert!(flask.constraints[0].error.is_some());
+    assert!(!report.is_clean());
+    assert!(report.to_text().contains("  billing: >=2.0,<=latest in billing/requirements.txt (unparseable: "), "{}", report.to_text());
+    assert!(VersionBaseline::new(&BTreeMap::from([("x".to_string(), "latest".to_string())])).is_err());
+}
+
+#[test]
+fn test_select_affected_tests() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    write_file(root, "auth/auth/models.py", "");
+    write_file(root, "auth/auth/views.py", "from auth.models import User\n");
+    write_file(root, "auth/tests/conftest.py", "from auth.views import app\n");
+    write_file(root, "auth/tests/test_views.py", "from auth.views import handler\n");
+    write_file(root, "auth/tests/api/test_api.py", "");
+    write_file(root, "auth/tests/helpers.py", "from auth.models import User\n");
+    write_file(root, "billing/billing/pay.py", "");
+    write_file(root, "billing/pay_test.py", "from billing.pay import charge\n");
+    write_file(root, "proto/billing.proto", "");
+    let mut graph = DependencyGraph::new()
+        .with_source_roots(vec![root.join("auth"), root.join("billing")])
+        .with_impact_rules(vec![CompiledImpactRule::new(root, ImpactRule {
+            paths: vec!["proto/**".to_string()],
+            services: vec!["billing".to_string()],
+            ..Default::default()
+        }).unwrap()])
+        .with_trigger_files(root, &["ci/**".to_string()])
+        .unwrap();
+    let services = HashMap::from([service_at(root, "auth"), service_at(root, "billing")]);
+    graph.build_from_directory(root, services).unwrap();
+    assert!(!graph.is_test_file(&root.join("auth/tests/conftest.py")));
+
+    // models.py reaches conftest.py, which pytest loads for every test beneath it
+    let selection = graph.select_tests(&[root.join("auth/auth/models.py")]);
+    assert_eq!(selection.files, vec![root.join("auth/tests/api/test_api.py"), root.join("auth/tests/test_views.py")]);
+    assert_eq!(selection.run_all, None);
+
+    let selection = graph.select_tests(&[root.join("billing/billing/pay.py"), root.join("proto/billing.proto")]);
+    assert_eq!(selection.files, vec![root.join("billing/pay_test.py")]);
+    assert_eq!(selection.render(TestFormat::Pytest, root), "billing/pay_test.py\n");
+    assert!(graph.select_tests(&[root.join("auth/tests/helpers.py")]).files.is_empty());
+
+    // A deleted test file is reported as changed by git but must not be passed to pytest
+    std::fs::remove_file(root.join("auth/tests/api/test_api.py")).unwrap();
+    graph.remove_file(&root.join("auth/tests/api/test_api.py")).unwrap();
+    assert!(graph.select_tests(&[root.join("auth/tests/api/test_api.py")]).files.is_empty());
+    write_file(root, "auth/tests/api/test_api.py", "");
+    graph.update_file(&root.join("auth/tests/api/test_api.py")).unwrap();
+
+    let selection = graph.select_tests(&[root.join("ci/pipeline.yml")]);
+    assert_eq!(selection.run_all.as_ref().unwrap().file, root.join("ci/pipeline.yml"));
+    assert_eq!(selection.files.len(), 3);
+    assert_eq!(selection.render(TestFormat::Pytest, root), ".\n");
+    let json: serde_json::Value = serde_json::from_str(&selection.render(TestFormat::Json, root)).unwrap();
+    assert_eq!(json["run_all"]["file"], "ci/pipeline.yml");
+
+    let selector = TestSelector::new(root, &TestConfig { patterns: vec!["auth/tests".to_string()], ..Default::default() }).unwrap();
+    let graph = graph.with_test_selector(selector);
+    let selection = graph.select_tests(&[root.join("auth/auth/models.py")]);
+    assert_eq!(selection.files, vec![
+        root.join("auth/tests/api/test_api.py"),
+        root.join("auth/tests/helpers.py"),
+        root.join("auth/tests/test_views.py"),
+    ]);
+}
+
+#[test]
+fn test_conftest_scoping_plugins_and_testpaths() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    write_file(root, "conftest.py", "");
+    write_file(root, "api/pytest.ini", "[pytest]\ntestpaths =\n    tests\n");
+    write_file(root, "api/conftest.py", "pytest_plugins = [\"fixtures.db\"]\n");
+    write_file(root, "api/fixtures/__init__.py", "");
+    write_file(root, "api/fixtures/db.py", "");
+    write_file(root, "api/tests/conftest.py", "");
+    write_file(root, "api/tests/unit/test_models.py", "");
+    write_file(root, "api/scripts/test_data.py", "");
+    write_file(root, "web/pyproject.toml", "[tool.pytest.ini_options]\ntestpaths = [\"spec\"]\n");
+    write_file(root, "web/spec/test_pages.py", "");
+    write_file(root, "tests/test_root.py", "");
+    let mut graph = DependencyGraph::new().with_source_roots(vec![root.join("api")]);
+    graph.build_from_directory(root, HashMap::new()).unwrap();
+
+    // Conftest files above a rootdir are not loaded for its tests
+    let selection = graph.select_tests(&[root.join("conftest.py")]);
+    assert_eq!(selection.files, vec![root.join("tests/test_root.py")]);
+
+    // Plugins reach every test under the conftest.py declaring them, through nested conftests
+    let selection = graph.select_tests(&[root.join("api/fixtures/db.py")]);
+    assert_eq!(selection.files, vec![root.join("api/tests/unit/test_models.py")]);
+    let selection = graph.select_tests(&[root.join("api/tests/conftest.py")]);
+    assert_eq!(selection.files, vec![root.join("api/tests/unit/test_models.py")]);
+
+    // Files outside the testpaths are not collected
+    assert!(!graph.is_test_file(&root.join("api/scripts/test_data.py")));
+    assert!(graph.is_test_file(&root.join("web/spec/test_pages.py")));
+    assert_eq!(graph.pytest_rootdir(&root.join("web/spec/test_pages.py")), Some(root.join("web").as_path()));
+
+    // Conftest edges follow created and deleted files and config changes
+    write_file(root, "web/spec/conftest.py", "");
+    graph.update_file(&root.join("web/spec/conftest.py")).unwrap();
+    assert_eq!(graph.select_tests(&[root.join("web/spec/conftest.py")]).files, vec![root.join("web/spec/test_pages.py")]);
+    write_file(root, "api/pytest.ini", "[pytest]\n");
+    graph.update_file(&root.join("api/pytest.ini")).unwrap();
+    assert!(graph.is_test_file(&root.join("api/scripts/test_data.py")));
+    assert_eq!(graph.select_tests(&[root.join("api/conftest.py")]).files.len(), 2);
+    std::fs::remove_file(root.join("api/pytest.ini")).unwrap();
+    graph.remove_file(&root.join("api/pytest.ini")).unwrap();
+    assert!(graph.select_tests(&[root.join("conftest.py")]).files.contains(&root.join("api/tests/unit/test_models.py")));
+
+    let config = |name: &str, content: &str| parse_pytest_config(Path::new(name), content);
+    assert_eq!(config("setup.cfg", "[metadata]\nname = x\n[tool:pytest]\ntestpaths = a b\n"), Some(vec!["a".to_string(), "b".to_string()]));
+    assert_eq!(config("tox.ini", "[tox]\nenvlist = py3\n"), None);
+    assert_eq!(config("pytest.ini", ""), Some(vec![]));
+    assert_eq!(config("pyproject.toml", "[project]\nname = \"x\"\n"), None);
+}
+
+#[test]
+fn test_coverage_tests_union_static_selection() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    write_file(root, "app/app/core.py", "");
+    write_file(root, "app/app/plugins/email.py", "");
+    write_file(root, "app/tests/test_core.py", "from app.core import run\n");
+    write_file(root, "app/tests/test_dispatch.py", "import importlib\n");
+    write_file(root, "pytest.ini", "[pytest]\n");
+    let mut graph = DependencyGraph::new().with_source_roots(vec![root.join("app")]);
+    graph.build_from_directory(root, HashMap::from([service_at(root, "app")])).unwrap();
+    assert!(graph.select_tests(&[root.join("app/app/plugins/email.py")]).files.is_empty());
+
+    // pytest-cov with --cov-context=test, run from the service directory: files are recorded
+    // relative to it, node IDs relative to the pytest rootdir above
+    let database = root.join("app/.coverage");
+    let connection = rusqlite::Connection::open(&database).unwrap();
+    connection.execute_batch("
+        CREATE TABLE file (id INTEGER PRIMARY KEY, path TEXT);
+        CREATE TABLE context (id INTEGER PRIMARY KEY, context TEXT);
+        CREATE TABLE line_bits (file_id INTEGER, context_id INTEGER, numbits BLOB);
+        CREATE TABLE arc (file_id INTEGER, context_id INTEGER, fromno INTEGER, tono INTEGER);
+        INSERT INTO file VALUES (1, 'app/plugins/email.py'), (2, 'app/core.py');
+        INSERT INTO context VALUES (1, ''), (2, 'app/tests/test_dispatch.py::test_send|run'),
+            (3, 'app/tests/test_core.py::TestRun::test_run|setup'), (4, 'app/tests/test_core.py::TestRun::test_run|run');
+        INSERT INTO line_bits VALUES (1, 1, x'01'), (1, 2, x'01'), (2, 4, x'01');
+        INSERT INTO arc VALUES (1, 3, 1, 2);
+    ").unwrap();
+    drop(connection);
+    let coverage = CoverageMap::load(&database).unwrap();
+    assert_eq!(coverage.files.len(), 2);
+    let graph = graph.with_coverage(coverage);
+
+    // Tests whose file static analysis already selects are not repeated as node IDs
+    let selection = graph.select_tests(&[root.join("app/app/plugins/email.py")]);
+    assert!(selection.files.is_empty());
+    assert_eq!(selection.covered, vec![
+        CoveredTest { file: root.join("app/tests/test_core.py"), name: "TestRun::test_run".to_string() },
+        CoveredTest { file: root.join("app/tests/test_dispatch.py"), name: "test_send".to_string() },
+    ]);
+    assert_eq!(
+        selection.render(TestFormat::Pytest, root),
+        "app/tests/test_core.py::TestRun::test_run app/tests/test_dispatch.py::test_send\n",
+    );
+    assert_eq!(selection.render(TestFormat::Files, root), "app/tests/test_core.py\napp/tests/test_dispatch.py\n");
+    let selection = graph.select_tests(&[root.join("app/app/core.py")]);
+    assert_eq!(selection.files, vec![root.join("app/tests/test_core.py")]);
+    assert!(selection.covered.is_empty());
+
+    // Coverage is kept in snapshots, relative to the root
+    let graph = DependencyGraph::from_snapshot(graph.snapshot(root), root);
+    let impacts = graph.explain(&root.join("app/app/plugins/email.py"));
+    assert_eq!(impacts[0].reasons, vec![ImpactReason::ContainsChange]);
+    assert_eq!(graph.tests_covering(&root.join("app/app/core.py")).len(), 1);
+
+    let json = root.join("coverage.json");
+    write_file(root, "coverage.json", r#"{"files": {"app/app/core.py": {"contexts": {"1": ["app/tests/test_core.py::test_a|run"], "2": ["", "app/tests/test_core.py::test_a|run"]}}}}"#);
+    let coverage = CoverageMap::load(&json).unwrap();
+    assert_eq!(coverage.files[&root.join("app/app/core.py")], BTreeSet::from(["app/tests/test_core.py::test_a".to_string()]));
+    write_file(root, "coverage.json", r#"{"files": {"app/app/core.py": {"executed_lines": [1]}}}"#);
+    assert_eq!(CoverageMap::load(&json).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,86 @@
+use super::cycles::strongly_connected_components;
+use super::DependencyGraph;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+
+/// Files reached by walking importers outward from a changed file, breadth first
+#[derive(Debug, Clone, Default)]
+pub struct Traversal {
+    /// Files in the order they were reached, starting with the changed file
+    pub order: Vec<PathBuf>,
+    /// Depth at which each file was reached: 0 for the changed file, 1 for its direct importers
+    pub depths: HashMap<PathBuf, usize>,
+    /// Import cycles among the reached files, as the sorted members of each strongly
+    /// connected component
+    pub cycles: Vec<Vec<PathBuf>>,
+    /// Whether the depth limit left importers unvisited
+    pub truncated: bool,
+    // The file each reached file was first reached from
+    parents: HashMap<PathBuf, PathBuf>,
+}
+
+impl Traversal {
+    pub fn depth(&self, file: &Path) -> Option<usize> {
+        self.depths.get(file).copied()
+    }
+
+    /// The shortest import chain from the changed file to `file`, both included
+    pub fn chain(&self, file: &Path) -> Vec<PathBuf> {
+        let mut chain = vec![file.to_path_buf()];
+        while let Some(parent) = chain.last().and_then(|f| self.parents.get(f)) {
+            chain.push(parent.clone());
+        }
+        chain.reverse();
+        chain
+    }
+}
+
+impl DependencyGraph {
+    /// Walk the files importing `start`, directly or transitively, up to `max_depth` import
+    /// steps away (`Some(1)` for direct importers only, `None` for the full closure)
+    pub fn traverse_importers(&self, start: &Path, max_depth: Option<usize>) -> Traversal {
+        let mut traversal = Traversal::default();
+        traversal.depths.insert(start.to_path_buf(), 0);
+        let mut queue = VecDeque::from([start.to_path_buf()]);
+
+        while let Some(file) = queue.pop_front() {
+            let depth = traversal.depths[&file];
+            traversal.order.push(file.clone());
+            let Some(dependents) = self.reverse_deps.get(&file) else {
+                continue;
+            };
+            if max_depth.is_some_and(|max| depth >= max) {
+                traversal.truncated |= !dependents.is_empty();
+                continue;
+            }
+
+            let mut dependents: Vec<&PathBuf> = dependents.iter().collect();
+            dependents.sort();
+            for dependent in dependents {
+                if traversal.depths.contains_key(dependent) {
+                    continue;
+                }
+                traversal.depths.insert(dependent.clone(), depth + 1);
+                traversal.parents.insert(dependent.clone(), file.clone());
+                queue.push_back(dependent.clone());
+            }
+        }
+
+        // Components rather than back edges, so cycles between files reached along different
+        // paths are found too
+        let edges: BTreeMap<&PathBuf, BTreeSet<&PathBuf>> = traversal.order.iter()
+            .map(|file| {
+                let dependents = self.reverse_deps.get(file).into_iter()
+                    .flatten()
+                    .filter(|dependent| traversal.depths.contains_key(*dependent))
+                    .collect();
+                (file, dependents)
+            })
+            .collect();
+        traversal.cycles = strongly_connected_components(&edges)
+            .into_iter()
+            .map(|members| members.into_iter().cloned().collect())
+            .collect();
+        traversal
+    }
+}
@@ -0,0 +1,229 @@
+use super::deps_check::ReportFormat;
+use super::DependencyGraph;
+use crate::package_deps::models::DependencyFile;
+use crate::package_deps::names::normalize_name;
+use crate::package_deps::specifier::SpecifierSet;
+use crate::utils::relative_path_string;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::Path;
+
+/// Agreed version specifiers, keyed by normalized distribution name
+#[derive(Debug, Clone, Default)]
+pub struct VersionBaseline {
+    specifiers: BTreeMap<String, (String, SpecifierSet)>,
+}
+
+impl VersionBaseline {
+    /// Parse the `[version_baseline]` configuration section
+    pub fn new(baseline: &BTreeMap<String, String>) -> Result<Self, String> {
+        let specifiers = baseline.iter()
+            .map(|(name, specifier)| {
+                let parsed = specifier.parse::<SpecifierSet>().map_err(|e| format!("{}: {}", name, e))?;
+                Ok((normalize_name(name), (specifier.clone(), parsed)))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self { specifiers })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VersionStatus {
+    /// Every declaration allows the same versions
+    Consistent,
+    /// Declarations differ but some version satisfies all of them
+    Differs,
+    /// No version satisfies every declaration
+    Conflict,
+}
+
+/// How a declaration departs from the baseline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Drift {
+    /// Allows different versions, some of which the baseline also allows
+    Compatible,
+    /// Allows none of the versions the baseline allows
+    Incompatible,
+}
+
+/// One declaration of a distribution in a dependency file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VersionConstraint {
+    /// The service containing the dependency file, or none for files outside every service
+    pub service: Option<String>,
+    /// Dependency file, relative to the root
+    pub file: String,
+    /// The specifier as written; none when any version is accepted
+    pub specifier: Option<String>,
+    pub marker: Option<String>,
+    pub drift: Option<Drift>,
+    /// Why the specifier could not be parsed; such declarations are left out of the checks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PackageVersions {
+    /// Normalized distribution name
+    pub package: String,
+    pub status: VersionStatus,
+    pub baseline: Option<String>,
+    pub constraints: Vec<VersionConstraint>,
+}
+
+impl PackageVersions {
+    pub fn has_drift(&self) -> bool {
+        self.constraints.iter().any(|c| c.drift.is_some())
+    }
+
+    pub fn has_unparseable(&self) -> bool {
+        self.constraints.iter().any(|c| c.error.is_some())
+    }
+
+    fn is_flagged(&self) -> bool {
+        self.status != VersionStatus::Consistent || self.has_drift() || self.has_unparseable()
+    }
+}
+
+/// Version constraints of every distribution declared in more than one dependency file, listed
+/// in the baseline or declared with a specifier that cannot be parsed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct VersionReport {
+    pub packages: Vec<PackageVersions>,
+}
+
+impl VersionReport {
+    /// Whether nothing conflicts, drifts from the baseline or fails to parse
+    pub fn is_clean(&self) -> bool {
+        self.packages.iter().all(|p| p.status != VersionStatus::Conflict && !p.has_drift() && !p.has_unparseable())
+    }
+
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Text => self.to_text(),
+            ReportFormat::Json => serde_json::to_string_pretty(self).expect("reports always serialize") + "\n",
+        }
+    }
+
+    /// Packages whose declarations differ, conflict, drift or fail to parse only
+    pub fn to_text(&self) -> String {
+        let flagged: Vec<&PackageVersions> = self.packages.iter().filter(|p| p.is_flagged()).collect();
+        if flagged.is_empty() {
+            return "No version conflicts\n".to_string();
+        }
+        let mut out = String::new();
+        for package in flagged {
+            let status = match package.status {
+                VersionStatus::Consistent => "consistent",
+                VersionStatus::Differs => "differs",
+                VersionStatus::Conflict => "conflict",
+            };
+            let _ = writeln!(out, "{}: {}", package.package, status);
+            if let Some(baseline) = &package.baseline {
+                let _ = writeln!(out, "  baseline {}", baseline);
+            }
+            for constraint in &package.constraints {
+                let drift = match (&constraint.error, constraint.drift) {
+                    (Some(error), _) => format!(" (unparseable: {})", error),
+                    (None, Some(Drift::Compatible)) => " (drifted)".to_string(),
+                    (None, Some(Drift::Incompatible)) => " (incompatible with baseline)".to_string(),
+                    (None, None) => String::new(),
+                };
+                let marker = constraint.marker.as_ref().map(|m| format!("; {}", m)).unwrap_or_default();
+                let _ = writeln!(
+                    out,
+                    "  {}: {}{} in {}{}",
+                    constraint.service.as_deref().unwrap_or("(no service)"),
+                    constraint.specifier.as_deref().unwrap_or("any version"),
+                    marker,
+                    constraint.file,
+                    drift,
+                );
+            }
+        }
+        out
+    }
+}
+
+impl DependencyGraph {
+    /// Group the requirements of every parsed dependency file by distribution and compare their
+    /// version specifiers with each other and with `baseline`. Requirements with environment
+    /// markers apply to some environments only and are left out of the satisfiability check;
+    /// specifiers that cannot be parsed are reported as such instead.
+    pub fn version_report(&self, baseline: &VersionBaseline, root: &Path) -> VersionReport {
+        let relative = |path: &Path| relative_path_string(path, root).unwrap_or_else(|| path.display().to_string());
+        let mut dependency_files: Vec<&DependencyFile> = self.package_deps.dependency_files().values().collect();
+        dependency_files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let mut by_package: BTreeMap<String, Vec<(VersionConstraint, Option<SpecifierSet>)>> = BTreeMap::new();
+        for dependency_file in dependency_files {
+            // A file inside nested services belongs to the innermost one
+            let service = self.services.values()
+                .filter(|s| s.contains_path(&dependency_file.path))
+                .max_by_key(|s| s.root_path.components().count())
+                .map(|s| s.name.clone());
+            for requirement in &dependency_file.dependencies {
+                let parsed = requirement.specifier.as_deref().unwrap_or("").parse::<SpecifierSet>();
+                by_package.entry(normalize_name(&requirement.name)).or_default().push((
+                    VersionConstraint {
+                        service: service.clone(),
+                        file: relative(&dependency_file.path),
+                        specifier: requirement.specifier.clone(),
+                        marker: requirement.marker.clone(),
+                        drift: None,
+                        error: parsed.as_ref().err().cloned(),
+                    },
+                    parsed.ok(),
+                ));
+            }
+        }
+
+        let packages = by_package.into_iter()
+            .filter(|(name, constraints)| {
+                constraints.len() > 1
+                    || baseline.specifiers.contains_key(name)
+                    || constraints.iter().any(|(constraint, _)| constraint.error.is_some())
+            })
+            .map(|(name, constraints)| {
+                let expected = baseline.specifiers.get(&name);
+                let unconditional: Vec<&SpecifierSet> = constraints.iter()
+                    .filter(|(constraint, _)| constraint.marker.is_none())
+                    .filter_map(|(_, parsed)| parsed.as_ref())
+                    .collect();
+                let status = if !SpecifierSet::compatible(unconditional.iter().copied()) {
+                    VersionStatus::Conflict
+                } else if unconditional.windows(2).any(|pair| pair[0] != pair[1]) {
+                    VersionStatus::Differs
+                } else {
+                    VersionStatus::Consistent
+                };
+
+                let constraints = constraints.into_iter()
+                    .map(|(constraint, parsed)| {
+                        let drift = match (expected, parsed) {
+                            (Some((_, expected)), Some(parsed)) if parsed != *expected => {
+                                if SpecifierSet::compatible([expected, &parsed]) {
+                                    Some(Drift::Compatible)
+                                } else {
+                                    Some(Drift::Incompatible)
+                                }
+                            }
+                            _ => None,
+                        };
+                        VersionConstraint { drift, ..constraint }
+                    })
+                    .collect();
+                PackageVersions {
+                    package: name,
+                    status,
+                    baseline: expected.map(|(specifier, _)| specifier.clone()),
+                    constraints,
+                }
+            })
+            .collect();
+        VersionReport { packages }
+    }
+}
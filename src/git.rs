@@ -0,0 +1,69 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Run git in `dir`, returning its trimmed standard output
+fn git(dir: &Path, args: &[&str]) -> std::io::Result<String> {
+    let output = Command::new("git").arg("-C").arg(dir).args(args).output()?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim(),
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The top-level directory of the repository containing `dir`
+pub fn toplevel(dir: &Path) -> std::io::Result<PathBuf> {
+    git(dir, &["rev-parse", "--show-toplevel"]).map(PathBuf::from)
+}
+
+/// Files changed in the working tree of the repository containing `dir` since `revision`,
+/// including untracked files, as absolute paths
+pub fn changed_files(dir: &Path, revision: &str) -> std::io::Result<Vec<PathBuf>> {
+    let toplevel = toplevel(dir)?;
+    let changed = git(&toplevel, &["diff", "--name-only", "--no-renames", revision, "--"])?;
+    let untracked = git(&toplevel, &["ls-files", "--others", "--exclude-standard"])?;
+    let mut files: Vec<PathBuf> = changed.lines()
+        .chain(untracked.lines())
+        .filter(|line| !line.is_empty())
+        .map(|line| toplevel.join(line))
+        .collect();
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// A temporary detached checkout of a revision, removed again when dropped
+#[derive(Debug)]
+pub struct Worktree {
+    repo: PathBuf,
+    pub path: PathBuf,
+}
+
+impl Worktree {
+    /// Check out `revision` of the repository containing `repo` into a temporary directory
+    pub fn checkout(repo: &Path, revision: &str) -> std::io::Result<Self> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "pybia-worktree-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed),
+        ));
+        let path_arg = path.to_string_lossy().into_owned();
+        git(repo, &["worktree", "add", "--detach", "--quiet", &path_arg, revision])?;
+        Ok(Self { repo: repo.to_path_buf(), path })
+    }
+}
+
+impl Drop for Worktree {
+    fn drop(&mut self) {
+        let path_arg = self.path.to_string_lossy().into_owned();
+        if git(&self.repo, &["worktree", "remove", "--force", &path_arg]).is_err() {
+            let _ = std::fs::remove_dir_all(&self.path);
+            let _ = git(&self.repo, &["worktree", "prune"]);
+        }
+    }
+}
@@ -1,5 +1,13 @@
+pub mod cache;
+pub mod command;
+pub mod config;
 pub mod dependency;
+pub mod git;
 pub mod package_deps;
+pub mod scan;
 pub mod service;
 pub mod utils;
-pub mod watcher; 
\ No newline at end of file
+pub mod watcher;
+
+#[cfg(test)]
+mod test_support;
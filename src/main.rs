@@ -1,91 +1,834 @@
 use notify::Result;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use signal_hook::consts::{SIGINT, SIGTERM};
+use std::time::Duration;
 
 use file_watcher::{
-    watcher::FileWatcher,
+    cache::AnalysisCache,
+    scan::RepositoryScan,
+    command::{models::{CommandPolicy, RunMode}, runner::CommandRunner},
+    config::{self, models::{PybiaConfig, ServiceFormat}, validate::Severity},
+    dependency::{
+        assets::CompiledAssetRule,
+        coverage::CoverageMap,
+        cycles::{CycleBaseline, CycleLevel, ImportCycle},
+        deps_check::{DepsCheck, ReportFormat},
+        diff::{DiffFormat, GraphDiff},
+        export::{ExportFormat, ExportOptions, Granularity},
+        impact::CompiledImpactRule,
+        rules::{CompiledImportRule, RuleAction},
+        selection::{TestFormat, TestSelector},
+        versions::VersionBaseline,
+        DependencyGraph,
+    },
+    git::{self, Worktree},
+    watcher::{FileWatcher, WatcherBackend, PROBE_DIR_PREFIX},
     service::detector::ServiceDetector,
+    service::models::{DetectedService, NameCollisionPolicy},
+    utils::{clean_path, normalize_path, relative_path_string, PathPatterns},
 };
 
 #[derive(StructOpt, Debug)]
 #[structopt(name = "file-watcher")]
 struct Opt {
-    /// Paths to watch for changes
-    #[structopt(long = "paths", required = true, parse(from_os_str))]
+    #[structopt(subcommand)]
+    cmd: Option<Command>,
+
+    /// Paths to scan and watch for changes, one per flag so that a subcommand may follow
+    /// [default: the config directory, or the current directory]
+    #[structopt(long = "paths", number_of_values = 1, parse(from_os_str))]
     paths: Vec<PathBuf>,
 
-    /// Command to run when files change
+    /// Command to run when files change; {affected_services}, {affected_paths} and {changed_files}
+    /// are substituted, plus {service} and {service_path} in per-service mode
     #[structopt(last = true)]
     command: Vec<String>,
 
-    /// Services configuration file
-    #[structopt(long = "services-config")]
-    services_config: Option<PathBuf>,
+    /// Configuration file [default: pybia.toml or [tool.pybia] in pyproject.toml, searched upwards]
+    #[structopt(long = "config", alias = "services-config", global = true, parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// Output format for affected services: name or name-path
+    #[structopt(long = "service-format")]
+    service_format: Option<ServiceFormat>,
+
+    /// Run the command once per batch of changes or once per affected service: once or per-service
+    #[structopt(long = "run-mode")]
+    run_mode: Option<RunMode>,
+
+    /// What to do when changes arrive while the command runs: queue, restart or parallel
+    #[structopt(long = "command-policy")]
+    command_policy: Option<CommandPolicy>,
+
+    /// Maximum simultaneous runs with the parallel command policy
+    #[structopt(long = "max-parallel")]
+    max_parallel: Option<usize>,
+
+    /// Where file events come from: auto, native or poll. auto probes the native watcher once at
+    /// startup and polls if it reports nothing; a watcher that stops reporting later is not noticed
+    #[structopt(long = "backend")]
+    backend: Option<WatcherBackend>,
+
+    /// How often the polling backend scans for changes, in milliseconds
+    #[structopt(long = "poll-interval-ms")]
+    poll_interval_ms: Option<u64>,
+
+    /// Only follow importers this many import steps from a changed file (1: direct importers)
+    #[structopt(long = "max-depth", global = true)]
+    max_depth: Option<usize>,
+
+    /// Parse every file again instead of reusing results cached by earlier runs
+    #[structopt(long = "no-cache", global = true)]
+    no_cache: bool,
+
+    /// Analyse against a graph saved by `snapshot` instead of scanning the repository
+    #[structopt(long = "graph", global = true, parse(from_os_str))]
+    graph: Option<PathBuf>,
+
+    /// How to resolve services that share a name: error, path-qualified or alias
+    #[structopt(long = "name-collisions")]
+    name_collisions: Option<NameCollisionPolicy>,
 
-    /// Output format for affected services
-    #[structopt(long = "service-format", default_value = "name")]
-    service_format: ServiceFormat,
+    /// Per-test coverage data (.coverage or coverage JSON) whose tests count as affected too
+    #[structopt(long = "coverage", global = true, parse(from_os_str))]
+    coverage: Option<PathBuf>,
 }
 
-#[derive(Debug)]
-enum ServiceFormat {
-    Name,
-    NamePath,
+#[derive(StructOpt, Debug)]
+enum Command {
+    /// Check the configuration file for errors
+    Validate {
+        /// Also fail on warnings
+        #[structopt(long = "deny-warnings")]
+        deny_warnings: bool,
+    },
+    /// Show which services each changed file affects, and why
+    Explain {
+        /// Changed files
+        #[structopt(required = true, parse(from_os_str))]
+        files: Vec<PathBuf>,
+    },
+    /// List the test files affected by changed files
+    Tests {
+        /// Changed files
+        #[structopt(required_unless = "since", parse(from_os_str))]
+        files: Vec<PathBuf>,
+        /// Also take the files changed since this git revision, including uncommitted and untracked ones
+        #[structopt(long = "since")]
+        since: Option<String>,
+        /// files, pytest or json
+        #[structopt(long = "format", default_value = "files")]
+        format: TestFormat,
+    },
+    /// Report import cycles between files, packages and services
+    Cycles {
+        /// file, package or service [default: all three]
+        #[structopt(long = "level")]
+        level: Option<CycleLevel>,
+        /// Known service cycles; exit non-zero when a service cycle not listed here appears
+        #[structopt(long = "baseline", parse(from_os_str))]
+        baseline: Option<PathBuf>,
+        /// Record the current service cycles as the baseline instead of checking against it
+        #[structopt(long = "write-baseline", requires = "baseline")]
+        write_baseline: bool,
+    },
+    /// Export the dependency graph as Graphviz DOT, Mermaid or JSON
+    Graph {
+        /// dot, mermaid or json
+        #[structopt(long = "format", default_value = "dot")]
+        format: ExportFormat,
+        /// file, module, package or service
+        #[structopt(long = "granularity", default_value = "service")]
+        granularity: Granularity,
+        /// Only export what this service imports, directly or transitively
+        #[structopt(long = "service")]
+        service: Option<String>,
+        /// Leave out packages that do not resolve to a file in the repository
+        #[structopt(long = "first-party-only")]
+        first_party_only: bool,
+        /// Import steps to follow from the --service files [default: no limit]
+        #[structopt(long = "depth")]
+        depth: Option<usize>,
+        /// Changed files to highlight along with everything they affect
+        #[structopt(long = "changed", parse(from_os_str))]
+        changed: Vec<PathBuf>,
+        /// Write to this file instead of standard output
+        #[structopt(long = "output", parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Check imports against the configured import rules
+    Check,
+    /// Report third-party packages each service imports without declaring, or declares without importing
+    DepsCheck {
+        /// text or json
+        #[structopt(long = "format", default_value = "text")]
+        format: ReportFormat,
+    },
+    /// Compare the version constraints services declare for the same packages
+    Versions {
+        /// text or json
+        #[structopt(long = "format", default_value = "text")]
+        format: ReportFormat,
+    },
+    /// Report how dependencies changed between two revisions or saved graphs
+    Diff {
+        /// Git revision to compare against
+        #[structopt(long = "base", required_unless = "base-graph")]
+        base: Option<String>,
+        /// Graph saved by `snapshot` to compare against
+        #[structopt(long = "base-graph", conflicts_with = "base", parse(from_os_str))]
+        base_graph: Option<PathBuf>,
+        /// Git revision to compare [default: the working tree]
+        #[structopt(long = "head")]
+        head: Option<String>,
+        /// Graph saved by `snapshot` to compare
+        #[structopt(long = "head-graph", conflicts_with = "head", parse(from_os_str))]
+        head_graph: Option<PathBuf>,
+        /// text, json or markdown
+        #[structopt(long = "format", default_value = "text")]
+        format: DiffFormat,
+    },
+    /// Save the analysed dependency graph for later runs with --graph
+    Snapshot {
+        /// Where to write the graph
+        #[structopt(parse(from_os_str))]
+        output: PathBuf,
+    },
+    /// Detect services and write a starter configuration file
+    Init {
+        /// Repository root to scan [default: the current directory]
+        #[structopt(parse(from_os_str))]
+        root: Option<PathBuf>,
+        /// Where to write the configuration [default: <root>/pybia.toml]
+        #[structopt(long = "output", parse(from_os_str))]
+        output: Option<PathBuf>,
+        /// Overwrite an existing configuration file
+        #[structopt(long = "force")]
+        force: bool,
+    },
+}
+
+fn invalid_config(e: glob::PatternError) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Invalid glob in config: {}", e))
 }
 
-impl std::str::FromStr for ServiceFormat {
-    type Err = String;
+fn config_path(opt: &Opt) -> std::io::Result<Option<PathBuf>> {
+    match &opt.config {
+        Some(path) => Ok(Some(path.clone())),
+        None => Ok(config::discover(&std::env::current_dir()?)),
+    }
+}
 
-    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        match s {
-            "name" => Ok(ServiceFormat::Name),
-            "name-path" => Ok(ServiceFormat::NamePath),
-            _ => Err("Invalid service format".into()),
+fn load_config(opt: &Opt) -> std::io::Result<PybiaConfig> {
+    match config_path(opt)? {
+        Some(path) => config::load(&path),
+        None => {
+            let mut config = PybiaConfig::default();
+            config.resolve_paths(&std::env::current_dir()?);
+            Ok(config)
         }
     }
 }
 
-fn run() -> Result<()> {
-    let opt = Opt::from_args();
-    
-    // Load services configuration
-    let service_configs = if let Some(config_path) = &opt.services_config {
-        let content = std::fs::read_to_string(config_path)?;
-        toml::from_str(&content).map_err(|e| std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            format!("Failed to parse services config: {}", e)
-        ))?
+/// Configuration and scan settings shared by every command that analyses the repository
+struct Analysis {
+    config: PybiaConfig,
+    paths: Vec<PathBuf>,
+    ignore: PathPatterns,
+}
+
+impl Analysis {
+    fn prepare(opt: &Opt) -> std::io::Result<Self> {
+        let mut config = load_config(opt)?;
+        if let Some(coverage) = &opt.coverage {
+            config.tests.coverage = Some(absolute_path(coverage)?);
+        }
+        let paths = if opt.paths.is_empty() {
+            config.scan_paths()
+        } else {
+            opt.paths.iter().map(|p| absolute_path(p)).collect::<std::io::Result<_>>()?
+        };
+        Self::from_config(config, paths)
+    }
+
+    fn from_config(config: PybiaConfig, paths: Vec<PathBuf>) -> std::io::Result<Self> {
+        // The cache and the watcher's probe are written while running and are never part of
+        // the analysed tree
+        let mut ignore = config.ignore.clone();
+        ignore.extend(relative_path_string(&config.cache.dir, &config.root).filter(|dir| !dir.is_empty()));
+        ignore.push(format!("**/{}-*", PROBE_DIR_PREFIX));
+        let ignore = PathPatterns::new(&config.root, &ignore).map_err(invalid_config)?;
+        Ok(Self { config, paths, ignore })
+    }
+
+    fn open_cache(&self, opt: &Opt) -> Option<AnalysisCache> {
+        (self.config.cache.enabled && !opt.no_cache).then(|| AnalysisCache::open(&self.config.cache.dir))
+    }
+
+    fn service_detector(&self, opt: &Opt) -> ServiceDetector {
+        ServiceDetector::new(self.config.services.clone())
+            .with_collision_policy(opt.name_collisions.unwrap_or(self.config.name_collisions))
+            .with_ignore(self.ignore.clone())
+    }
+
+    fn detect_services(
+        &self,
+        opt: &Opt,
+        scan: &RepositoryScan,
+        cache: Option<&mut AnalysisCache>,
+    ) -> std::io::Result<HashMap<String, DetectedService>> {
+        let detector = self.service_detector(opt);
+        let detection = match cache {
+            Some(cache) => {
+                let fingerprint = detector.fingerprint_in(scan)?;
+                match cache.services(&fingerprint) {
+                    Some(detection) => detection.clone(),
+                    None => {
+                        let detection = detector.detect_in(scan)?;
+                        cache.set_services(fingerprint, detection.clone());
+                        detection
+                    }
+                }
+            }
+            None => detector.detect_in(scan)?,
+        };
+        for collision in &detection.collisions {
+            eprintln!("Warning: {} (resolved as: {})", collision, collision.resolved_names.join(", "));
+        }
+        Ok(detection.services)
+    }
+
+    /// A dependency graph configured from the config file, not yet built
+    fn new_graph(&self, opt: &Opt) -> std::io::Result<DependencyGraph> {
+        self.configure(opt, DependencyGraph::new())
+    }
+
+    /// Apply the rules and settings from the config file to `graph`
+    fn configure(&self, opt: &Opt, graph: DependencyGraph) -> std::io::Result<DependencyGraph> {
+        let impact_rules = self.config.impact_rules.iter()
+            .map(|rule| CompiledImpactRule::new(&self.config.root, rule.clone()))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(invalid_config)?;
+        let asset_rules = self.config.asset_rules.iter()
+            .map(|rule| CompiledAssetRule::new(&self.config.root, rule))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(invalid_config)?;
+        let test_selector = TestSelector::new(&self.config.root, &self.config.tests).map_err(invalid_config)?;
+        let graph = match self.load_coverage()? {
+            Some(coverage) => graph.with_coverage(coverage),
+            None => graph,
+        };
+        graph
+            .with_source_roots(self.config.source_roots.clone())
+            .with_test_selector(test_selector)
+            .with_max_depth(opt.max_depth.or(self.config.max_depth))
+            .with_ignore(self.ignore.clone())
+            .with_impact_rules(impact_rules)
+            .with_asset_rules(asset_rules)
+            .with_trigger_files(&self.config.root, &self.config.trigger_files)
+            .map_err(invalid_config)
+    }
+
+    /// The configured coverage data; missing data only leaves test selection to static analysis
+    fn load_coverage(&self) -> std::io::Result<Option<CoverageMap>> {
+        let Some(path) = &self.config.tests.coverage else {
+            return Ok(None);
+        };
+        match CoverageMap::load(path) {
+            Ok(coverage) => Ok(Some(coverage)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                eprintln!("Warning: no coverage data at {}; using static analysis only", path.display());
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Scan the first path once for both service detection and the graph, then add the others,
+    /// or load the graph given by `--graph`
+    fn build_graph(&self, opt: &Opt) -> std::io::Result<DependencyGraph> {
+        match &opt.graph {
+            Some(path) => self.configure(opt, DependencyGraph::load(path, &self.config.root)?),
+            None => self.scan_graph(opt),
+        }
+    }
+
+    fn scan_graph(&self, opt: &Opt) -> std::io::Result<DependencyGraph> {
+        let mut cache = self.open_cache(opt);
+        let mut scan = RepositoryScan::walk(&self.paths[0], &self.ignore);
+        let services = self.detect_services(opt, &scan, cache.as_mut())?;
+        scan.parse(cache.as_mut());
+
+        let mut graph = self.new_graph(opt)?;
+        if let Some(cache) = cache {
+            graph = graph.with_cache(cache);
+        }
+        graph.build_from_scan(&scan, services.clone())?;
+        for path in &self.paths[1..] {
+            graph.build_from_directory(path, services.clone())?;
+        }
+        save_cache(&mut graph);
+        Ok(graph)
+    }
+}
+
+/// A cache that cannot be written only costs the next run time, so it is not an error
+fn save_cache(graph: &mut DependencyGraph) {
+    if let Err(e) = graph.save_cache() {
+        eprintln!("Warning: failed to write analysis cache: {}", e);
+    }
+}
+
+fn absolute_path(path: &Path) -> std::io::Result<PathBuf> {
+    Ok(clean_path(&std::path::absolute(path)?))
+}
+
+fn explain(opt: &Opt, files: &[PathBuf]) -> std::io::Result<()> {
+    let analysis = Analysis::prepare(opt)?;
+    let graph = analysis.build_graph(opt)?;
+
+    for file in files {
+        let file = absolute_path(file)?;
+        println!("{}", file.display());
+        let impacts = graph.explain(&file);
+        if impacts.is_empty() {
+            println!("  no services affected");
+        }
+        for impact in impacts {
+            println!("  {} ({})", impact.name, impact.root_path.display());
+            for reason in &impact.reasons {
+                println!("    - {}", reason);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Print the test files affected by `files` and the files changed since `since`
+fn tests(opt: &Opt, files: &[PathBuf], since: Option<&str>, format: TestFormat) -> std::io::Result<()> {
+    let analysis = Analysis::prepare(opt)?;
+    let mut changed = files.iter().map(|f| absolute_path(f)).collect::<std::io::Result<Vec<_>>>()?;
+    if let Some(revision) = since {
+        changed.extend(git::changed_files(&analysis.config.root, revision)?);
+    }
+
+    let graph = analysis.build_graph(opt)?;
+    let selection = graph.select_tests(&changed);
+    if let Some(trigger) = &selection.run_all {
+        eprintln!("Running all tests: {} matches {}", trigger.file.display(), trigger.rule);
+    }
+    print!("{}", selection.render(format, &analysis.config.root));
+    Ok(())
+}
+
+/// A file or package node relative to the config root; service names are shown as is
+fn cycle_node(level: CycleLevel, node: &str, root: &Path) -> String {
+    match level {
+        CycleLevel::Service => node.to_string(),
+        CycleLevel::File | CycleLevel::Package => {
+            relative_path_string(Path::new(node), root).unwrap_or_else(|| node.to_string())
+        }
+    }
+}
+
+fn print_cycle(cycle: &ImportCycle, root: &Path) {
+    let members: Vec<String> = cycle.members.iter().map(|m| cycle_node(cycle.level, m, root)).collect();
+    println!("{} cycle: {}", cycle.level, members.join(", "));
+    for edge in &cycle.edges {
+        println!("  {} -> {}", cycle_node(cycle.level, &edge.from, root), cycle_node(cycle.level, &edge.to, root));
+        for site in &edge.imports {
+            let file = relative_path_string(&site.file, root).unwrap_or_else(|| site.file.display().to_string());
+            println!("    {}:{}: {}", file, site.line, site.text);
+        }
+    }
+}
+
+/// Print import cycles, returning whether no service cycle is missing from the baseline
+fn cycles(
+    opt: &Opt,
+    level: Option<CycleLevel>,
+    baseline: Option<&Path>,
+    write_baseline: bool,
+) -> std::io::Result<bool> {
+    let analysis = Analysis::prepare(opt)?;
+    let graph = analysis.build_graph(opt)?;
+    let root = &analysis.config.root;
+
+    let levels = match level {
+        Some(level) => vec![level],
+        None => CycleLevel::ALL.to_vec(),
+    };
+    let found: Vec<ImportCycle> = levels.iter().flat_map(|level| graph.cycles(*level)).collect();
+    for cycle in &found {
+        print_cycle(cycle, root);
+    }
+    for level in &levels {
+        let count = found.iter().filter(|c| c.level == *level).count();
+        eprintln!("{} {}-level cycle(s)", count, level);
+    }
+
+    let Some(baseline) = baseline else {
+        return Ok(true);
+    };
+    let service_cycles = if levels.contains(&CycleLevel::Service) {
+        found.into_iter().filter(|c| c.level == CycleLevel::Service).collect()
     } else {
-        Vec::new()
+        graph.cycles(CycleLevel::Service)
     };
+    if write_baseline {
+        CycleBaseline::from_cycles(&service_cycles).save(baseline)?;
+        eprintln!("Wrote {} service cycle(s) to {}", service_cycles.len(), baseline.display());
+        return Ok(true);
+    }
+    let new_cycles = CycleBaseline::load(baseline)?.new_cycles(&service_cycles);
+    for cycle in &new_cycles {
+        eprintln!("New service cycle not in {}: {}", baseline.display(), cycle.members.join(", "));
+    }
+    Ok(new_cycles.is_empty())
+}
+
+fn graph(opt: &Opt, format: ExportFormat, options: ExportOptions, output: Option<&Path>) -> std::io::Result<()> {
+    let analysis = Analysis::prepare(opt)?;
+    let graph = analysis.build_graph(opt)?;
+    if let Some(service) = &options.service {
+        if !graph.services().contains_key(service) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Unknown service: {}", service),
+            ));
+        }
+    }
+
+    let changed = options.changed.iter().map(|f| absolute_path(f)).collect::<std::io::Result<_>>()?;
+    let options = options.with_root(&analysis.config.root).with_changed(changed);
+    let rendered = graph.export(&options).render(format);
+    match output {
+        Some(path) => std::fs::write(path, rendered),
+        None => {
+            print!("{}", rendered);
+            Ok(())
+        }
+    }
+}
+
+/// Print every import the configured rules forbid, returning whether there were none
+fn check(opt: &Opt) -> std::io::Result<bool> {
+    let analysis = Analysis::prepare(opt)?;
+    let rules = analysis.config.import_rules.iter()
+        .map(|rule| CompiledImportRule::new(rule.clone()))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Invalid import rule: {}", e)))?;
+    if !rules.iter().any(|rule| rule.rule.action == RuleAction::Deny) {
+        eprintln!("No import rules deny anything; add [[import_rules]] to the configuration");
+        return Ok(true);
+    }
+
+    let root = &analysis.config.root;
+    let graph = analysis.build_graph(opt)?;
+    let violations = graph.check_rules(&rules, root);
+    let relative = |path: &Path| relative_path_string(path, root).unwrap_or_else(|| path.display().to_string());
+    for violation in &violations {
+        let rule = &rules[violation.rule].rule;
+        let location = match violation.line {
+            Some(line) => format!("{}:{}", relative(&violation.file), line),
+            None => relative(&violation.file),
+        };
+        println!("{}: forbidden import of {}", location, relative(&violation.target));
+        if let Some(text) = &violation.text {
+            println!("    {}", text);
+        }
+        let reason = rule.reason.as_ref().map(|r| format!(": {}", r)).unwrap_or_default();
+        println!("    denied by import rule {} ({} -> {}){}", violation.rule + 1, rule.from, rule.to, reason);
+    }
+    eprintln!("{} forbidden import(s)", violations.len());
+    Ok(violations.is_empty())
+}
+
+/// Print each service's undeclared and unused dependencies, returning whether there were none
+fn deps_check(opt: &Opt, format: ReportFormat) -> std::io::Result<bool> {
+    let analysis = Analysis::prepare(opt)?;
+    let check = DepsCheck::new(&analysis.config.deps_check)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Invalid deps_check glob: {}", e)))?;
+    let root = analysis.config.root.clone();
+    let graph = analysis.build_graph(opt)?;
+    let report = graph.check_dependencies(&check, &root);
+    print!("{}", report.render(format));
+    Ok(report.is_clean())
+}
+
+/// Print version constraints that differ between dependency files, returning whether none
+/// conflict or drift from the configured baseline
+fn versions(opt: &Opt, format: ReportFormat) -> std::io::Result<bool> {
+    let analysis = Analysis::prepare(opt)?;
+    let baseline = VersionBaseline::new(&analysis.config.version_baseline)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Invalid version baseline: {}", e)))?;
+    let root = analysis.config.root.clone();
+    let graph = analysis.build_graph(opt)?;
+    let report = graph.version_report(&baseline, &root);
+    print!("{}", report.render(format));
+    Ok(report.is_clean())
+}
+
+/// Where one side of a diff comes from
+enum GraphSource {
+    Revision(String),
+    Snapshot(PathBuf),
+    WorkingTree,
+}
+
+impl GraphSource {
+    fn new(revision: &Option<String>, snapshot: &Option<PathBuf>) -> Self {
+        match (revision, snapshot) {
+            (Some(revision), _) => GraphSource::Revision(revision.clone()),
+            (None, Some(snapshot)) => GraphSource::Snapshot(snapshot.clone()),
+            (None, None) => GraphSource::WorkingTree,
+        }
+    }
+
+    /// The graph with the root its paths are relative to, and the checkout it was built in
+    fn build(&self, opt: &Opt, analysis: &Analysis) -> std::io::Result<(DependencyGraph, PathBuf, Option<Worktree>)> {
+        let root = analysis.config.root.clone();
+        match self {
+            GraphSource::WorkingTree => Ok((analysis.build_graph(opt)?, root, None)),
+            GraphSource::Snapshot(path) => {
+                Ok((analysis.configure(opt, DependencyGraph::load(path, &root)?)?, root, None))
+            }
+            GraphSource::Revision(revision) => {
+                let (analysis, worktree) = revision_analysis(opt, analysis, revision)?;
+                let graph = analysis.scan_graph(opt)?;
+                Ok((graph, analysis.config.root, Some(worktree)))
+            }
+        }
+    }
+}
+
+/// Analysis settings for a temporary checkout of `revision`, read from the configuration file
+/// at the same place in that revision. The paths scanned are those of `analysis`, moved into
+/// the checkout, so both graphs cover the same directories.
+fn revision_analysis(opt: &Opt, analysis: &Analysis, revision: &str) -> std::io::Result<(Analysis, Worktree)> {
+    let config = &analysis.config;
+    let config_root = normalize_path(&absolute_path(&config.root)?);
+    let toplevel = git::toplevel(&config_root)?;
+    let worktree = Worktree::checkout(&toplevel, revision)?;
+    let in_worktree = |path: &Path| {
+        path.strip_prefix(&toplevel).map(|relative| worktree.path.join(relative)).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{} is outside the repository {}", path.display(), toplevel.display()),
+            )
+        })
+    };
+
+    let mut config = match config_path(opt)? {
+        Some(path) => {
+            let path = in_worktree(&normalize_path(&absolute_path(&path)?))?;
+            if !path.is_file() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("{} has no {}", revision, path.strip_prefix(&worktree.path).unwrap_or(&path).display()),
+                ));
+            }
+            config::load(&path)?
+        }
+        None => {
+            let mut config = PybiaConfig::default();
+            config.resolve_paths(&in_worktree(&config_root)?);
+            config
+        }
+    };
+    // A cache inside the temporary checkout would be discarded with it, and coverage data
+    // describes the working tree rather than the revision
+    config.cache.enabled = false;
+    config.tests.coverage = None;
+    let paths = analysis.paths.iter()
+        .map(|path| in_worktree(&normalize_path(path)))
+        .collect::<std::io::Result<_>>()?;
+    Ok((Analysis::from_config(config, paths)?, worktree))
+}
+
+fn diff(opt: &Opt, base: GraphSource, head: GraphSource, format: DiffFormat) -> std::io::Result<()> {
+    let analysis = Analysis::prepare(opt)?;
+    let (base, base_root, _base_worktree) = base.build(opt, &analysis)?;
+    let (head, head_root, _head_worktree) = head.build(opt, &analysis)?;
+    print!("{}", GraphDiff::between(&base, &base_root, &head, &head_root).render(format));
+    Ok(())
+}
 
-    // Detect services
-    let detector = ServiceDetector::new(service_configs);
-    let services = detector.detect_services(&opt.paths[0])?;
+fn snapshot(opt: &Opt, output: &Path) -> std::io::Result<()> {
+    let analysis = Analysis::prepare(opt)?;
+    let graph = analysis.build_graph(opt)?;
+    graph.save(output, &analysis.config.root)?;
+    eprintln!("Saved the dependency graph of {} service(s) to {}", graph.services().len(), output.display());
+    Ok(())
+}
+
+/// Validate the config file, returning whether it passed
+fn validate(opt: &Opt, deny_warnings: bool) -> std::io::Result<bool> {
+    let path = config_path(opt)?.ok_or_else(|| std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        "No configuration file found; pass --config or create pybia.toml",
+    ))?;
+
+    let diagnostics = config::validate::validate_file(&path)?;
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic.render(&path));
+    }
+
+    let errors = diagnostics.iter().filter(|d| d.severity == Severity::Error).count();
+    let warnings = diagnostics.len() - errors;
+    eprintln!("{}: {} error(s), {} warning(s)", path.display(), errors, warnings);
+    Ok(errors == 0 && (!deny_warnings || warnings == 0))
+}
 
-    let mut watcher = FileWatcher::new(&opt.paths, services)?;
-    watcher.watch(&opt.paths)?;
+fn init(root: Option<PathBuf>, output: Option<PathBuf>, force: bool) -> std::io::Result<()> {
+    let root = match root {
+        Some(root) => root,
+        None => std::env::current_dir()?,
+    };
+    let output = output.unwrap_or_else(|| root.join(config::CONFIG_FILE_NAME));
+    if output.exists() && !force {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("{} already exists; pass --force to overwrite it", output.display()),
+        ));
+    }
+
+    let defaults: Vec<String> = config::init::DEFAULT_IGNORE.iter().map(|p| p.to_string()).collect();
+    let ignore = PathPatterns::new(&root, &defaults).map_err(invalid_config)?;
+    let detection = ServiceDetector::new(Vec::new())
+        .with_ignore(ignore)
+        .detect(&root)?;
+
+    println!("Detected {} service(s):", detection.services.len());
+    print!("{}", config::init::render_summary(&detection, &root));
+    for collision in &detection.collisions {
+        eprintln!("Warning: {} (resolved as: {})", collision, collision.resolved_names.join(", "));
+    }
 
-    println!("Watching paths: {:?}", opt.paths);
+    let config_root = output.parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+    std::fs::write(&output, config::init::render_config(&detection, &config_root))?;
+    println!("Wrote {}", output.display());
+    Ok(())
+}
+
+fn run() -> Result<()> {
+    let opt = Opt::from_args();
+
+    match &opt.cmd {
+        Some(Command::Validate { deny_warnings }) => {
+            if !validate(&opt, *deny_warnings)? {
+                process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Explain { files }) => {
+            return Ok(explain(&opt, files)?);
+        }
+        Some(Command::Cycles { level, baseline, write_baseline }) => {
+            if !cycles(&opt, *level, baseline.as_deref(), *write_baseline)? {
+                process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Graph { format, granularity, service, first_party_only, depth, changed, output }) => {
+            let options = ExportOptions::new(*granularity)
+                .with_service(service.clone())
+                .with_first_party_only(*first_party_only)
+                .with_max_depth(*depth)
+                .with_changed(changed.clone());
+            return Ok(graph(&opt, *format, options, output.as_deref())?);
+        }
+        Some(Command::Check) => {
+            if !check(&opt)? {
+                process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::DepsCheck { format }) => {
+            if !deps_check(&opt, *format)? {
+                process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Tests { files, since, format }) => {
+            return Ok(tests(&opt, files, since.as_deref(), *format)?);
+        }
+        Some(Command::Versions { format }) => {
+            if !versions(&opt, *format)? {
+                process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Diff { base, base_graph, head, head_graph, format }) => {
+            let base = GraphSource::new(base, base_graph);
+            let head = GraphSource::new(head, head_graph);
+            return Ok(diff(&opt, base, head, *format)?);
+        }
+        Some(Command::Snapshot { output }) => {
+            return Ok(snapshot(&opt, output)?);
+        }
+        Some(Command::Init { root, output, force }) => {
+            return Ok(init(root.clone(), output.clone(), *force)?);
+        }
+        None => {}
+    }
+
+    let analysis = Analysis::prepare(&opt)?;
+    let service_format = opt.service_format.unwrap_or(analysis.config.output.service_format);
+    let paths = analysis.paths.clone();
+    let graph = analysis.build_graph(&opt)?;
+
+    let runner = CommandRunner::new(opt.command_policy.unwrap_or(analysis.config.watcher.command_policy))
+        .with_max_parallel(opt.max_parallel.unwrap_or(analysis.config.watcher.max_parallel));
+    let backend = opt.backend.unwrap_or(analysis.config.watcher.backend);
+    let poll_interval = Duration::from_millis(opt.poll_interval_ms.unwrap_or(analysis.config.watcher.poll_interval_ms));
+    let mut watcher = FileWatcher::from_dependency_graph(graph)?
+        .with_backend(backend, poll_interval)?
+        .with_debounce(Duration::from_millis(analysis.config.watcher.debounce_ms))
+        .with_run_mode(opt.run_mode.unwrap_or(analysis.config.watcher.run_mode))
+        .with_command_runner(runner)
+        .with_service_detector(analysis.service_detector(&opt), paths[0].clone());
+    watcher.watch(&paths)?;
+
+    println!("Watching paths: {:?}", paths);
     println!("Will run command: {:?}", opt.command);
     println!("Listening! Ctrl-C to quit.");
 
-    loop {
-        if let Err(e) = watcher.handle_events(&opt.command) {
-            eprintln!("Error handling events: {}", e);
+    let shutdown = Arc::new(AtomicBool::new(false));
+    for signal in [SIGINT, SIGTERM] {
+        signal_hook::flag::register(signal, Arc::clone(&shutdown))?;
+    }
+
+    while !shutdown.load(Ordering::Relaxed) {
+        match watcher.handle_events(&opt.command) {
+            Ok(false) => continue,
+            Ok(true) => {}
+            Err(e) => {
+                eprintln!("Error handling events: {}", e);
+                continue;
+            }
         }
 
         // Print affected services
         for (name, path) in watcher.get_affected_services() {
-            match opt.service_format {
+            match service_format {
                 ServiceFormat::Name => println!("{}", name),
                 ServiceFormat::NamePath => println!("{},{}", name, path.display()),
             }
         }
-
-        std::thread::sleep(std::time::Duration::from_millis(100));
     }
+
+    println!("Stopping running commands");
+    watcher.shutdown();
+    Ok(())
 }
 
 fn main() {
@@ -8,10 +8,10 @@ use toml;
 
 lazy_static! {
     static ref REQUIREMENT_REGEX: Regex = Regex::new(concat!(
-        r"^(?P<name>[A-Za-z0-9][A-Za-z0-9._-]*[A-Za-z0-9])",
-        r"(?:\[(?P<extras>[^\]]+)\])?",
-        r"(?P<constraint>(?:<=?|>=?|==|!=|~=|===)[^;,\s]*)",
-        r"(?:\s*;\s*(?P<marker>.*))?$"
+        r"^(?P<name>[A-Za-z0-9](?:[A-Za-z0-9._-]*[A-Za-z0-9])?)",
+        r"\s*(?:\[(?P<extras>[^\]]+)\])?",
+        r"\s*(?P<constraint>(?:<=?|>=?|==|!=|~=|===)[^;]*?)?",
+        r"\s*(?:;\s*(?P<marker>.*))?$"
     )).unwrap();
 }
 
@@ -20,24 +20,29 @@ pub struct DependencyParser;
 impl DependencyParser {
     pub fn parse_file(path: &Path) -> std::io::Result<DependencyFile> {
         let content = fs::read_to_string(path)?;
+        Ok(Self::parse_content(path, &content))
+    }
+
+    /// Parse the already-read `content` of the dependency file at `path`
+    pub fn parse_content(path: &Path, content: &str) -> DependencyFile {
         let kind = Self::determine_file_kind(path);
         
         let dependencies = match kind {
             DependencyFileKind::RequirementsTxt | DependencyFileKind::RequirementsIn => {
-                Self::parse_requirements(&content)
+                Self::parse_requirements(content)
             }
-            DependencyFileKind::SetupPy => Self::parse_setup_py(&content),
+            DependencyFileKind::SetupPy => Self::parse_setup_py(content),
             DependencyFileKind::PyprojectToml | DependencyFileKind::Poetry => {
-                Self::parse_pyproject_toml(&content)
+                Self::parse_pyproject_toml(content)
             }
-            DependencyFileKind::Pipfile => Self::parse_pipfile(&content),
+            DependencyFileKind::Pipfile => Self::parse_pipfile(content),
         };
 
-        Ok(DependencyFile {
+        DependencyFile {
             path: path.to_path_buf(),
             kind,
             dependencies,
-        })
+        }
     }
 
     fn determine_file_kind(path: &Path) -> DependencyFileKind {
@@ -73,12 +78,15 @@ impl DependencyParser {
     }
 
     fn parse_requirement_line(line: &str) -> Option<PackageRequirement> {
+        let line = line.split(" #").next().unwrap_or(line).trim();
         let captures = REQUIREMENT_REGEX.captures(line)?;
         
         Some(PackageRequirement {
             name: captures.name("name")?.as_str().to_string(),
             version_req: captures.name("constraint")
                 .and_then(|c| VersionReq::parse(c.as_str()).ok()),
+            specifier: captures.name("constraint")
+                .map(|c| c.as_str().trim().to_string()),
             extras: captures.name("extras")
                 .map(|e| e.as_str().split(',').map(|s| s.trim().to_string()).collect())
                 .unwrap_or_default(),
@@ -120,6 +128,35 @@ impl DependencyParser {
                 }
             }
             
+            // Optional dependency groups are declared too
+            if let Some(groups) = toml.get("project")
+                .and_then(|p| p.get("optional-dependencies"))
+                .and_then(|d| d.as_table()) {
+                for dep in groups.values().filter_map(|g| g.as_array()).flatten() {
+                    if let Some(req) = dep.as_str().and_then(Self::parse_requirement_line) {
+                        requirements.push(req);
+                    }
+                }
+            }
+
+            if let Some(poetry) = toml.get("tool").and_then(|t| t.get("poetry")) {
+                let groups = poetry.get("group")
+                    .and_then(|g| g.as_table())
+                    .into_iter()
+                    .flat_map(|groups| groups.values())
+                    .filter_map(|group| group.get("dependencies"));
+                let tables = [poetry.get("dependencies"), poetry.get("dev-dependencies")]
+                    .into_iter()
+                    .flatten()
+                    .chain(groups)
+                    .filter_map(|d| d.as_table());
+                for (name, constraint) in tables.flatten() {
+                    if name != "python" {
+                        requirements.push(Self::poetry_requirement(name, constraint));
+                    }
+                }
+            }
+
             // Check project dependencies table format
             if let Some(deps) = toml.get("project")
                 .and_then(|p| p.get("dependencies"))
@@ -137,6 +174,29 @@ impl DependencyParser {
         requirements
     }
 
+    /// A Poetry or Pipfile dependency, given as a version string or a table with a `version` key
+    fn poetry_requirement(name: &str, constraint: &toml::Value) -> PackageRequirement {
+        let (version, extras) = match constraint {
+            toml::Value::String(v) => (Some(v.as_str()), Vec::new()),
+            toml::Value::Table(t) => (
+                t.get("version").and_then(|v| v.as_str()),
+                t.get("extras")
+                    .and_then(|e| e.as_array())
+                    .map(|e| e.iter().filter_map(|x| x.as_str().map(str::to_string)).collect())
+                    .unwrap_or_default(),
+            ),
+            _ => (None, Vec::new()),
+        };
+        let version = version.map(str::trim).filter(|v| !v.is_empty() && *v != "*");
+        PackageRequirement {
+            name: name.to_string(),
+            version_req: version.and_then(|v| VersionReq::parse(v).ok()),
+            specifier: version.map(str::to_string),
+            extras,
+            marker: None,
+        }
+    }
+
     fn parse_pipfile(content: &str) -> Vec<PackageRequirement> {
         let mut requirements = Vec::new();
         
@@ -145,24 +205,7 @@ impl DependencyParser {
             for section in ["packages", "dev-packages"] {
                 if let Some(deps) = toml.get(section).and_then(|p| p.as_table()) {
                     for (name, constraint) in deps {
-                        let version_req = match constraint {
-                            toml::Value::String(v) => Some(v.as_str()),
-                            toml::Value::Table(t) => t.get("version").and_then(|v| v.as_str()),
-                            _ => None,
-                        };
-
-                        if let Some(version) = version_req {
-                            if let Some(req) = Self::parse_requirement_line(&format!("{}=={}", name, version)) {
-                                requirements.push(req);
-                            }
-                        } else {
-                            requirements.push(PackageRequirement {
-                                name: name.clone(),
-                                version_req: None,
-                                extras: Vec::new(),
-                                marker: None,
-                            });
-                        }
+                        requirements.push(Self::poetry_requirement(name, constraint));
                     }
                 }
             }
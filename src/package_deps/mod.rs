@@ -1,12 +1,16 @@
 pub mod models;
 pub mod import_parser;
 pub mod dep_parser;
+pub mod names;
+pub mod specifier;
+pub mod stdlib;
 
 use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use models::{DependencyFile, ImportInfo};
 use import_parser::ImportParser;
 use dep_parser::DependencyParser;
+use crate::utils::PathPatterns;
 
 #[derive(Debug)]
 pub struct PackageDependencyManager {
@@ -16,6 +20,8 @@ pub struct PackageDependencyManager {
     dependency_files: HashMap<PathBuf, DependencyFile>,
     // Cache of parsed imports by file
     import_cache: HashMap<PathBuf, Vec<ImportInfo>>,
+    // Paths excluded from scanning
+    ignore: PathPatterns,
 }
 
 impl Default for PackageDependencyManager {
@@ -30,12 +36,20 @@ impl PackageDependencyManager {
             package_usages: HashMap::new(),
             dependency_files: HashMap::new(),
             import_cache: HashMap::new(),
+            ignore: PathPatterns::default(),
         }
     }
 
+    pub fn with_ignore(mut self, ignore: PathPatterns) -> Self {
+        self.ignore = ignore;
+        self
+    }
+
     pub fn scan_directory(&mut self, dir: &Path) -> std::io::Result<()> {
+        let ignore = self.ignore.clone();
         for entry in walkdir::WalkDir::new(dir)
             .into_iter()
+            .filter_entry(|e| !ignore.matches(e.path()))
             .filter_map(|e| e.ok())
         {
             let path = entry.path();
@@ -55,8 +69,12 @@ impl PackageDependencyManager {
 
     pub fn scan_python_file(&mut self, path: &Path, content: &str) -> std::io::Result<()> {
         let mut parser = ImportParser::new(content.to_string());
-        let imports = parser.parse_imports();
-        
+        self.add_imports(path, parser.parse_imports());
+        Ok(())
+    }
+
+    /// Record already-parsed imports of the Python file at `path`
+    pub fn add_imports(&mut self, path: &Path, imports: Vec<ImportInfo>) {
         for import_info in &imports {
             self.package_usages
                 .entry(import_info.package_name.clone())
@@ -65,16 +83,64 @@ impl PackageDependencyManager {
         }
         
         self.import_cache.insert(path.to_path_buf(), imports);
+    }
+
+    /// Record an already-parsed dependency file
+    pub fn add_dependency_file(&mut self, dep_file: DependencyFile) {
+        self.dependency_files.insert(dep_file.path.clone(), dep_file);
+    }
+
+    /// Re-parse a created or modified file
+    pub fn update_file(&mut self, path: &Path) -> std::io::Result<()> {
+        self.remove_file(path);
+        if self.ignore.matches(path) {
+            return Ok(());
+        }
+        if self.is_dependency_file(path) {
+            if let Ok(dep_file) = DependencyParser::parse_file(path) {
+                self.dependency_files.insert(path.to_path_buf(), dep_file);
+            }
+        } else if self.is_python_file(path) {
+            let content = std::fs::read_to_string(path)?;
+            self.scan_python_file(path, &content)?;
+        }
         Ok(())
     }
 
+    /// Forget everything known about a deleted file
+    pub fn remove_file(&mut self, path: &Path) {
+        self.dependency_files.remove(path);
+        if let Some(imports) = self.import_cache.remove(path) {
+            for import_info in imports {
+                if let Some(files) = self.package_usages.get_mut(&import_info.package_name) {
+                    files.remove(path);
+                    if files.is_empty() {
+                        self.package_usages.remove(&import_info.package_name);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Parsed imports of every known Python file
+    pub fn imports(&self) -> &HashMap<PathBuf, Vec<ImportInfo>> {
+        &self.import_cache
+    }
+
+    /// Parsed contents of every known dependency file
+    pub fn dependency_files(&self) -> &HashMap<PathBuf, DependencyFile> {
+        &self.dependency_files
+    }
+
     pub fn get_affected_by_dependency_change(&self, changed_file: &Path) -> HashSet<PathBuf> {
         let mut affected = HashSet::new();
         
         if let Some(dep_file) = self.dependency_files.get(changed_file) {
             for req in &dep_file.dependencies {
-                if let Some(files) = self.package_usages.get(&req.name) {
-                    affected.extend(files.iter().cloned());
+                for module in names::import_names(&req.name, &Default::default()) {
+                    if let Some(files) = self.package_usages.get(&module) {
+                        affected.extend(files.iter().cloned());
+                    }
                 }
             }
         }
@@ -83,12 +149,7 @@ impl PackageDependencyManager {
     }
 
     pub fn is_dependency_file(&self, path: &Path) -> bool {
-        matches!(path.file_name().and_then(|n| n.to_str()),
-            Some("requirements.txt") |
-            Some("requirements.in") |
-            Some("setup.py") |
-            Some("pyproject.toml") |
-            Some("Pipfile"))
+        is_dependency_file(path)
     }
 
     pub fn is_python_file(&self, path: &Path) -> bool {
@@ -96,5 +157,14 @@ impl PackageDependencyManager {
     }
 }
 
+pub fn is_dependency_file(path: &Path) -> bool {
+    matches!(path.file_name().and_then(|n| n.to_str()),
+        Some("requirements.txt") |
+        Some("requirements.in") |
+        Some("setup.py") |
+        Some("pyproject.toml") |
+        Some("Pipfile"))
+}
+
 #[cfg(test)]
 mod tests; 
\ No newline at end of file
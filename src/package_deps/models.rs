@@ -1,22 +1,26 @@
 use semver::{VersionReq};
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct PackageRequirement {
     pub name: String,
     pub version_req: Option<VersionReq>,
+    /// The version constraint as written, e.g. `>=2.28,<3` or Poetry's `^2.28`
+    #[serde(default)]
+    pub specifier: Option<String>,
     pub extras: Vec<String>,
     pub marker: Option<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DependencyFile {
     pub path: PathBuf,
     pub kind: DependencyFileKind,
     pub dependencies: Vec<PackageRequirement>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum DependencyFileKind {
     RequirementsTxt,
     RequirementsIn,
@@ -26,7 +30,7 @@ pub enum DependencyFileKind {
     Pipfile,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImportInfo {
     pub package_name: String,
     pub module_path: Vec<String>,
@@ -0,0 +1,82 @@
+use std::collections::BTreeMap;
+
+/// Distributions whose top-level modules are not simply their normalized name with
+/// `-` replaced by `_`, keyed by normalized distribution name
+const KNOWN_IMPORT_NAMES: &[(&str, &[&str])] = &[
+    ("attrs", &["attr", "attrs"]),
+    ("beautifulsoup4", &["bs4"]),
+    ("discord-py", &["discord"]),
+    ("django-rest-framework", &["rest_framework"]),
+    ("djangorestframework", &["rest_framework"]),
+    ("dnspython", &["dns"]),
+    ("elasticsearch-dsl", &["elasticsearch_dsl"]),
+    ("email-validator", &["email_validator"]),
+    ("faiss-cpu", &["faiss"]),
+    ("google-api-python-client", &["googleapiclient"]),
+    ("google-cloud-bigquery", &["google"]),
+    ("google-cloud-pubsub", &["google"]),
+    ("google-cloud-storage", &["google"]),
+    ("grpcio", &["grpc"]),
+    ("grpcio-tools", &["grpc_tools"]),
+    ("ipython", &["IPython"]),
+    ("msgpack-python", &["msgpack"]),
+    ("mysql-connector-python", &["mysql"]),
+    ("mysqlclient", &["MySQLdb"]),
+    ("opencv-contrib-python", &["cv2"]),
+    ("opencv-python", &["cv2"]),
+    ("opencv-python-headless", &["cv2"]),
+    ("pillow", &["PIL"]),
+    ("protobuf", &["google"]),
+    ("psycopg", &["psycopg"]),
+    ("psycopg2-binary", &["psycopg2"]),
+    ("pycryptodome", &["Crypto"]),
+    ("pycryptodomex", &["Cryptodome"]),
+    ("pyjwt", &["jwt"]),
+    ("pymupdf", &["fitz"]),
+    ("pyopenssl", &["OpenSSL"]),
+    ("python-dateutil", &["dateutil"]),
+    ("python-dotenv", &["dotenv"]),
+    ("python-jose", &["jose"]),
+    ("python-magic", &["magic"]),
+    ("python-multipart", &["multipart"]),
+    ("python-slugify", &["slugify"]),
+    ("pytz", &["pytz"]),
+    ("pyyaml", &["yaml"]),
+    ("pyzmq", &["zmq"]),
+    ("scikit-image", &["skimage"]),
+    ("scikit-learn", &["sklearn"]),
+    ("setuptools", &["setuptools", "pkg_resources"]),
+    ("typing-extensions", &["typing_extensions"]),
+];
+
+/// The PEP 503 normalized form of a distribution name: lowercase, with runs of `-`, `_`
+/// and `.` replaced by a single `-`
+pub fn normalize_name(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len());
+    let mut separator = false;
+    for c in name.chars() {
+        if matches!(c, '-' | '_' | '.') {
+            separator = true;
+            continue;
+        }
+        if separator && !normalized.is_empty() {
+            normalized.push('-');
+        }
+        separator = false;
+        normalized.extend(c.to_lowercase());
+    }
+    normalized
+}
+
+/// Top-level modules `distribution` can be imported as, from `overrides` (keyed by
+/// normalized name), the built-in table, or its name with `-` replaced by `_`
+pub fn import_names(distribution: &str, overrides: &BTreeMap<String, Vec<String>>) -> Vec<String> {
+    let normalized = normalize_name(distribution);
+    if let Some(names) = overrides.get(&normalized) {
+        return names.clone();
+    }
+    match KNOWN_IMPORT_NAMES.binary_search_by_key(&normalized.as_str(), |(name, _)| name) {
+        Ok(index) => KNOWN_IMPORT_NAMES[index].1.iter().map(|name| name.to_string()).collect(),
+        Err(_) => vec![normalized.replace('-', "_")],
+    }
+}
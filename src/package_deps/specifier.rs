@@ -0,0 +1,231 @@
+use std::cmp::Ordering;
+
+/// A release version such as `2.31.0`. Pre-, post-, dev-release and local segments are
+/// ignored, and missing components count as zero, so `1.2` equals `1.2.0`.
+#[derive(Debug, Clone)]
+pub struct Version(Vec<u64>);
+
+impl std::str::FromStr for Version {
+    type Err = String;
+
+    /// Parse the release segment of a PEP 440 version, after any epoch
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().trim_start_matches(['v', 'V']);
+        let s = s.split_once('!').map_or(s, |(_, release)| release);
+        let s = s.split('+').next().unwrap_or(s);
+        let mut release = Vec::new();
+        for component in s.split('.') {
+            let digits: String = component.chars().take_while(char::is_ascii_digit).collect();
+            let Ok(number) = digits.parse() else {
+                break;
+            };
+            release.push(number);
+            if digits.len() < component.len() {
+                // A pre- or post-release suffix such as `0rc1` ends the release segment
+                break;
+            }
+        }
+        if release.is_empty() {
+            return Err(format!("invalid version '{}'", s));
+        }
+        Ok(Version(release))
+    }
+}
+
+impl Version {
+    fn component(&self, index: usize) -> u64 {
+        self.0.get(index).copied().unwrap_or(0)
+    }
+
+    /// The smallest version above every version starting with the first `len` components,
+    /// e.g. `1.3` for the prefix `1.2` of `1.2.5`
+    fn next_prefix(&self, len: usize) -> Version {
+        let len = len.max(1);
+        let mut release: Vec<u64> = (0..len).map(|i| self.component(i)).collect();
+        release[len - 1] += 1;
+        Version(release)
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let len = self.0.len().max(other.0.len());
+        (0..len)
+            .map(|i| self.component(i).cmp(&other.component(i)))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+/// One end of a version range, with whether the version itself is included
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Bound {
+    version: Version,
+    inclusive: bool,
+}
+
+/// The versions a set of specifiers allows: an interval less some excluded versions
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VersionRange {
+    lower: Option<Bound>,
+    upper: Option<Bound>,
+    excluded: Vec<Version>,
+}
+
+impl VersionRange {
+    fn at_least(version: Version, inclusive: bool) -> Self {
+        Self { lower: Some(Bound { version, inclusive }), ..Default::default() }
+    }
+
+    fn below(version: Version, inclusive: bool) -> Self {
+        Self { upper: Some(Bound { version, inclusive }), ..Default::default() }
+    }
+
+    fn between(lower: Version, upper: Version) -> Self {
+        Self {
+            lower: Some(Bound { version: lower, inclusive: true }),
+            upper: Some(Bound { version: upper, inclusive: false }),
+            excluded: Vec::new(),
+        }
+    }
+
+    fn exactly(version: Version) -> Self {
+        Self {
+            lower: Some(Bound { version: version.clone(), inclusive: true }),
+            upper: Some(Bound { version, inclusive: true }),
+            excluded: Vec::new(),
+        }
+    }
+
+    /// The versions both ranges allow
+    pub fn intersect(&self, other: &VersionRange) -> VersionRange {
+        let tighter = |a: &Option<Bound>, b: &Option<Bound>, keep_greater: bool| match (a, b) {
+            (Some(a), Some(b)) => {
+                let a_wins = match a.version.cmp(&b.version) {
+                    Ordering::Greater => keep_greater,
+                    Ordering::Less => !keep_greater,
+                    // Of two bounds at the same version, the exclusive one is tighter on either side
+                    Ordering::Equal => !a.inclusive || b.inclusive,
+                };
+                Some(if a_wins { a.clone() } else { b.clone() })
+            }
+            (a, b) => a.clone().or_else(|| b.clone()),
+        };
+        let mut excluded = self.excluded.clone();
+        excluded.extend(other.excluded.iter().cloned());
+        excluded.sort();
+        excluded.dedup();
+        VersionRange {
+            lower: tighter(&self.lower, &other.lower, true),
+            upper: tighter(&self.upper, &other.upper, false),
+            excluded,
+        }
+    }
+
+    /// Whether no version satisfies the range
+    pub fn is_empty(&self) -> bool {
+        let (Some(lower), Some(upper)) = (&self.lower, &self.upper) else {
+            return false;
+        };
+        match lower.version.cmp(&upper.version) {
+            Ordering::Greater => true,
+            Ordering::Equal => {
+                !(lower.inclusive && upper.inclusive) || self.excluded.contains(&lower.version)
+            }
+            Ordering::Less => false,
+        }
+    }
+
+    pub fn contains(&self, version: &Version) -> bool {
+        let above = self.lower.as_ref().is_none_or(|b| {
+            if b.inclusive { *version >= b.version } else { *version > b.version }
+        });
+        let below = self.upper.as_ref().is_none_or(|b| {
+            if b.inclusive { *version <= b.version } else { *version < b.version }
+        });
+        above && below && !self.excluded.contains(version)
+    }
+}
+
+/// A comma-separated set of version specifiers such as `>=2.28,<3`.
+///
+/// PEP 440 operators are supported along with Poetry's `^`, `~` and bare versions, so
+/// constraints from requirements files and `[tool.poetry]` tables compare. Wildcards in `!=`
+/// are ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpecifierSet {
+    range: VersionRange,
+}
+
+impl SpecifierSet {
+    pub fn range(&self) -> &VersionRange {
+        &self.range
+    }
+
+    pub fn contains(&self, version: &Version) -> bool {
+        self.range.contains(version)
+    }
+
+    /// Whether some version satisfies every set
+    pub fn compatible<'a>(sets: impl IntoIterator<Item = &'a SpecifierSet>) -> bool {
+        !sets.into_iter()
+            .fold(VersionRange::default(), |range, set| range.intersect(&set.range))
+            .is_empty()
+    }
+
+    fn parse_clause(clause: &str) -> Result<VersionRange, String> {
+        let operator_len = clause.find(|c: char| !"<>=!~^".contains(c)).unwrap_or(clause.len());
+        let (operator, version) = clause.split_at(operator_len);
+        let version = version.trim();
+        let wildcard = version.ends_with(".*");
+        let parsed = version.trim_end_matches(".*").parse::<Version>()?;
+        let components = version.trim_end_matches(".*").split('.').count();
+        Ok(match (operator, wildcard) {
+            ("==" | "===" | "", false) => VersionRange::exactly(parsed),
+            ("==" | "", true) => VersionRange::between(parsed.clone(), parsed.next_prefix(components)),
+            ("!=", false) => VersionRange { excluded: vec![parsed], ..Default::default() },
+            ("!=", true) => VersionRange::default(),
+            (">=", false) => VersionRange::at_least(parsed, true),
+            (">", false) => VersionRange::at_least(parsed, false),
+            ("<=", false) => VersionRange::below(parsed, true),
+            ("<", false) => VersionRange::below(parsed, false),
+            ("~=", false) if components >= 2 => {
+                VersionRange::between(parsed.clone(), parsed.next_prefix(components - 1))
+            }
+            ("^", false) => {
+                let first_nonzero = (0..components).find(|&i| parsed.component(i) != 0).unwrap_or(components - 1);
+                VersionRange::between(parsed.clone(), parsed.next_prefix(first_nonzero + 1))
+            }
+            ("~", false) => {
+                VersionRange::between(parsed.clone(), parsed.next_prefix(if components == 1 { 1 } else { 2 }))
+            }
+            _ => return Err(format!("invalid version specifier '{}'", clause)),
+        })
+    }
+}
+
+impl std::str::FromStr for SpecifierSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut range = VersionRange::default();
+        for clause in s.split(',').map(str::trim).filter(|c| !c.is_empty() && *c != "*") {
+            range = range.intersect(&Self::parse_clause(clause)?);
+        }
+        Ok(Self { range })
+    }
+}
@@ -89,4 +89,97 @@ fn test_import_parsing() {
     assert!(imports.iter().any(|i| i.package_name == "os"));
     assert!(imports.iter().any(|i| i.package_name == "sys"));
     assert!(imports.iter().any(|i| i.package_name == "typing"));
-} 
\ No newline at end of file
+} 
+
+#[test]
+fn test_stdlib_modules() {
+    use super::stdlib::is_stdlib_module;
+    assert!(is_stdlib_module("os"));
+    assert!(is_stdlib_module("os.path"));
+    assert!(is_stdlib_module("__future__"));
+    assert!(is_stdlib_module("cProfile"));
+    assert!(!is_stdlib_module("requests"));
+    assert!(!is_stdlib_module("osmium"));
+}
+
+#[test]
+fn test_requirement_forms_and_poetry_groups() {
+    use super::dep_parser::DependencyParser;
+    let names = |path: &str, content: &str| -> Vec<String> {
+        DependencyParser::parse_content(Path::new(path), content)
+            .dependencies
+            .into_iter()
+            .map(|r| r.name)
+            .collect()
+    };
+
+    assert_eq!(
+        names("requirements.txt", "pytest\nrequests >= 2.0, < 3  # pinned below 3\nuvicorn[standard]>=0.20; python_version >= '3.8'\n-r base.txt\n"),
+        vec!["pytest", "requests", "uvicorn"],
+    );
+    assert_eq!(
+        names("pyproject.toml", r#"
+[project]
+dependencies = ["httpx"]
+[project.optional-dependencies]
+test = ["pytest-cov>=4"]
+"#),
+        vec!["httpx", "pytest-cov"],
+    );
+    assert_eq!(
+        names("pyproject.toml", r#"
+[tool.poetry.dependencies]
+python = "^3.11"
+PyYAML = "^6.0"
+[tool.poetry.group.dev.dependencies]
+mypy = { version = "*" }
+"#),
+        vec!["PyYAML", "mypy"],
+    );
+}
+
+#[test]
+fn test_distribution_import_names() {
+    use super::names::{import_names, normalize_name};
+    use std::collections::BTreeMap;
+    assert_eq!(normalize_name("Foo.Bar__baz"), "foo-bar-baz");
+    assert_eq!(import_names("PyYAML", &BTreeMap::new()), vec!["yaml"]);
+    assert_eq!(import_names("typing_extensions", &BTreeMap::new()), vec!["typing_extensions"]);
+    assert_eq!(import_names("python-dateutil", &BTreeMap::new()), vec!["dateutil"]);
+    let overrides = BTreeMap::from([("my-dist".to_string(), vec!["mine".to_string()])]);
+    assert_eq!(import_names("My_Dist", &overrides), vec!["mine"]);
+}
+
+#[test]
+fn test_version_specifiers() {
+    use specifier::{SpecifierSet, Version};
+    let set = |s: &str| s.parse::<SpecifierSet>().unwrap();
+    let version = |s: &str| s.parse::<Version>().unwrap();
+
+    assert_eq!(version("1.2"), version("1.2.0"));
+    assert!(version("2.0rc1") < version("2.1"));
+    assert!(set(">=2.28, <3").contains(&version("2.31.0")));
+    assert!(!set(">=2.28,<3").contains(&version("3.0")));
+    assert!(set("~=1.4.2").contains(&version("1.4.9")));
+    assert!(!set("~=1.4.2").contains(&version("1.5")));
+    assert!(set("==1.4.*").contains(&version("1.4.7")));
+    assert!(!set("!=1.4.7").contains(&version("1.4.7")));
+
+    // Poetry constraints compare with their PEP 440 equivalents
+    assert_eq!(set("^2.28"), set(">=2.28,<3"));
+    assert_eq!(set("^0.3.1"), set(">=0.3.1,<0.4"));
+    assert_eq!(set("~1.2.3"), set(">=1.2.3,<1.3"));
+    assert_eq!(set("1.2.3"), set("==1.2.3"));
+    assert_eq!(set("*"), set(""));
+
+    assert!(SpecifierSet::compatible([&set(">=2.28"), &set("<3"), &set("!=2.29")]));
+    assert!(!SpecifierSet::compatible([&set("==2.28.0"), &set(">=2.31")]));
+    assert!(!SpecifierSet::compatible([&set("<2"), &set(">=2")]));
+    assert!(!SpecifierSet::compatible([&set("==2.0"), &set("!=2.0.0")]));
+    assert!(!SpecifierSet::compatible([&set("<3"), &set("==3")]));
+    assert!(!SpecifierSet::compatible([&set("<=3"), &set("<3"), &set("==3")]));
+    assert!(!SpecifierSet::compatible([&set(">3"), &set("==3")]));
+    assert!(!SpecifierSet::compatible([&set(">=3"), &set(">3"), &set("==3")]));
+    assert_eq!(set("<3").range().intersect(set("<=3").range()), *set("<3").range());
+    assert!("banana".parse::<SpecifierSet>().is_err());
+}
@@ -0,0 +1,182 @@
+#[cfg(test)]
+mod tests;
+
+use crate::cache::{content_hash, models::ParsedFile, AnalysisCache};
+use crate::package_deps::{dep_parser::DependencyParser, import_parser::ImportParser, is_dependency_file};
+use crate::utils::{is_python_file, PathPatterns};
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// A directory or file found while scanning
+#[derive(Debug, Clone)]
+pub struct ScanEntry {
+    pub path: PathBuf,
+    pub is_dir: bool,
+    /// Contents of service metadata files (`setup.py`, `pyproject.toml`), read while walking
+    pub content: Option<String>,
+    /// Imports and requirements, once the scan has been parsed
+    pub parsed: Option<ParsedFile>,
+}
+
+/// Every directory and file under a root, walked once and read at most once each
+#[derive(Debug, Clone)]
+pub struct RepositoryScan {
+    pub root: PathBuf,
+    /// Entries in walk order with children sorted by name, which is also `Path` order
+    pub entries: Vec<ScanEntry>,
+}
+
+pub fn is_metadata_file(path: &Path) -> bool {
+    matches!(path.file_name().and_then(|n| n.to_str()), Some("setup.py" | "pyproject.toml"))
+}
+
+impl RepositoryScan {
+    /// Walk `root`, skipping ignored paths, and read service metadata files in parallel
+    pub fn walk(root: &Path, ignore: &PathPatterns) -> Self {
+        let mut entries: Vec<ScanEntry> = WalkDir::new(root)
+            .follow_links(true)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|e| !ignore.matches(e.path()))
+            .filter_map(|e| e.ok())
+            .map(|entry| ScanEntry {
+                is_dir: entry.file_type().is_dir(),
+                path: entry.into_path(),
+                content: None,
+                parsed: None,
+            })
+            .collect();
+
+        entries.par_iter_mut()
+            .filter(|entry| !entry.is_dir && is_metadata_file(&entry.path))
+            .for_each(|entry| entry.content = std::fs::read_to_string(&entry.path).ok());
+
+        Self { root: root.to_path_buf(), entries }
+    }
+
+    /// Walk and parse `root` in one go
+    pub fn scan(root: &Path, ignore: &PathPatterns, cache: Option<&mut AnalysisCache>) -> Self {
+        let mut scan = Self::walk(root, ignore);
+        scan.parse(cache);
+        scan
+    }
+
+    /// Parse every Python and dependency file in parallel, reusing `cache` entries for
+    /// files whose contents are unchanged
+    pub fn parse(&mut self, cache: Option<&mut AnalysisCache>) {
+        let shared = cache.as_deref();
+        let results: Vec<(usize, String, ParsedFile, bool)> = self.entries.par_iter()
+            .enumerate()
+            .filter(|(_, entry)| !entry.is_dir && (is_python_file(&entry.path) || is_dependency_file(&entry.path)))
+            .filter_map(|(index, entry)| {
+                let read;
+                let content = match &entry.content {
+                    Some(content) => content,
+                    None => {
+                        read = std::fs::read_to_string(&entry.path).ok()?;
+                        &read
+                    }
+                };
+                let hash = content_hash(content.as_bytes());
+                match shared.and_then(|cache| cache.lookup(&entry.path, &hash)) {
+                    Some(parsed) => Some((index, hash, parsed.clone(), true)),
+                    None => Some((index, hash, parse_file(&entry.path, content), false)),
+                }
+            })
+            .collect();
+
+        let mut cache = cache;
+        for (index, hash, parsed, hit) in results {
+            if let Some(cache) = cache.as_deref_mut() {
+                cache.store(&self.entries[index].path, hash, parsed.clone(), hit);
+            }
+            self.entries[index].parsed = Some(parsed);
+        }
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &ScanEntry> {
+        self.entries.iter().filter(|entry| !entry.is_dir)
+    }
+
+    /// The entries at or below `dir`, in walk order
+    pub fn entries_under(&self, dir: &Path) -> &[ScanEntry] {
+        let start = self.entries.partition_point(|entry| entry.path.as_path() < dir);
+        let len = self.entries[start..].partition_point(|entry| entry.path.starts_with(dir));
+        &self.entries[start..start + len]
+    }
+
+    /// The contents of the file at `path`, from the scan when it was read while walking
+    pub fn read(&self, path: &Path) -> std::io::Result<String> {
+        let scanned = self.entries.binary_search_by(|entry| entry.path.as_path().cmp(path))
+            .ok()
+            .and_then(|index| self.entries[index].content.clone());
+        match scanned {
+            Some(content) => Ok(content),
+            None => std::fs::read_to_string(path),
+        }
+    }
+}
+
+/// Parse imports from a Python file and requirements from a dependency file
+pub fn parse_file(path: &Path, content: &str) -> ParsedFile {
+    let mut parsed = ParsedFile::default();
+    if is_python_file(path) {
+        parsed.modules = imported_modules(content);
+        parsed.imports = ImportParser::new(content.to_string()).parse_imports();
+    }
+    if is_dependency_file(path) {
+        parsed.dependency_file = Some(DependencyParser::parse_content(path, content));
+    }
+    parsed
+}
+
+/// Module names from the `import` and `from` statements at the start of lines, followed by
+/// the modules named in `pytest_plugins`
+fn imported_modules(content: &str) -> Vec<String> {
+    let mut modules: Vec<String> = import_statements(content).map(|(_, module, _)| module).collect();
+    modules.extend(pytest_plugins(content));
+    modules
+}
+
+/// Modules named by a top-level `pytest_plugins` assignment, which pytest imports as plugins;
+/// the value is a string or a list or tuple of strings, possibly over several lines
+pub fn pytest_plugins(content: &str) -> Vec<String> {
+    let Some(start) = content.lines().position(|line| {
+        line.strip_prefix("pytest_plugins")
+            .map(str::trim_start)
+            .is_some_and(|rest| rest.starts_with('=') && !rest.starts_with("=="))
+    }) else {
+        return Vec::new();
+    };
+
+    let mut value = String::new();
+    let mut depth = 0i32;
+    for line in content.lines().skip(start) {
+        let line = line.split(" #").next().unwrap_or(line);
+        value.push_str(line);
+        value.push('\n');
+        depth += line.matches(['[', '(']).count() as i32 - line.matches([']', ')']).count() as i32;
+        if depth <= 0 && !line.trim_end().ends_with('\\') {
+            break;
+        }
+    }
+    let value = &value[value.find('=').map_or(0, |i| i + 1)..];
+    value.split(['"', '\''])
+        .skip(1)
+        .step_by(2)
+        .filter(|module| !module.is_empty() && module.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// The 1-based line number, imported module and text of each top-level import statement
+pub fn import_statements(content: &str) -> impl Iterator<Item = (usize, String, &str)> {
+    content.lines()
+        .enumerate()
+        .filter(|(_, line)| line.starts_with("from ") || line.starts_with("import "))
+        .filter_map(|(index, line)| {
+            let module = line.split_whitespace().nth(1)?;
+            Some((index + 1, module.trim_end_matches(',').to_string(), line))
+        })
+}
@@ -0,0 +1,74 @@
+use super::*;
+use crate::test_support::write_file;
+
+#[test]
+fn test_scan_walks_and_parses_once() {
+    let temp = tempfile::tempdir().unwrap();
+    let root = temp.path();
+    write_file(root, "api/pyproject.toml", "[project]\nname = \"api\"\ndependencies = [\"requests>=2.0\"]\n");
+    write_file(root, "api/app/__init__.py", "");
+    write_file(root, "api/app/main.py", "import requests\nfrom app import models\n");
+    write_file(root, "api/app.txt", "");
+    write_file(root, "node_modules/x.py", "import os\n");
+
+    let ignore = PathPatterns::new(root, &["node_modules".to_string()]).unwrap();
+    let mut scan = RepositoryScan::walk(root, &ignore);
+
+    // Walk order is `Path` order, so entries can be looked up by path
+    let paths: Vec<&Path> = scan.entries.iter().map(|e| e.path.as_path()).collect();
+    let mut sorted = paths.clone();
+    sorted.sort();
+    assert_eq!(paths, sorted);
+    assert!(!paths.iter().any(|p| p.starts_with(root.join("node_modules"))));
+    assert_eq!(scan.entries_under(&root.join("api/app")).len(), 3);
+
+    // Only metadata files are read while walking
+    assert!(scan.read(&root.join("api/pyproject.toml")).unwrap().contains("requests"));
+    assert!(scan.files().filter(|e| e.content.is_some()).all(|e| is_metadata_file(&e.path)));
+
+    let mut cache = AnalysisCache::open(&root.join(".cache"));
+    scan.parse(Some(&mut cache));
+    let main = scan.files().find(|e| e.path.ends_with("main.py")).unwrap();
+    let parsed = main.parsed.as_ref().unwrap();
+    assert_eq!(parsed.modules, vec!["requests", "app"]);
+    assert_eq!(parsed.imports.len(), 2);
+    let pyproject = scan.files().find(|e| e.path.ends_with("pyproject.toml")).unwrap();
+    assert_eq!(pyproject.parsed.as_ref().unwrap().dependency_file.as_ref().unwrap().dependencies.len(), 1);
+    assert!(scan.files().find(|e| e.path.ends_with("app.txt")).unwrap().parsed.is_none());
+    assert_eq!(cache.stats(), (0, 3));
+
+    let mut rescan = RepositoryScan::walk(root, &ignore);
+    rescan.parse(Some(&mut cache));
+    assert_eq!(cache.stats(), (3, 3));
+}
+
+#[test]
+fn test_detection_from_scan_matches_walk() {
+    let temp = tempfile::tempdir().unwrap();
+    write_file(temp.path(), "services/api/setup.py", "from setuptools import setup\nsetup(\n    name='api',\n)\n");
+    write_file(temp.path(), "services/api/api/__init__.py", "");
+    write_file(temp.path(), "libs/core/pyproject.toml", "[project]\nname = \"core\"\n");
+
+    let detector = crate::service::detector::ServiceDetector::new(Vec::new());
+    let scan = RepositoryScan::walk(temp.path(), &PathPatterns::default());
+    let from_scan = detector.detect_in(&scan).unwrap();
+    let walked = detector.detect(temp.path()).unwrap();
+
+    let mut names: Vec<&String> = from_scan.services.keys().collect();
+    names.sort();
+    assert_eq!(names, ["api", "core"]);
+    assert_eq!(from_scan.services["api"].package_root, temp.path().join("services/api/api"));
+    assert_eq!(from_scan.services["api"].package_root, walked.services["api"].package_root);
+    assert_eq!(detector.fingerprint_in(&scan).unwrap(), detector.fingerprint(temp.path()).unwrap());
+}
+
+#[test]
+fn test_pytest_plugins_are_loaded_modules() {
+    assert_eq!(pytest_plugins("pytest_plugins = \"fixtures.db\"\n"), vec!["fixtures.db"]);
+    let content = "import pytest\n\npytest_plugins = [\n    \"fixtures.db\",  # database\n    'fixtures.http',\n]\n\ndef f(): pass\n";
+    assert_eq!(pytest_plugins(content), vec!["fixtures.db", "fixtures.http"]);
+    assert_eq!(imported_modules(content), vec!["pytest", "fixtures.db", "fixtures.http"]);
+    assert_eq!(pytest_plugins("pytest_plugins = (\"a\", \"b\")\n"), vec!["a", "b"]);
+    assert!(pytest_plugins("if pytest_plugins == 'x':\n    pass\n").is_empty());
+    assert!(pytest_plugins("    pytest_plugins = ['nested']\n").is_empty());
+}
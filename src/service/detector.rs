@@ -1,55 +1,350 @@
-use super::models::{DetectedService, ServiceConfig, ServiceDetectionType};
-use std::collections::HashMap;
+use super::models::{
+    DetectedService, NameCollisionPolicy, ServiceCollision, ServiceConfig, ServiceDetection,
+    ServiceDetectionRules, ServiceDetectionType,
+};
+use crate::cache::content_hash;
+use crate::scan::RepositoryScan;
+use crate::utils::{clean_path, normalize_path, relative_path_string, PathPatterns};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
 pub struct ServiceDetector {
     configs: Vec<ServiceConfig>,
+    collision_policy: NameCollisionPolicy,
+    ignore: PathPatterns,
 }
 
 impl ServiceDetector {
     pub fn new(configs: Vec<ServiceConfig>) -> Self {
-        Self { configs }
+        Self {
+            configs,
+            collision_policy: NameCollisionPolicy::default(),
+            ignore: PathPatterns::default(),
+        }
+    }
+
+    pub fn with_ignore(mut self, ignore: PathPatterns) -> Self {
+        self.ignore = ignore;
+        self
+    }
+
+    pub fn with_collision_policy(mut self, policy: NameCollisionPolicy) -> Self {
+        self.collision_policy = policy;
+        self
     }
 
     pub fn detect_services(&self, root_path: &Path) -> std::io::Result<HashMap<String, DetectedService>> {
-        let mut services = HashMap::new();
+        Ok(self.detect(root_path)?.services)
+    }
+
+    /// Detect services under `root_path`, reporting name collisions alongside the services
+    pub fn detect(&self, root_path: &Path) -> std::io::Result<ServiceDetection> {
+        self.detect_in(&RepositoryScan::walk(root_path, &self.ignore))
+    }
+
+    /// Detect services in an existing scan, reading metadata files from it rather than disk
+    pub fn detect_in(&self, scan: &RepositoryScan) -> std::io::Result<ServiceDetection> {
+        let root_path = scan.root.as_path();
 
         // First, add configured services
+        let mut configured = Vec::new();
         for config in &self.configs {
-            let service = DetectedService {
-                name: config.name.clone(),
-                root_path: config.path.clone(),
-                package_root: self.find_package_root(&config.path)?,
-                detection_type: ServiceDetectionType::ConfigurationDefined,
-            };
-            services.insert(service.name.clone(), service);
+            let services = self.expand_config_in(config, root_path, Some(scan))?;
+            configured.extend(services.into_iter().map(|s| (s, config.alias.clone())));
+        }
+
+        // Then detect additional services, one per root directory
+        let mut seen_roots: HashSet<PathBuf> = configured.iter()
+            .map(|(s, _)| normalize_path(&s.root_path))
+            .collect();
+        let mut detected = Vec::new();
+        for entry in scan.files() {
+            let path = entry.path.as_path();
+
+            // Skip if path is in an existing service
+            if self.is_path_in_existing_service(path, &detected) {
+                continue;
+            }
+
+            if let Some(service) = self.detect_service_at_path(path, Some(scan))? {
+                // A configured service at the same root acts as an alias for it
+                if seen_roots.insert(normalize_path(&service.root_path)) {
+                    detected.push(service);
+                }
+            }
         }
 
-        // Then detect additional services
-        for entry in WalkDir::new(root_path)
-            .follow_links(true)
+        self.resolve_collisions(root_path, configured, detected)
+    }
+
+    /// Whether `path` is named like a file that marks a service root: `setup.py`,
+    /// `pyproject.toml` or a configured indicator file
+    pub fn is_marker_file(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            return false;
+        };
+        matches!(name, "setup.py" | "pyproject.toml")
+            || self.configs.iter().any(|c| c.detection.indicator_files.iter().any(|f| f == name))
+    }
+
+    /// Whether a change at `path` can alter detection under `root_path`: a marker file, a
+    /// directory matched by a glob service path, or a directory holding a marker file
+    pub fn affects_detection(&self, path: &Path, root_path: &Path) -> bool {
+        if self.is_marker_file(path) {
+            return true;
+        }
+        if !path.is_dir() {
+            return false;
+        }
+        let matches_glob = self.configs.iter().any(|c| {
+            let pattern = if c.path.is_absolute() { c.path.clone() } else { root_path.join(&c.path) };
+            let pattern = pattern.to_string_lossy();
+            is_glob(&pattern) && glob::Pattern::new(&pattern).is_ok_and(|p| p.matches_path(path))
+        });
+        matches_glob || WalkDir::new(path)
             .into_iter()
+            .filter_entry(|e| !self.ignore.matches(e.path()))
             .filter_map(|e| e.ok())
-        {
-            let path = entry.path();
+            .any(|e| e.file_type().is_file() && self.is_marker_file(e.path()))
+    }
 
-            // Skip if path is in an existing service
-            if self.is_path_in_existing_service(path, &services) {
+    /// A digest of everything detection under `root_path` depends on: the detector settings,
+    /// the contents of marker files, the paths glob service paths match and the `__init__.py`
+    /// files that can decide a service's package root. Other files and directories are left
+    /// out, so unrelated churn keeps cached detection valid.
+    pub fn fingerprint(&self, root_path: &Path) -> std::io::Result<String> {
+        self.fingerprint_in(&RepositoryScan::walk(root_path, &self.ignore))
+    }
+
+    /// The fingerprint of detection in an existing scan
+    pub fn fingerprint_in(&self, scan: &RepositoryScan) -> std::io::Result<String> {
+        let config_paths: Vec<PathBuf> = self.configs.iter()
+            .map(|c| if c.path.is_absolute() { c.path.clone() } else { scan.root.join(&c.path) })
+            .collect();
+        let patterns: Vec<glob::Pattern> = config_paths.iter()
+            .filter(|path| is_glob(&path.to_string_lossy()))
+            .filter_map(|path| glob::Pattern::new(&path.to_string_lossy()).ok())
+            .collect();
+        // Directories that may become service roots, whose package roots depend on `__init__.py`
+        let mut roots: HashSet<PathBuf> = config_paths.iter()
+            .filter(|path| !is_glob(&path.to_string_lossy()))
+            .cloned()
+            .collect();
+
+        let mut input = format!(
+            "{}\n{:?}\n{:?}\n",
+            serde_json::to_string(&self.configs).unwrap_or_default(),
+            self.collision_policy,
+            self.ignore,
+        );
+        for entry in &scan.entries {
+            let is_marker = !entry.is_dir && self.is_marker_file(&entry.path);
+            let parent = entry.path.parent().unwrap_or(&entry.path);
+            if is_marker {
+                let content = scan.read(&entry.path)?;
+                input.push_str(&format!("{} {}\n", entry.path.display(), content_hash(content.as_bytes())));
+                roots.insert(parent.to_path_buf());
+            }
+            if patterns.iter().any(|pattern| pattern.matches_path(&entry.path)) {
+                input.push_str(&format!("{}\n", entry.path.display()));
+                roots.insert(if entry.is_dir { entry.path.clone() } else { parent.to_path_buf() });
+            }
+        }
+        // The package root is the directory of the first `__init__.py` up to three levels down
+        for entry in scan.entries.iter().filter(|e| e.path.file_name().is_some_and(|n| n == "__init__.py")) {
+            if entry.path.ancestors().skip(1).take(3).any(|dir| roots.contains(dir)) {
+                input.push_str(&format!("{}\n", entry.path.display()));
+            }
+        }
+        Ok(content_hash(input.as_bytes()))
+    }
+
+    /// Expand a configured service into one service per matching root directory
+    pub fn expand_config(&self, config: &ServiceConfig, root_path: &Path) -> std::io::Result<Vec<DetectedService>> {
+        self.expand_config_in(config, root_path, None)
+    }
+
+    fn expand_config_in(
+        &self,
+        config: &ServiceConfig,
+        root_path: &Path,
+        scan: Option<&RepositoryScan>,
+    ) -> std::io::Result<Vec<DetectedService>> {
+        let pattern = config.path.to_string_lossy();
+        if !is_glob(&pattern) {
+            return Ok(vec![self.configured_service(config, &config.path, root_path, scan)?]);
+        }
+
+        let pattern = if config.path.is_absolute() {
+            config.path.clone()
+        } else {
+            root_path.join(&config.path)
+        };
+        let matches = glob::glob(&pattern.to_string_lossy()).map_err(|e| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Invalid service path pattern '{}': {}", config.path.display(), e),
+            )
+        })?;
+        let literal_file_name = config.path.file_name()
+            .map(|n| !is_glob(&n.to_string_lossy()))
+            .unwrap_or(false);
+
+        let mut roots = Vec::new();
+        for matched in matches.filter_map(|m| m.ok()) {
+            let root = if matched.is_dir() {
+                matched
+            } else if literal_file_name || self.is_service_marker(&matched, &config.detection) {
+                match matched.parent() {
+                    Some(parent) => parent.to_path_buf(),
+                    None => continue,
+                }
+            } else {
+                continue;
+            };
+            if !roots.contains(&root) {
+                roots.push(root);
+            }
+        }
+        roots.sort();
+
+        roots.iter()
+            .map(|root| self.configured_service(config, root, root_path, scan))
+            .collect()
+    }
+
+    fn configured_service(
+        &self,
+        config: &ServiceConfig,
+        root: &Path,
+        scan_root: &Path,
+        scan: Option<&RepositoryScan>,
+    ) -> std::io::Result<DetectedService> {
+        let resolve = |p: &PathBuf| clean_path(&root.join(p));
+        Ok(DetectedService {
+            name: self.render_name(&config.name, root, scan_root, scan)?,
+            root_path: root.to_path_buf(),
+            package_root: self.find_package_root(root, scan)?,
+            detection_type: ServiceDetectionType::ConfigurationDefined,
+            include_paths: config.include_paths.iter().map(resolve).collect(),
+            exclude_paths: config.exclude_paths.iter().map(resolve).collect(),
+            tags: config.tags.clone(),
+        })
+    }
+
+    /// Fill in the placeholders of a service name template for the service rooted at `root`
+    fn render_name(&self, template: &str, root: &Path, scan_root: &Path, scan: Option<&RepositoryScan>) -> std::io::Result<String> {
+        if !template.contains('{') {
+            return Ok(template.to_string());
+        }
+
+        let dir = file_name_of(root);
+        let parent = root.parent().map(file_name_of).unwrap_or_default();
+        let relative = relative_path_string(root, scan_root)
+            .unwrap_or_else(|| root.display().to_string());
+
+        let mut name = template
+            .replace("{dir}", &dir)
+            .replace("{parent}", &parent)
+            .replace("{path}", &relative);
+        if name.contains("{name}") {
+            let metadata_name = self.extract_package_name(root, scan)?.unwrap_or_else(|| dir.clone());
+            name = name.replace("{name}", &metadata_name);
+        }
+        Ok(name)
+    }
+
+    /// Read the project name from a `pyproject.toml` or `setup.py` in `root`
+    fn extract_package_name(&self, root: &Path, scan: Option<&RepositoryScan>) -> std::io::Result<Option<String>> {
+        let pyproject = root.join("pyproject.toml");
+        if pyproject.is_file() {
+            if let Some(name) = self.extract_package_name_from_pyproject(&pyproject, scan)? {
+                return Ok(Some(name));
+            }
+        }
+        let setup_py = root.join("setup.py");
+        if setup_py.is_file() {
+            return self.extract_package_name_from_setup_py(&setup_py, scan);
+        }
+        Ok(None)
+    }
+
+    fn is_service_marker(&self, path: &Path, rules: &ServiceDetectionRules) -> bool {
+        match path.file_name().and_then(|n| n.to_str()) {
+            Some("setup.py") => rules.detect_setup_py,
+            Some("pyproject.toml") => rules.detect_pyproject,
+            Some(name) => rules.indicator_files.iter().any(|f| f == name),
+            None => false,
+        }
+    }
+
+    fn resolve_collisions(
+        &self,
+        root_path: &Path,
+        configured: Vec<(DetectedService, Option<String>)>,
+        detected: Vec<DetectedService>,
+    ) -> std::io::Result<ServiceDetection> {
+        let mut by_name: BTreeMap<String, Vec<(DetectedService, Option<String>)>> = BTreeMap::new();
+        for (service, alias) in configured.into_iter().chain(detected.into_iter().map(|s| (s, None))) {
+            by_name.entry(service.name.clone()).or_default().push((service, alias));
+        }
+
+        let mut detection = ServiceDetection::default();
+        for (name, claimants) in by_name {
+            if claimants.len() == 1 {
+                detection.services.extend(claimants.into_iter().map(|(s, _)| (name.clone(), s)));
                 continue;
             }
 
-            if let Some(service) = self.detect_service_at_path(path)? {
-                if !services.contains_key(&service.name) {
-                    services.insert(service.name.clone(), service);
+            let paths = claimants.iter().map(|(s, _)| s.root_path.clone()).collect();
+            let mut resolved_names = Vec::new();
+            match self.collision_policy {
+                NameCollisionPolicy::Error => {}
+                NameCollisionPolicy::PathQualified | NameCollisionPolicy::Alias => {
+                    // Aliases apply only under the alias policy. Of the services left without
+                    // one, a lone claimant or a lone configured service keeps the name
+                    let use_aliases = self.collision_policy == NameCollisionPolicy::Alias;
+                    let unaliased: Vec<bool> = claimants.iter()
+                        .filter(|(_, alias)| !use_aliases || alias.is_none())
+                        .map(|(s, _)| s.detection_type == ServiceDetectionType::ConfigurationDefined)
+                        .collect();
+                    let configured = unaliased.iter().filter(|c| **c).count();
+                    for (mut service, alias) in claimants {
+                        let is_configured = service.detection_type == ServiceDetectionType::ConfigurationDefined;
+                        service.name = match alias.filter(|_| use_aliases) {
+                            Some(alias) => alias,
+                            None if unaliased.len() == 1 || (configured == 1 && is_configured) => name.clone(),
+                            None => qualified_name(&name, &service.root_path, root_path),
+                        };
+                        if let Some(existing) = detection.services.get(&service.name) {
+                            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!(
+                                "services at {} and {} both resolve to the name '{}'",
+                                existing.root_path.display(),
+                                service.root_path.display(),
+                                service.name,
+                            )));
+                        }
+                        resolved_names.push(service.name.clone());
+                        detection.services.insert(service.name.clone(), service);
+                    }
                 }
             }
+            detection.collisions.push(ServiceCollision { name, paths, resolved_names });
+        }
+
+        if self.collision_policy == NameCollisionPolicy::Error && !detection.collisions.is_empty() {
+            let messages: Vec<String> = detection.collisions.iter().map(|c| c.to_string()).collect();
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                messages.join("; "),
+            ));
         }
 
-        Ok(services)
+        Ok(detection)
     }
 
-    fn detect_service_at_path(&self, path: &Path) -> std::io::Result<Option<DetectedService>> {
+    fn detect_service_at_path(&self, path: &Path, scan: Option<&RepositoryScan>) -> std::io::Result<Option<DetectedService>> {
         let file_name = match path.file_name().and_then(|n| n.to_str()) {
             Some(name) => name,
             None => return Ok(None),
@@ -58,34 +353,40 @@ impl ServiceDetector {
         match file_name {
             "setup.py" => {
                 let root_path = path.parent().unwrap_or(path).to_path_buf();
-                let name = self.extract_package_name_from_setup_py(path)?
+                let name = self.extract_package_name_from_setup_py(path, scan)?
                     .unwrap_or_else(|| root_path.file_name().unwrap().to_string_lossy().into_owned());
                 
                 Ok(Some(DetectedService {
                     name,
                     root_path: root_path.clone(),
-                    package_root: self.find_package_root(&root_path)?,
+                    package_root: self.find_package_root(&root_path, scan)?,
                     detection_type: ServiceDetectionType::SetupPy,
+                    include_paths: Vec::new(),
+                    exclude_paths: Vec::new(),
+                    tags: Vec::new(),
                 }))
             }
             "pyproject.toml" => {
                 let root_path = path.parent().unwrap_or(path).to_path_buf();
-                let name = self.extract_package_name_from_pyproject(path)?
+                let name = self.extract_package_name_from_pyproject(path, scan)?
                     .unwrap_or_else(|| root_path.file_name().unwrap().to_string_lossy().into_owned());
 
                 Ok(Some(DetectedService {
                     name,
                     root_path: root_path.clone(),
-                    package_root: self.find_package_root(&root_path)?,
+                    package_root: self.find_package_root(&root_path, scan)?,
                     detection_type: ServiceDetectionType::PyprojectToml,
+                    include_paths: Vec::new(),
+                    exclude_paths: Vec::new(),
+                    tags: Vec::new(),
                 }))
             }
             _ => Ok(None),
         }
     }
 
-    fn extract_package_name_from_setup_py(&self, path: &Path) -> std::io::Result<Option<String>> {
-        let content = std::fs::read_to_string(path)?;
+    fn extract_package_name_from_setup_py(&self, path: &Path, scan: Option<&RepositoryScan>) -> std::io::Result<Option<String>> {
+        let content = read(path, scan)?;
         
         for line in content.lines() {
             let line = line.trim();
@@ -124,8 +425,8 @@ impl ServiceDetector {
         Ok(None)
     }
 
-    fn extract_package_name_from_pyproject(&self, path: &Path) -> std::io::Result<Option<String>> {
-        let content = std::fs::read_to_string(path)?;
+    fn extract_package_name_from_pyproject(&self, path: &Path, scan: Option<&RepositoryScan>) -> std::io::Result<Option<String>> {
+        let content = read(path, scan)?;
         let toml: toml::Value = toml::from_str(&content).map_err(|e| {
             std::io::Error::new(std::io::ErrorKind::InvalidData, e)
         })?;
@@ -136,10 +437,18 @@ impl ServiceDetector {
             .map(String::from))
     }
 
-    fn find_package_root(&self, dir: &Path) -> std::io::Result<PathBuf> {
-        // Look for __init__.py to determine package root
+    fn find_package_root(&self, dir: &Path, scan: Option<&RepositoryScan>) -> std::io::Result<PathBuf> {
+        // Look for __init__.py to determine package root, limiting depth to avoid searching too deep
+        let max_depth = dir.components().count() + 3;
+        if let Some(scan) = scan.filter(|scan| dir.starts_with(&scan.root)) {
+            let init = scan.entries_under(dir).iter()
+                .find(|e| e.path.file_name().is_some_and(|n| n == "__init__.py")
+                    && e.path.components().count() <= max_depth);
+            return Ok(init.and_then(|e| e.path.parent()).unwrap_or(dir).to_path_buf());
+        }
+
         for entry in WalkDir::new(dir)
-            .max_depth(3)  // Limit depth to avoid searching too deep
+            .max_depth(3)
             .into_iter()
             .filter_map(|e| e.ok())
         {
@@ -150,8 +459,35 @@ impl ServiceDetector {
         Ok(dir.to_path_buf())
     }
 
-    fn is_path_in_existing_service(&self, _path: &Path, _services: &HashMap<String, DetectedService>) -> bool {
+    fn is_path_in_existing_service(&self, _path: &Path, _services: &[DetectedService]) -> bool {
         // Don't skip nested services
         false
     }
-} 
\ No newline at end of file
+}
+
+/// Read `path` from the scan when it holds its contents, or from disk
+fn read(path: &Path, scan: Option<&RepositoryScan>) -> std::io::Result<String> {
+    match scan {
+        Some(scan) => scan.read(path),
+        None => std::fs::read_to_string(path),
+    }
+}
+
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+fn file_name_of(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Qualify `name` with the service root relative to the scanned root, e.g. `api@services/api`
+fn qualified_name(name: &str, service_root: &Path, scan_root: &Path) -> String {
+    match relative_path_string(service_root, scan_root) {
+        Some(relative) if relative.is_empty() => format!("{}@.", name),
+        Some(relative) => format!("{}@{}", name, relative),
+        None => format!("{}@{}", name, service_root.display()),
+    }
+}
@@ -1,11 +1,12 @@
-use std::path::{PathBuf};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServiceConfig {
-    /// Name of the service
+    /// Name of the service; may use `{dir}`, `{parent}`, `{path}` and `{name}` placeholders
     pub name: String,
-    /// Root path of the service
+    /// Root path of the service, or a glob such as `services/*` or `libs/**/pyproject.toml`
     pub path: PathBuf,
     /// Optional list of additional paths to include
     #[serde(default)]
@@ -16,6 +17,12 @@ pub struct ServiceConfig {
     /// Optional service detection rules
     #[serde(default)]
     pub detection: ServiceDetectionRules,
+    /// Tags impact rules can target
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Name to take instead when the service's name collides under the `alias` policy
+    #[serde(default)]
+    pub alias: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,18 +52,96 @@ impl Default for ServiceDetectionRules {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DetectedService {
     pub name: String,
     pub root_path: PathBuf,
     pub package_root: PathBuf,
     pub detection_type: ServiceDetectionType,
+    /// Additional paths that belong to the service
+    pub include_paths: Vec<PathBuf>,
+    /// Paths under the service root that do not belong to it
+    pub exclude_paths: Vec<PathBuf>,
+    /// Tags from the service configuration
+    pub tags: Vec<String>,
+}
+
+impl DetectedService {
+    /// Whether `path` belongs to this service, honouring include and exclude paths
+    pub fn contains_path(&self, path: &Path) -> bool {
+        if self.exclude_paths.iter().any(|p| path.starts_with(p)) {
+            return false;
+        }
+        path.starts_with(&self.root_path) || self.include_paths.iter().any(|p| path.starts_with(p))
+    }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ServiceDetectionType {
     SetupPy,
     PyprojectToml,
     ConfigurationDefined,
     IndicatorFile(String),
+}
+
+impl std::fmt::Display for ServiceDetectionType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServiceDetectionType::SetupPy => write!(f, "setup.py"),
+            ServiceDetectionType::PyprojectToml => write!(f, "pyproject.toml"),
+            ServiceDetectionType::ConfigurationDefined => write!(f, "configuration"),
+            ServiceDetectionType::IndicatorFile(file) => write!(f, "indicator file {}", file),
+        }
+    }
+}
+
+/// How to resolve detected services that share a name
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NameCollisionPolicy {
+    /// Fail detection when two services share a name
+    Error,
+    /// Suffix colliding names with their path relative to the scanned root
+    #[default]
+    PathQualified,
+    /// Rename colliding services to their configured `alias`; of the others, a lone one keeps
+    /// the name and the rest are path-qualified
+    Alias,
+}
+
+impl std::str::FromStr for NameCollisionPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(NameCollisionPolicy::Error),
+            "path-qualified" => Ok(NameCollisionPolicy::PathQualified),
+            "alias" => Ok(NameCollisionPolicy::Alias),
+            _ => Err("Invalid name collision policy".into()),
+        }
+    }
+}
+
+/// Two or more services that were detected under the same name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServiceCollision {
+    pub name: String,
+    /// Root paths of every service claiming the name, configured services first
+    pub paths: Vec<PathBuf>,
+    /// Names the colliding services ended up with after applying the policy
+    pub resolved_names: Vec<String>,
+}
+
+impl std::fmt::Display for ServiceCollision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let paths: Vec<String> = self.paths.iter().map(|p| p.display().to_string()).collect();
+        write!(f, "service name '{}' is used by multiple paths: {}", self.name, paths.join(", "))
+    }
+}
+
+/// Result of a detection run, including any diagnostics
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ServiceDetection {
+    pub services: HashMap<String, DetectedService>,
+    pub collisions: Vec<ServiceCollision>,
 } 
\ No newline at end of file
@@ -1,27 +1,19 @@
 use super::super::{
     detector::ServiceDetector,
-    models::{ServiceConfig, ServiceDetectionType, ServiceDetectionRules},
+    models::{NameCollisionPolicy, ServiceConfig, ServiceDetectionType, ServiceDetectionRules},
 };
-use std::fs;
 use std::path::{Path, PathBuf};
+use crate::test_support::write_file;
 use tempfile::TempDir;
 
 fn create_test_dir() -> TempDir {
     tempfile::tempdir().unwrap()
 }
 
-fn create_file(root: &Path, path: &str, content: &str) {
-    let full_path = root.join(path);
-    if let Some(parent) = full_path.parent() {
-        fs::create_dir_all(parent).unwrap();
-    }
-    fs::write(full_path, content).unwrap();
-}
-
 #[test]
 fn test_detect_setup_py_service() {
     let temp = create_test_dir();
-    create_file(
+    write_file(
         temp.path(),
         "service1/setup.py",
         r#"from setuptools import setup
@@ -31,7 +23,7 @@ fn test_detect_setup_py_service() {
         )
         "#,
     );
-    create_file(temp.path(), "service1/src/__init__.py", "");
+    write_file(temp.path(), "service1/src/__init__.py", "");
 
     let detector = ServiceDetector::new(vec![]);
     let services = detector.detect_services(temp.path()).unwrap();
@@ -45,7 +37,7 @@ fn test_detect_setup_py_service() {
 #[test]
 fn test_detect_pyproject_toml_service() {
     let temp = create_test_dir();
-    create_file(
+    write_file(
         temp.path(),
         "service2/pyproject.toml",
         r#"
@@ -66,7 +58,7 @@ fn test_detect_pyproject_toml_service() {
 #[test]
 fn test_configured_service() {
     let temp = create_test_dir();
-    create_file(temp.path(), "custom-service/src/__init__.py", "");
+    write_file(temp.path(), "custom-service/src/__init__.py", "");
 
     let config = ServiceConfig {
         name: "custom-service".to_string(),
@@ -74,6 +66,8 @@ fn test_configured_service() {
         include_paths: vec![],
         exclude_paths: vec![],
         detection: ServiceDetectionRules::default(),
+        tags: vec![],
+        alias: None,
     };
 
     let detector = ServiceDetector::new(vec![config]);
@@ -87,7 +81,7 @@ fn test_configured_service() {
 #[test]
 fn test_nested_services() {
     let temp = create_test_dir();
-    create_file(
+    write_file(
         temp.path(),
         "parent/setup.py",
         r#"from setuptools import setup
@@ -97,7 +91,7 @@ fn test_nested_services() {
         )
         "#,
     );
-    create_file(
+    write_file(
         temp.path(),
         "parent/child/setup.py",
         r#"from setuptools import setup
@@ -107,8 +101,8 @@ fn test_nested_services() {
         )
         "#,
     );
-    create_file(temp.path(), "parent/src/__init__.py", "");
-    create_file(temp.path(), "parent/child/src/__init__.py", "");
+    write_file(temp.path(), "parent/src/__init__.py", "");
+    write_file(temp.path(), "parent/child/src/__init__.py", "");
 
     let detector = ServiceDetector::new(vec![]);
     let services = detector.detect_services(temp.path()).unwrap();
@@ -116,4 +110,196 @@ fn test_nested_services() {
     assert_eq!(services.len(), 2);
     assert!(services.contains_key("parent-service"));
     assert!(services.contains_key("child-service"));
-} 
\ No newline at end of file
+} 
+
+fn create_pyproject(root: &Path, dir: &str, name: &str) {
+    write_file(
+        root,
+        &format!("{}/pyproject.toml", dir),
+        &format!("[project]\nname = \"{}\"\n", name),
+    );
+}
+
+#[test]
+fn test_name_collision_path_qualified() {
+    let temp = create_test_dir();
+    create_pyproject(temp.path(), "services/a", "template");
+    create_pyproject(temp.path(), "services/b", "template");
+
+    let detection = ServiceDetector::new(vec![]).detect(temp.path()).unwrap();
+
+    assert_eq!(detection.collisions.len(), 1);
+    let collision = &detection.collisions[0];
+    assert_eq!(collision.name, "template");
+    assert_eq!(collision.paths.len(), 2);
+    assert!(detection.services.contains_key("template@services/a"));
+    assert!(detection.services.contains_key("template@services/b"));
+    assert!(!detection.services.contains_key("template"));
+}
+
+#[test]
+fn test_name_collision_error_policy() {
+    let temp = create_test_dir();
+    create_pyproject(temp.path(), "a", "template");
+    create_pyproject(temp.path(), "b", "template");
+
+    let detector = ServiceDetector::new(vec![])
+        .with_collision_policy(NameCollisionPolicy::Error);
+    let err = detector.detect(temp.path()).unwrap_err();
+
+    assert!(err.to_string().contains("'template'"));
+    assert!(err.to_string().contains(&temp.path().join("a").display().to_string()));
+    assert!(err.to_string().contains(&temp.path().join("b").display().to_string()));
+}
+
+#[test]
+fn test_name_collision_alias_policy() {
+    let temp = create_test_dir();
+    create_pyproject(temp.path(), "a", "template");
+    create_pyproject(temp.path(), "b", "template");
+    create_pyproject(temp.path(), "c", "template");
+    let config = |dir: &str, alias: &str| ServiceConfig {
+        name: "{name}".to_string(),
+        path: temp.path().join(dir),
+        include_paths: vec![],
+        exclude_paths: vec![],
+        detection: ServiceDetectionRules::default(),
+        tags: vec![],
+        alias: Some(alias.to_string()),
+    };
+
+    // b and c take their aliases and a, left alone with the name, keeps it
+    let detector = ServiceDetector::new(vec![config("c", "reports"), config("b", "billing")])
+        .with_collision_policy(NameCollisionPolicy::Alias);
+    let detection = detector.detect(temp.path()).unwrap();
+    assert_eq!(detection.services.len(), 3);
+    assert!(detection.services["template"].root_path.ends_with("a"));
+    assert!(detection.services["billing"].root_path.ends_with("b"));
+    assert!(detection.services["reports"].root_path.ends_with("c"));
+    assert_eq!(detection.collisions.len(), 1);
+    assert_eq!(detection.collisions[0].resolved_names, vec!["reports", "billing", "template"]);
+
+    // Without an alias for c, a and c are path-qualified
+    let detector = ServiceDetector::new(vec![config("b", "billing")])
+        .with_collision_policy(NameCollisionPolicy::Alias);
+    let detection = detector.detect(temp.path()).unwrap();
+    assert_eq!(detection.collisions[0].resolved_names, vec!["billing", "template@a", "template@c"]);
+
+    // Aliases are ignored under other policies
+    let detection = ServiceDetector::new(vec![config("b", "billing")]).detect(temp.path()).unwrap();
+    assert!(detection.services.contains_key("template"));
+    assert!(!detection.services.contains_key("billing"));
+}
+
+#[test]
+fn test_configured_service_collides_with_detected() {
+    let temp = create_test_dir();
+    create_pyproject(temp.path(), "detected", "api");
+    write_file(temp.path(), "configured/src/__init__.py", "");
+
+    let config = ServiceConfig {
+        name: "api".to_string(),
+        path: temp.path().join("configured"),
+        include_paths: vec![],
+        exclude_paths: vec![],
+        detection: ServiceDetectionRules::default(),
+        tags: vec![],
+        alias: None,
+    };
+    let detection = ServiceDetector::new(vec![config]).detect(temp.path()).unwrap();
+
+    assert_eq!(detection.collisions.len(), 1);
+    let api = &detection.services["api"];
+    assert_eq!(api.detection_type, ServiceDetectionType::ConfigurationDefined);
+    assert!(detection.services.contains_key("api@detected"));
+}
+
+#[test]
+fn test_configured_services_sharing_a_name() {
+    let temp = create_test_dir();
+    write_file(temp.path(), "one/app.py", "");
+    write_file(temp.path(), "two/app.py", "");
+    let config = |dir: &str| ServiceConfig {
+        name: "api".to_string(),
+        path: temp.path().join(dir),
+        include_paths: vec![],
+        exclude_paths: vec![],
+        detection: ServiceDetectionRules::default(),
+        tags: vec![],
+        alias: None,
+    };
+
+    // Neither configured service is lost to the other
+    let detection = ServiceDetector::new(vec![config("one"), config("two")]).detect(temp.path()).unwrap();
+    assert_eq!(detection.collisions[0].resolved_names, vec!["api@one", "api@two"]);
+    assert_eq!(detection.services["api@two"].root_path, temp.path().join("two"));
+
+    let detection = ServiceDetector::new(vec![config("one"), config("two")])
+        .with_collision_policy(NameCollisionPolicy::Alias)
+        .detect(temp.path())
+        .unwrap();
+    assert_eq!(detection.services.len(), 2);
+    assert!(detection.services.contains_key("api@two"));
+
+    // Two entries for the same directory cannot be told apart by path either
+    let err = ServiceDetector::new(vec![config("one"), config("one")]).detect(temp.path()).unwrap_err();
+    assert!(err.to_string().contains("both resolve to the name 'api@one'"), "{}", err);
+}
+
+fn glob_config(name: &str, path: &str) -> ServiceConfig {
+    ServiceConfig {
+        name: name.to_string(),
+        path: PathBuf::from(path),
+        include_paths: vec![PathBuf::from("shared")],
+        exclude_paths: vec![PathBuf::from("docs")],
+        detection: ServiceDetectionRules::default(),
+        tags: vec![],
+        alias: None,
+    }
+}
+
+#[test]
+fn test_glob_service_directories() {
+    let temp = create_test_dir();
+    write_file(temp.path(), "services/auth/app.py", "");
+    write_file(temp.path(), "services/billing/app.py", "");
+    write_file(temp.path(), "services/README.md", "");
+
+    let detector = ServiceDetector::new(vec![glob_config("svc-{dir}", "services/*")]);
+    let services = detector.detect_services(temp.path()).unwrap();
+
+    assert_eq!(services.len(), 2);
+    let auth = &services["svc-auth"];
+    assert_eq!(auth.detection_type, ServiceDetectionType::ConfigurationDefined);
+    assert_eq!(auth.root_path, temp.path().join("services/auth"));
+    assert_eq!(auth.include_paths, vec![temp.path().join("services/auth/shared")]);
+    assert_eq!(auth.exclude_paths, vec![temp.path().join("services/auth/docs")]);
+    assert!(services.contains_key("svc-billing"));
+}
+
+#[test]
+fn test_glob_service_marker_files_with_metadata_names() {
+    let temp = create_test_dir();
+    create_pyproject(temp.path(), "libs/core/pkg", "core-lib");
+    create_pyproject(temp.path(), "libs/io", "io-lib");
+    write_file(temp.path(), "libs/unrelated/readme.txt", "");
+
+    let detector = ServiceDetector::new(vec![glob_config("{name}", "libs/**/pyproject.toml")]);
+    let services = detector.detect_services(temp.path()).unwrap();
+
+    assert_eq!(services.len(), 2);
+    assert_eq!(services["core-lib"].root_path, temp.path().join("libs/core/pkg"));
+    assert_eq!(services["io-lib"].root_path, temp.path().join("libs/io"));
+    assert!(services.values().all(|s| s.detection_type == ServiceDetectionType::ConfigurationDefined));
+}
+
+#[test]
+fn test_glob_service_path_template() {
+    let temp = create_test_dir();
+    write_file(temp.path(), "teams/a/api/main.py", "");
+
+    let detector = ServiceDetector::new(vec![glob_config("{parent}-{dir}:{path}", "teams/*/*")]);
+    let services = detector.detect_services(temp.path()).unwrap();
+
+    assert!(services.contains_key("a-api:teams/a/api"));
+}
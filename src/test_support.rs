@@ -0,0 +1,10 @@
+//! Fixtures shared by the unit tests
+
+use std::path::Path;
+
+/// Write `content` to `relative` under `root`, creating parent directories
+pub fn write_file(root: &Path, relative: &str, content: &str) {
+    let path = root.join(relative);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, content).unwrap();
+}
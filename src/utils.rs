@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 pub fn is_python_file(path: &Path) -> bool {
     path.extension()
@@ -7,4 +7,85 @@ pub fn is_python_file(path: &Path) -> bool {
 
 pub fn normalize_path(path: &Path) -> std::path::PathBuf {
     path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
-} 
\ No newline at end of file
+}
+
+/// Lexically resolve `.` and `..` components without touching the filesystem
+pub fn clean_path(path: &Path) -> PathBuf {
+    let mut cleaned = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                let at_start = matches!(
+                    cleaned.components().next_back(),
+                    None | Some(std::path::Component::ParentDir)
+                );
+                if at_start || !cleaned.pop() {
+                    cleaned.push("..");
+                }
+            }
+            other => cleaned.push(other),
+        }
+    }
+    cleaned
+}
+
+/// Render `path` relative to `root` with `/` separators, as used by config globs
+pub fn relative_path_string(path: &Path, root: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    let parts: Vec<String> = relative.components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    Some(parts.join("/"))
+}
+
+/// A set of glob patterns matched against paths relative to a root directory.
+///
+/// A path matches when the pattern matches the path itself or any of its parent
+/// directories, so `build` or `**/node_modules` cover everything beneath them.
+#[derive(Debug, Clone, Default)]
+pub struct PathPatterns {
+    root: PathBuf,
+    patterns: Vec<glob::Pattern>,
+}
+
+impl PathPatterns {
+    pub fn new(root: &Path, patterns: &[String]) -> Result<Self, glob::PatternError> {
+        Ok(Self {
+            root: root.to_path_buf(),
+            patterns: patterns.iter()
+                .map(|p| glob::Pattern::new(p.trim_end_matches('/')))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        if self.patterns.is_empty() {
+            return false;
+        }
+        let relative = match relative_path_string(path, &self.root) {
+            Some(relative) if !relative.is_empty() => relative,
+            _ => return false,
+        };
+
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        let mut prefix = String::new();
+        for part in relative.split('/') {
+            if !prefix.is_empty() {
+                prefix.push('/');
+            }
+            prefix.push_str(part);
+            if self.patterns.iter().any(|p| p.matches_with(&prefix, options)) {
+                return true;
+            }
+        }
+        false
+    }
+}
@@ -1,95 +1,379 @@
+use crate::command::{self, models::{AffectedService, CommandContext, CommandPolicy, RunMode}, runner::CommandRunner};
 use crate::dependency::DependencyGraph;
-use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher};
+use notify::{Config, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::time::{Duration, Instant};
-use std::collections::HashSet;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use crate::service::detector::ServiceDetector;
 use crate::service::models::DetectedService;
+use notify::event::{EventKind, MetadataKind, ModifyKind, RenameMode};
+
+/// How long to wait for an event when no batch is pending
+const IDLE_TIMEOUT: Duration = Duration::from_millis(500);
+/// How long to wait for an event while commands are running, so their exits are noticed
+const BUSY_TIMEOUT: Duration = Duration::from_millis(50);
+/// How long the auto backend waits for the native watcher to report its probe file
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+/// Prefix of the directory the auto backend writes its probe file into. It has to live in the
+/// watched tree, since that tree's filesystem is what may not deliver events; scans should
+/// ignore it and `queue_event` drops its events
+pub const PROBE_DIR_PREFIX: &str = ".pybia-watch-probe";
+
+/// Where file events come from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WatcherBackend {
+    /// The native watcher, switching to polling if it reports no events at startup
+    #[default]
+    Auto,
+    /// The platform's native notifications (inotify, FSEvents, ...)
+    Native,
+    /// Periodically scanning the watched paths
+    Poll,
+}
+
+impl std::str::FromStr for WatcherBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(WatcherBackend::Auto),
+            "native" => Ok(WatcherBackend::Native),
+            "poll" => Ok(WatcherBackend::Poll),
+            _ => Err("Invalid watcher backend".into()),
+        }
+    }
+}
 
 pub struct FileWatcher {
-    watcher: RecommendedWatcher,
+    watcher: Box<dyn Watcher + Send>,
+    backend: WatcherBackend,
+    poll_interval: Duration,
     rx: Receiver<notify::Event>,
-    _tx: Sender<notify::Event>,
+    tx: Sender<notify::Event>,
     dependency_graph: DependencyGraph,
-    last_run: Instant,
+    // Quiet period after the last event before a batch is processed
     debounce_duration: Duration,
-    last_changed_file: Option<PathBuf>,
+    pending: BTreeSet<PathBuf>,
+    last_event: Option<Instant>,
+    last_batch: Vec<PathBuf>,
+    run_mode: RunMode,
+    runner: CommandRunner,
+    // Re-runs service detection when service metadata files change
+    service_detector: Option<(ServiceDetector, PathBuf)>,
 }
 
 impl FileWatcher {
     pub fn new(paths: &[PathBuf], services: HashMap<String, DetectedService>) -> notify::Result<Self> {
-        let (tx, rx) = channel();
-        let tx_clone = tx.clone();
-        let watcher = RecommendedWatcher::new(
-            move |res| {
-                if let Ok(event) = res {
-                    let _ = tx_clone.send(event);
-                }
-            },
-            Config::default(),
-        )?;
+        Self::with_dependency_graph(paths, services, DependencyGraph::new())
+    }
 
-        let mut dependency_graph = DependencyGraph::new();
+    /// Create a watcher whose graph is built from `paths` using a preconfigured `DependencyGraph`
+    pub fn with_dependency_graph(
+        paths: &[PathBuf],
+        services: HashMap<String, DetectedService>,
+        mut dependency_graph: DependencyGraph,
+    ) -> notify::Result<Self> {
         for path in paths {
             dependency_graph.build_from_directory(path, services.clone())?;
         }
+        if let Err(e) = dependency_graph.save_cache() {
+            eprintln!("Warning: failed to write analysis cache: {}", e);
+        }
+        Self::from_dependency_graph(dependency_graph)
+    }
+
+    /// Create a watcher that keeps an already built `DependencyGraph` up to date
+    pub fn from_dependency_graph(dependency_graph: DependencyGraph) -> notify::Result<Self> {
+        let (tx, rx) = channel();
+        let poll_interval = Duration::from_secs(1);
+        let watcher = create_watcher(WatcherBackend::Native, poll_interval, tx.clone())?;
 
         Ok(FileWatcher {
             watcher,
+            backend: WatcherBackend::Auto,
+            poll_interval,
             rx,
-            _tx: tx,
+            tx,
             dependency_graph,
-            last_run: Instant::now(),
             debounce_duration: Duration::from_millis(100),
-            last_changed_file: None,
+            pending: BTreeSet::new(),
+            last_event: None,
+            last_batch: Vec::new(),
+            run_mode: RunMode::default(),
+            runner: CommandRunner::new(CommandPolicy::default()),
+            service_detector: None,
         })
     }
 
+    pub fn with_debounce(mut self, debounce_duration: Duration) -> Self {
+        self.debounce_duration = debounce_duration;
+        self
+    }
+
+    /// Select the event source; must be called before `watch`
+    pub fn with_backend(mut self, backend: WatcherBackend, poll_interval: Duration) -> notify::Result<Self> {
+        self.backend = backend;
+        self.poll_interval = poll_interval;
+        self.watcher = create_watcher(backend, poll_interval, self.tx.clone())?;
+        Ok(self)
+    }
+
+    pub fn with_run_mode(mut self, run_mode: RunMode) -> Self {
+        self.run_mode = run_mode;
+        self
+    }
+
+    pub fn with_command_runner(mut self, runner: CommandRunner) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    /// Re-detect services under `root` when a `setup.py` or `pyproject.toml` changes
+    pub fn with_service_detector(mut self, detector: ServiceDetector, root: PathBuf) -> Self {
+        self.service_detector = Some((detector, root));
+        self
+    }
+
+    pub fn dependency_graph(&self) -> &DependencyGraph {
+        &self.dependency_graph
+    }
+
+    /// Watch `paths` recursively. With the auto backend the native watcher is probed once
+    /// here; a watcher that stops delivering events later is not detected
     pub fn watch(&mut self, paths: &[PathBuf]) -> notify::Result<()> {
         for path in paths {
             self.watcher.watch(path, RecursiveMode::Recursive)?;
         }
+
+        if self.backend == WatcherBackend::Auto && !paths.is_empty() && !self.native_events_arrive(&paths[0])? {
+            eprintln!(
+                "No events from the native file watcher; polling every {}ms instead",
+                self.poll_interval.as_millis()
+            );
+            self.watcher = create_watcher(WatcherBackend::Poll, self.poll_interval, self.tx.clone())?;
+            self.backend = WatcherBackend::Poll;
+            for path in paths {
+                self.watcher.watch(path, RecursiveMode::Recursive)?;
+            }
+        }
         Ok(())
     }
 
+    /// The backend in use, which for `Auto` becomes `Poll` after a fallback
+    pub fn backend(&self) -> WatcherBackend {
+        self.backend
+    }
+
+    /// Write a probe file into a directory under `dir` and wait for the watcher to report it.
+    /// The directory is removed again before watching starts, also on errors
+    fn native_events_arrive(&mut self, dir: &Path) -> notify::Result<bool> {
+        let Ok(probe_dir) = ProbeDir::create(dir) else {
+            // Nothing to probe with in a read-only tree; trust the native watcher
+            return Ok(true);
+        };
+        std::fs::write(probe_dir.path.join("probe"), b"")?;
+        let deadline = Instant::now() + PROBE_TIMEOUT;
+        let mut seen = false;
+        while !seen {
+            let Ok(event) = self.rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) else {
+                break;
+            };
+            seen = event.paths.iter().any(|p| is_probe_path(p));
+            self.queue_event(event);
+        }
+        Ok(seen)
+    }
+
+    /// Services affected by any file in the last processed batch
     pub fn get_affected_services(&self) -> Vec<(&str, &Path)> {
-        self.last_changed_file.as_ref()
-            .map(|f| self.dependency_graph.get_affected_services(f))
-            .unwrap_or_default()
-    }
-
-    pub fn handle_events(&mut self, command: &[String]) -> notify::Result<()> {
-        let mut processed_paths = HashSet::new();
-
-        while let Ok(event) = self.rx.try_recv() {
-            for path in event.paths {
-                if !processed_paths.contains(&path) {
-                    processed_paths.insert(path.clone());
-                    println!("\nChanged path: {}", path.display());
-                    
-                    if let Err(e) = self.run_command(command, &path) {
-                        eprintln!("Failed to run command: {}", e);
-                    }
+        let mut services: BTreeMap<&str, &Path> = BTreeMap::new();
+        for file in &self.last_batch {
+            services.extend(self.dependency_graph.get_affected_services(file));
+        }
+        services.into_iter().collect()
+    }
+
+    /// The affected services and changed files of the last processed batch
+    pub fn command_context(&self) -> CommandContext {
+        CommandContext {
+            services: self.get_affected_services().into_iter()
+                .map(|(name, path)| AffectedService { name: name.to_string(), path: path.to_path_buf() })
+                .collect(),
+            changed_files: self.last_batch.clone(),
+        }
+    }
+
+    /// Files changed in the last processed batch
+    pub fn last_batch(&self) -> &[PathBuf] {
+        &self.last_batch
+    }
+
+    /// Wait briefly for file events and, once no event has arrived for the debounce
+    /// window, start the command for the whole batch. Returns whether a batch was started.
+    pub fn handle_events(&mut self, command: &[String]) -> notify::Result<bool> {
+        if let Err(e) = self.runner.poll() {
+            eprintln!("Failed to run command: {}", e);
+        }
+
+        let mut timeout = match self.last_event {
+            Some(last) => self.debounce_duration.saturating_sub(last.elapsed()),
+            None => IDLE_TIMEOUT,
+        };
+        if !self.runner.is_idle() {
+            timeout = timeout.min(BUSY_TIMEOUT);
+        }
+        if let Ok(event) = self.rx.recv_timeout(timeout) {
+            self.queue_event(event);
+            while let Ok(event) = self.rx.try_recv() {
+                self.queue_event(event);
+            }
+        }
+
+        if !self.take_ready_batch(Instant::now()) {
+            return Ok(false);
+        }
+        for path in &self.last_batch {
+            println!("\nChanged path: {}", path.display());
+        }
+        let invocations = command::invocations(command, &self.command_context(), self.run_mode);
+        if let Err(e) = self.runner.submit(invocations) {
+            eprintln!("Failed to run command: {}", e);
+        }
+        Ok(true)
+    }
+
+    /// Kill any running commands, e.g. before exiting
+    pub fn shutdown(&mut self) {
+        self.runner.terminate();
+    }
+
+    /// Apply a relevant event to the graph and add its paths to the pending batch
+    pub fn queue_event(&mut self, event: notify::Event) {
+        if !is_relevant(&event.kind) {
+            return;
+        }
+        let paths: Vec<PathBuf> = event.paths.iter()
+            .filter(|path| !self.dependency_graph.is_ignored(path))
+            .filter(|path| !is_probe_path(path))
+            // A directory's modification time changes with its listing, and the entries
+            // added or removed report themselves
+            .filter(|path| !(is_modification(&event.kind) && path.is_dir()))
+            .cloned()
+            .collect();
+        if paths.is_empty() {
+            return;
+        }
+        let event = notify::Event { paths: paths.clone(), ..event };
+
+        if let Err(e) = self.apply_event(&event) {
+            eprintln!("Failed to update dependency graph: {}", e);
+        }
+        self.pending.extend(paths);
+        self.last_event = Some(Instant::now());
+    }
+
+    /// Move the pending paths into `last_batch` if the quiet window has passed by `now`
+    pub fn take_ready_batch(&mut self, now: Instant) -> bool {
+        let Some(last) = self.last_event else {
+            return false;
+        };
+        if now.duration_since(last) < self.debounce_duration {
+            return false;
+        }
+        self.last_event = None;
+        self.last_batch = std::mem::take(&mut self.pending).into_iter().collect();
+        !self.last_batch.is_empty()
+    }
+
+    /// Update the dependency graph and service map for a file system event
+    pub fn apply_event(&mut self, event: &notify::Event) -> std::io::Result<()> {
+        match (&event.kind, event.paths.as_slice()) {
+            (EventKind::Access(_), _) => return Ok(()),
+            (EventKind::Modify(ModifyKind::Name(RenameMode::Both)), [from, to]) => {
+                self.dependency_graph.rename_file(from, to)?;
+            }
+            (EventKind::Remove(_), paths) | (EventKind::Modify(ModifyKind::Name(RenameMode::From)), paths) => {
+                for path in paths {
+                    self.dependency_graph.remove_file(path)?;
+                }
+            }
+            (_, paths) => {
+                for path in paths {
+                    self.dependency_graph.update_file(path)?;
                 }
             }
         }
 
+        // Directories carry services in or out with them only when they hold a marker file or
+        // contain a service root; other directory churn, such as `__pycache__`, is skipped
+        if let Some((detector, root)) = &self.service_detector {
+            let services_changed = event.paths.iter().any(|path| {
+                detector.affects_detection(path, root)
+                    || self.dependency_graph.services().values().any(|s| s.root_path.starts_with(path))
+            });
+            if services_changed {
+                let detection = detector.detect(root)?;
+                self.dependency_graph.set_services(detection.services);
+            }
+        }
         Ok(())
     }
+}
 
-    fn run_command(&mut self, command: &[String], changed_file: &Path) -> std::io::Result<bool> {
-        let now = Instant::now();
-        if now.duration_since(self.last_run) < self.debounce_duration {
-            return Ok(false);
-        }
-        self.last_run = now;
-        self.last_changed_file = Some(changed_file.to_path_buf());
+/// Whether an event can change file contents or the set of files
+/// A directory for the auto backend's probe file, removed again when dropped
+struct ProbeDir {
+    path: PathBuf,
+}
+
+impl ProbeDir {
+    fn create(parent: &Path) -> std::io::Result<Self> {
+        let path = parent.join(format!("{}-{}", PROBE_DIR_PREFIX, std::process::id()));
+        std::fs::create_dir_all(&path)?;
+        Ok(Self { path })
+    }
+}
+
+impl Drop for ProbeDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
 
-        let status = std::process::Command::new(&command[0])
-            .args(&command[1..])
-            .status()?;
+/// Whether `path` lies in a probe directory of the auto backend
+fn is_probe_path(path: &Path) -> bool {
+    path.components().any(|c| c.as_os_str().to_string_lossy().starts_with(PROBE_DIR_PREFIX))
+}
 
-        Ok(status.success())
+fn is_relevant(kind: &EventKind) -> bool {
+    match kind {
+        EventKind::Access(_) | EventKind::Other => false,
+        // Polling reports content changes as a new modification time
+        EventKind::Modify(ModifyKind::Metadata(kind)) => *kind == MetadataKind::WriteTime,
+        EventKind::Any | EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) => true,
     }
-} 
\ No newline at end of file
+}
+
+/// A change to an existing path, as opposed to creating, removing or renaming it
+fn is_modification(kind: &EventKind) -> bool {
+    matches!(kind, EventKind::Modify(kind) if !matches!(kind, ModifyKind::Name(_)))
+}
+
+fn create_watcher(
+    backend: WatcherBackend,
+    poll_interval: Duration,
+    tx: Sender<notify::Event>,
+) -> notify::Result<Box<dyn Watcher + Send>> {
+    let handler = move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res {
+            let _ = tx.send(event);
+        }
+    };
+    Ok(match backend {
+        WatcherBackend::Auto | WatcherBackend::Native => Box::new(RecommendedWatcher::new(handler, Config::default())?),
+        WatcherBackend::Poll => Box::new(PollWatcher::new(handler, Config::default().with_poll_interval(poll_interval))?),
+    })
+}
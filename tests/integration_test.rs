@@ -1,11 +1,9 @@
 use file_watcher::{
-    service::{
-        detector::ServiceDetector,
-        models::{ServiceConfig, ServiceDetectionRules},
-    },
+    service::detector::ServiceDetector,
     dependency::DependencyGraph,
 };
-use std::path::PathBuf;
+use std::path::Path;
+use std::process::Output;
 use tempfile::TempDir;
 
 fn setup_test_project() -> TempDir {
@@ -45,6 +43,15 @@ fn setup_test_project() -> TempDir {
     temp
 }
 
+/// Run the pybia binary in `dir`
+fn pybia(dir: &Path, args: &[&str]) -> Output {
+    std::process::Command::new(env!("CARGO_BIN_EXE_file-watcher"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
 #[test]
 fn test_full_service_detection_and_dependencies() {
     let temp = setup_test_project();
@@ -68,4 +75,274 @@ fn test_full_service_detection_and_dependencies() {
     
     assert_eq!(affected.len(), 1);
     assert_eq!(affected[0].0, "service1");
-} 
\ No newline at end of file
+}
+
+#[test]
+fn test_watcher_applies_events_to_graph_and_services() {
+    use file_watcher::watcher::FileWatcher;
+    use notify::event::{CreateKind, EventKind, ModifyKind, RenameMode};
+
+    let temp = setup_test_project();
+    let root = temp.path().to_path_buf();
+    let detector = ServiceDetector::new(vec![]);
+    let services = detector.detect_services(&root).unwrap();
+    let mut watcher = FileWatcher::new(std::slice::from_ref(&root), services)
+        .unwrap()
+        .with_service_detector(ServiceDetector::new(vec![]), root.clone());
+
+    // A new service appears
+    std::fs::create_dir_all(root.join("service3")).unwrap();
+    std::fs::write(root.join("service3/pyproject.toml"), "[project]\nname = \"service3\"\n").unwrap();
+    std::fs::write(root.join("service3/app.py"), "import service1.src\n").unwrap();
+    watcher.apply_event(&notify::Event::new(EventKind::Create(CreateKind::Folder)).add_path(root.join("service3"))).unwrap();
+
+    let mut affected: Vec<&str> = watcher.dependency_graph()
+        .get_affected_services(&root.join("service1/src/__init__.py"))
+        .into_iter().map(|(name, _)| name).collect();
+    affected.sort();
+    assert_eq!(affected, vec!["service1", "service3"]);
+
+    // ...and is renamed away
+    std::fs::rename(root.join("service3/app.py"), root.join("service3/main.py")).unwrap();
+    watcher.apply_event(
+        &notify::Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path(root.join("service3/app.py"))
+            .add_path(root.join("service3/main.py")),
+    ).unwrap();
+    let affected = watcher.dependency_graph().get_affected_files(&root.join("service1/src/__init__.py"));
+    assert!(affected.contains(&root.join("service3/main.py")));
+    assert!(!affected.contains(&root.join("service3/app.py")));
+
+    // Directories without marker files do not trigger detection, so a marker written without
+    // an event of its own is only picked up once a directory holding it is reported
+    std::fs::create_dir_all(root.join("service4")).unwrap();
+    std::fs::write(root.join("service4/pyproject.toml"), "[project]\nname = \"service4\"\n").unwrap();
+    std::fs::create_dir_all(root.join("service1/src/__pycache__")).unwrap();
+    watcher.apply_event(
+        &notify::Event::new(EventKind::Create(CreateKind::Folder)).add_path(root.join("service1/src/__pycache__")),
+    ).unwrap();
+    assert!(!watcher.dependency_graph().services().contains_key("service4"));
+    watcher.apply_event(&notify::Event::new(EventKind::Create(CreateKind::Folder)).add_path(root.join("service4"))).unwrap();
+    assert!(watcher.dependency_graph().services().contains_key("service4"));
+}
+
+#[test]
+fn test_watcher_batches_events() {
+    use file_watcher::watcher::FileWatcher;
+    use notify::event::{AccessKind, DataChange, EventKind, MetadataKind, ModifyKind};
+    use std::time::{Duration, Instant};
+
+    let temp = setup_test_project();
+    let root = temp.path().to_path_buf();
+    std::fs::create_dir_all(root.join("service2/app")).unwrap();
+    std::fs::write(root.join("service2/app/main.py"), "").unwrap();
+    let services = ServiceDetector::new(vec![]).detect_services(&root).unwrap();
+    let mut watcher = FileWatcher::new(std::slice::from_ref(&root), services)
+        .unwrap()
+        .with_debounce(Duration::from_millis(50));
+
+    let modify = |path: &str| {
+        notify::Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Content))).add_path(root.join(path))
+    };
+    watcher.queue_event(modify("service1/src/__init__.py"));
+    watcher.queue_event(modify("service1/src/__init__.py"));
+    watcher.queue_event(modify("service2/app/main.py"));
+    watcher.queue_event(notify::Event::new(EventKind::Access(AccessKind::Any)).add_path(root.join("service1/setup.py")));
+    watcher.queue_event(
+        notify::Event::new(EventKind::Modify(ModifyKind::Metadata(MetadataKind::Any))).add_path(root.join("service1/setup.py")),
+    );
+
+    // Nothing is processed until the quiet window has passed
+    assert!(!watcher.take_ready_batch(Instant::now()));
+    assert!(watcher.take_ready_batch(Instant::now() + Duration::from_secs(1)));
+    assert_eq!(watcher.last_batch(), [root.join("service1/src/__init__.py"), root.join("service2/app/main.py")]);
+
+    let affected: Vec<&str> = watcher.get_affected_services().into_iter().map(|(name, _)| name).collect();
+    assert_eq!(affected, vec!["service1", "service2"]);
+
+    // The batch is consumed
+    assert!(!watcher.take_ready_batch(Instant::now() + Duration::from_secs(1)));
+}
+
+#[test]
+fn test_polling_backend_batches_changes() {
+    use file_watcher::watcher::{FileWatcher, WatcherBackend};
+    use std::time::{Duration, Instant};
+
+    let temp = setup_test_project();
+    let root = temp.path().to_path_buf();
+    let services = ServiceDetector::new(vec![]).detect_services(&root).unwrap();
+    let mut watcher = FileWatcher::new(std::slice::from_ref(&root), services)
+        .unwrap()
+        .with_debounce(Duration::from_millis(20))
+        .with_backend(WatcherBackend::Poll, Duration::from_millis(50))
+        .unwrap();
+    watcher.watch(std::slice::from_ref(&root)).unwrap();
+    assert_eq!(watcher.backend(), WatcherBackend::Poll);
+
+    std::thread::sleep(Duration::from_millis(100));
+    std::fs::write(root.join("service1/src/api.py"), "x = 1\n").unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !watcher.handle_events(&[]).unwrap() {
+        assert!(Instant::now() < deadline, "polling backend reported no changes");
+    }
+    assert_eq!(watcher.last_batch(), [root.join("service1/src/api.py")]);
+}
+
+#[test]
+fn test_auto_backend_probe_leaves_no_trace() {
+    use file_watcher::watcher::{FileWatcher, WatcherBackend, PROBE_DIR_PREFIX};
+    use std::time::Duration;
+
+    let temp = setup_test_project();
+    let root = temp.path().to_path_buf();
+    let services = ServiceDetector::new(vec![]).detect_services(&root).unwrap();
+    let mut watcher = FileWatcher::new(std::slice::from_ref(&root), services)
+        .unwrap()
+        .with_debounce(Duration::from_millis(20))
+        .with_backend(WatcherBackend::Auto, Duration::from_millis(50))
+        .unwrap();
+    watcher.watch(std::slice::from_ref(&root)).unwrap();
+
+    // The probe directory is gone and its events never make up a batch
+    let leftovers: Vec<_> = std::fs::read_dir(&root).unwrap()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().starts_with(PROBE_DIR_PREFIX))
+        .collect();
+    assert!(leftovers.is_empty(), "{:?}", leftovers);
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(!watcher.handle_events(&[]).unwrap());
+    assert!(!watcher.handle_events(&[]).unwrap());
+}
+
+#[test]
+fn test_trigger_files_outside_source_roots_are_watched() {
+    use file_watcher::config;
+    use file_watcher::watcher::{FileWatcher, WatcherBackend};
+    use std::time::{Duration, Instant};
+
+    let temp = setup_test_project();
+    let root = temp.path().to_path_buf();
+    std::fs::write(
+        root.join("pybia.toml"),
+        "source_roots = [\"service1/src\"]\ntrigger_files = [\".github/workflows/*\"]\n",
+    ).unwrap();
+    std::fs::create_dir_all(root.join(".github/workflows")).unwrap();
+    let config = config::load(&root.join("pybia.toml")).unwrap();
+    let paths = config.scan_paths();
+    assert_eq!(paths, vec![root.clone()]);
+
+    let services = ServiceDetector::new(vec![]).detect_services(&root).unwrap();
+    let mut graph = DependencyGraph::new()
+        .with_source_roots(config.source_roots.clone())
+        .with_trigger_files(&config.root, &config.trigger_files)
+        .unwrap();
+    graph.build_from_directory(&paths[0], services).unwrap();
+    let mut watcher = FileWatcher::from_dependency_graph(graph)
+        .unwrap()
+        .with_debounce(Duration::from_millis(20))
+        .with_backend(WatcherBackend::Poll, Duration::from_millis(50))
+        .unwrap();
+    watcher.watch(&paths).unwrap();
+
+    std::thread::sleep(Duration::from_millis(100));
+    std::fs::write(root.join(".github/workflows/ci.yml"), "on: push\n").unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !watcher.handle_events(&[]).unwrap() {
+        assert!(Instant::now() < deadline, "the trigger file was not watched");
+    }
+    let affected: Vec<&str> = watcher.get_affected_services().into_iter().map(|(name, _)| name).collect();
+    assert_eq!(affected, vec!["service1", "service2"]);
+}
+
+#[test]
+fn test_worktree_checks_out_a_revision_and_cleans_up() {
+    use file_watcher::git::{self, Worktree};
+    use std::process::Command;
+
+    let temp = tempfile::tempdir().unwrap();
+    let repo = temp.path();
+    let run = |args: &[&str]| {
+        let status = Command::new("git").arg("-C").arg(repo).args(args).status().unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    };
+    run(&["init", "--quiet"]);
+    std::fs::write(repo.join("app.py"), "import base\n").unwrap();
+    run(&["add", "app.py"]);
+    run(&["-c", "user.name=test", "-c", "user.email=test@example.com", "commit", "--quiet", "-m", "base"]);
+    std::fs::write(repo.join("app.py"), "import head\n").unwrap();
+
+    let toplevel = git::toplevel(repo).unwrap();
+    let path = {
+        let worktree = Worktree::checkout(&toplevel, "HEAD").unwrap();
+        assert_eq!(std::fs::read_to_string(worktree.path.join("app.py")).unwrap(), "import base\n");
+        worktree.path.clone()
+    };
+    assert!(!path.exists());
+    assert!(Worktree::checkout(&toplevel, "no-such-revision").is_err());
+}
+
+#[test]
+fn test_changed_files_since_a_revision() {
+    use file_watcher::git;
+    use std::process::Command;
+
+    let temp = tempfile::tempdir().unwrap();
+    let repo = temp.path();
+    let run = |args: &[&str]| {
+        let status = Command::new("git").arg("-C").arg(repo).args(args).status().unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    };
+    run(&["init", "--quiet"]);
+    std::fs::write(repo.join("app.py"), "import base\n").unwrap();
+    std::fs::write(repo.join("models.py"), "").unwrap();
+    run(&["add", "."]);
+    run(&["-c", "user.name=test", "-c", "user.email=test@example.com", "commit", "--quiet", "-m", "base"]);
+    std::fs::write(repo.join("app.py"), "import head\n").unwrap();
+    std::fs::write(repo.join("new.py"), "").unwrap();
+
+    let toplevel = git::toplevel(repo).unwrap();
+    let changed = git::changed_files(repo, "HEAD").unwrap();
+    assert_eq!(changed, vec![toplevel.join("app.py"), toplevel.join("new.py")]);
+}
+
+#[test]
+fn test_relative_config_path() {
+    let temp = setup_test_project();
+    std::fs::write(temp.path().join("pybia.toml"), "").unwrap();
+
+    let output = pybia(temp.path(), &["explain", "--config", "pybia.toml", "--no-cache", "service1/src/__init__.py"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("  service1 ("), "{}", stdout);
+}
+
+#[test]
+fn test_flags_before_the_subcommand() {
+    let temp = setup_test_project();
+    std::fs::write(temp.path().join("ci.toml"), "").unwrap();
+
+    for args in [["--config", "ci.toml", "validate"], ["validate", "--config", "ci.toml"]] {
+        let output = pybia(temp.path(), &args);
+        assert!(output.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+        assert!(String::from_utf8(output.stderr).unwrap().starts_with("ci.toml:"));
+    }
+
+    let output = pybia(temp.path(), &["--paths", ".", "--no-cache", "explain", "service1/src/__init__.py"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8(output.stdout).unwrap().contains("  service1 ("));
+}
+
+#[test]
+fn test_commands_query_a_saved_graph() {
+    let temp = setup_test_project();
+    let output = pybia(temp.path(), &["snapshot", "--no-cache", "build/pybia-graph.json"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    // Only the saved graph still knows service1
+    std::fs::remove_file(temp.path().join("service1/setup.py")).unwrap();
+
+    let output = pybia(temp.path(), &["--graph", "build/pybia-graph.json", "explain", "service1/src/__init__.py"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8(output.stdout).unwrap().contains("  service1 ("));
+}